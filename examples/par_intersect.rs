@@ -1,5 +1,5 @@
 use anyhow::Result;
-use embree4_rs::{geometry::TriangleMeshGeometry, Device, Scene, SceneOptions};
+use embree4_rs::{geometry::TriangleMeshGeometry, Device, Scene, SceneFlags, SceneOptions};
 use glam::Vec3;
 use rand::{rngs::StdRng, Rng, SeedableRng};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
@@ -32,7 +32,11 @@ pub fn main() -> Result<()> {
         &device,
         SceneOptions {
             build_quality: embree4_sys::RTCBuildQuality::HIGH,
-            flags: embree4_sys::RTCSceneFlags::ROBUST,
+            flags: SceneFlags {
+                robust: true,
+                ..Default::default()
+            },
+            ..Default::default()
         },
     )?;
     scene.attach_geometry(&mesh)?;
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use embree4_rs::{
+    geometry::{Geometry, TriangleMeshGeometry},
+    Device, Scene, SceneOptions,
+};
+use embree4_sys::RTCBuildQuality;
+
+/// Compares the per-frame cost of refitting a deforming mesh with rebuilding its BVH.
+pub fn main() -> Result<()> {
+    let device = Device::try_new(None)?;
+
+    // A 1000 x 1000 grid of quads, split into two million triangles
+    let n = 1000u32;
+    let mut vertices = Vec::with_capacity(((n + 1) * (n + 1)) as usize);
+    for y in 0..=n {
+        for x in 0..=n {
+            vertices.push((x as f32, y as f32, 0.0));
+        }
+    }
+    let mut indices = Vec::with_capacity((2 * n * n) as usize);
+    for y in 0..n {
+        for x in 0..n {
+            let i = y * (n + 1) + x;
+            indices.push((i, i + 1, i + n + 2));
+            indices.push((i, i + n + 2, i + n + 1));
+        }
+    }
+
+    for quality in [RTCBuildQuality::MEDIUM, RTCBuildQuality::REFIT] {
+        let mut mesh = TriangleMeshGeometry::try_new(&device, &vertices, &indices)?;
+        mesh.set_build_quality(quality);
+        mesh.commit(&device)?;
+
+        let scene = Scene::try_new(&device, SceneOptions::dynamic())?;
+        scene.attach_geometry(&mesh)?;
+        scene.commit()?;
+
+        let frames = 30;
+        let mut total = Duration::ZERO;
+        for frame in 0..frames {
+            // Ripple the grid along z. No query of `scene` runs while the vertices are modified
+            let phase = frame as f32 * 0.2;
+            for (vertex, original) in unsafe { mesh.vertices_mut(&device, 0) }?
+                .iter_mut()
+                .zip(&vertices)
+            {
+                vertex[2] = (0.05 * original.0 + phase).sin() * (0.05 * original.1).cos();
+            }
+            mesh.update(&device)?;
+
+            let start = Instant::now();
+            scene.refit()?;
+            total += start.elapsed();
+        }
+
+        println!(
+            "{:?}: {:.2} ms per frame",
+            quality,
+            total.as_secs_f64() * 1000.0 / frames as f64
+        );
+    }
+
+    Ok(())
+}
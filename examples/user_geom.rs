@@ -1,5 +1,3 @@
-#![feature(pointer_byte_offsets)]
-
 use std::f32::consts::PI;
 
 use embree4_rs::{
@@ -16,12 +14,12 @@ fn main() -> Result<()> {
     let device = Device::try_new(config)?;
     let scene = Scene::try_new(&device, Default::default())?;
 
-    // For user geometry, the underlying data must outlive the scene.
+    // The geometry shares ownership of the sphere with the scene.
     let sphere = Sphere {
         center: vec3(0.0, 0.0, 5.0),
         radius: 1.0,
     };
-    let geom = UserGeometry::try_new(&device, &sphere)?;
+    let geom = UserGeometry::try_new(&device, sphere)?;
 
     scene.attach_geometry(&geom)?;
     let scene = scene.commit()?;
@@ -33,7 +31,7 @@ fn main() -> Result<()> {
     let width = 4096;
     let height = 4096;
 
-    let cam_dist = sphere.center.z;
+    let cam_dist = geom.data().center.z;
 
     let rays = width * height;
     let mut hits = 0;
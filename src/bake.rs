@@ -0,0 +1,316 @@
+use std::sync::atomic::AtomicBool;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    batch::{is_cancelled, CANCEL_CHUNK_SIZE},
+    math::{add, cosine_hemisphere, hammersley, length, normalize, offset_origin},
+    BatchStatus, CommittedScene,
+};
+
+/// The result of a bent normal computation, see [`CommittedScene::bent_normal`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BentNormal {
+    /// The normalized mean direction of all unoccluded samples.
+    /// Equals the normalized input normal if every traced sample was occluded.
+    pub normal: (f32, f32, f32),
+    /// The fraction of occluded samples in `[0, 1]`, among the traced samples.
+    pub occlusion: f32,
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Computes the bent normal and ambient occlusion at a surface point.
+    ///
+    /// Traces `sample_count` cosine-weighted occlusion rays over the hemisphere around `normal`,
+    /// up to a distance of `radius`. The sample directions are generated deterministically, so
+    /// repeated bakes of the same point produce identical results. The rays are traced in
+    /// packets, see [`CommittedScene::occluded_auto`].
+    ///
+    /// # Arguments
+    /// * `point` - The surface point.
+    /// * `normal` - The surface normal at `point`. Does not need to be normalized, but must not
+    ///   have zero length.
+    /// * `sample_count` - The number of hemisphere samples. Must be greater than zero.
+    /// * `radius` - The maximum distance at which geometry occludes the point.
+    /// * `cancel` - An optional cancellation token, checked before each chunk of samples.
+    ///
+    /// # Returns
+    /// A `Result` containing the `BentNormal` and whether all samples were traced, or an error
+    /// if an error occurred. If the computation was cancelled, the `BentNormal` covers the
+    /// traced samples.
+    pub fn bent_normal(
+        &self,
+        point: (f32, f32, f32),
+        normal: (f32, f32, f32),
+        sample_count: u32,
+        radius: f32,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(BentNormal, BatchStatus)> {
+        if sample_count == 0 {
+            bail!("Could not compute bent normal: sample count must be greater than zero");
+        }
+        if length(normal) == 0.0 {
+            bail!(
+                "Could not compute bent normal: normal {:?} has zero length",
+                normal
+            );
+        }
+
+        let mut traced = 0;
+        let mut occluded = 0;
+        let mut unoccluded_sum = (0.0, 0.0, 0.0);
+        let status = self.trace_hemisphere(
+            point,
+            normal,
+            sample_count,
+            radius,
+            cancel,
+            |dir, is_occluded| {
+                traced += 1;
+                if is_occluded {
+                    occluded += 1;
+                } else {
+                    unoccluded_sum = add(unoccluded_sum, dir);
+                }
+            },
+        )?;
+
+        let bent = if occluded < traced {
+            normalize(unoccluded_sum)
+        } else {
+            normalize(normal)
+        };
+        let occlusion = if traced > 0 {
+            occluded as f32 / traced as f32
+        } else {
+            0.0
+        };
+        Ok((
+            BentNormal {
+                normal: bent,
+                occlusion,
+            },
+            status,
+        ))
+    }
+
+    /// Computes the ambient occlusion at a surface point.
+    ///
+    /// Traces `sample_count` cosine-weighted occlusion rays over the hemisphere around `normal`,
+    /// up to a distance of `radius`. Uses the same deterministic sample directions as
+    /// [`CommittedScene::bent_normal`]. The rays are traced in packets, see
+    /// [`CommittedScene::occluded_auto`].
+    ///
+    /// # Arguments
+    /// * `point` - The surface point, e.g. [`Hit::hit_point`](crate::Hit::hit_point).
+    /// * `normal` - The surface normal at `point`. Does not need to be normalized, but must not
+    ///   have zero length.
+    /// * `sample_count` - The number of hemisphere samples. Must be greater than zero.
+    /// * `radius` - The maximum distance at which geometry occludes the point.
+    /// * `cancel` - An optional cancellation token, checked before each chunk of samples.
+    ///
+    /// # Returns
+    /// A `Result` containing the fraction of occluded samples in `[0, 1]` and whether all
+    /// samples were traced, or an error if an error occurred. If the computation was cancelled,
+    /// the fraction covers the traced samples, or is zero if no sample was traced.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, -5.0), (0.0, 0.0, 1.0));
+    /// if let Some(hit) = scene.intersect_1(ray).unwrap() {
+    ///     let (ao, _) = scene
+    ///         .ambient_occlusion(hit.hit_point(), hit.normal(), 64, 1.0, None)
+    ///         .unwrap();
+    ///     println!("ambient occlusion: {}", ao);
+    /// }
+    /// ```
+    pub fn ambient_occlusion(
+        &self,
+        point: (f32, f32, f32),
+        normal: (f32, f32, f32),
+        sample_count: u32,
+        radius: f32,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(f32, BatchStatus)> {
+        if sample_count == 0 {
+            bail!("Could not compute ambient occlusion: sample count must be greater than zero");
+        }
+        if length(normal) == 0.0 {
+            bail!(
+                "Could not compute ambient occlusion: normal {:?} has zero length",
+                normal
+            );
+        }
+
+        let mut traced = 0;
+        let mut occluded = 0;
+        let status = self.trace_hemisphere(
+            point,
+            normal,
+            sample_count,
+            radius,
+            cancel,
+            |_, is_occluded| {
+                traced += 1;
+                occluded += is_occluded as u32;
+            },
+        )?;
+        let occlusion = if traced > 0 {
+            occluded as f32 / traced as f32
+        } else {
+            0.0
+        };
+        Ok((occlusion, status))
+    }
+
+    /// Traces the occlusion rays of `sample_count` hemisphere samples around `normal` in
+    /// packets, checking `cancel` between chunks of samples.
+    ///
+    /// Calls `visit` with the direction of every traced sample and whether it was occluded, and
+    /// returns whether all samples were traced.
+    fn trace_hemisphere(
+        &self,
+        point: (f32, f32, f32),
+        normal: (f32, f32, f32),
+        sample_count: u32,
+        radius: f32,
+        cancel: Option<&AtomicBool>,
+        mut visit: impl FnMut((f32, f32, f32), bool),
+    ) -> Result<BatchStatus> {
+        let normal = normalize(normal);
+        let origin = offset_origin(point, normal);
+
+        for start in (0..sample_count).step_by(CANCEL_CHUNK_SIZE) {
+            if is_cancelled(cancel) {
+                return Ok(BatchStatus::Cancelled);
+            }
+
+            let end = start
+                .saturating_add(CANCEL_CHUNK_SIZE as u32)
+                .min(sample_count);
+            let dirs: Vec<_> = (start..end)
+                .map(|i| cosine_hemisphere(normal, hammersley(i, sample_count)))
+                .collect();
+            let rays: Vec<_> = dirs
+                .iter()
+                .map(|dir| embree4_sys::RTCRay {
+                    org_x: origin.0,
+                    org_y: origin.1,
+                    org_z: origin.2,
+                    dir_x: dir.0,
+                    dir_y: dir.1,
+                    dir_z: dir.2,
+                    tfar: radius,
+                    ..Default::default()
+                })
+                .collect();
+
+            let occluded = self.occluded_auto(&rays)?;
+            for (dir, occluded) in dirs.into_iter().zip(occluded) {
+                visit(dir, occluded);
+            }
+        }
+        Ok(BatchStatus::Completed)
+    }
+}
+
+#[test]
+fn bent_normal_unoccluded() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let (bent, status) = scene
+        .bent_normal((0.0, 0.0, 0.0), (0.0, 0.0, 2.0), 64, 1.0, None)
+        .unwrap();
+    assert_eq!(status, BatchStatus::Completed);
+    assert_eq!(bent.occlusion, 0.0);
+    assert!((bent.normal.2 - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn ambient_occlusion_matches_bent_normal() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 0.5), (1.0, -1.0, 0.5), (0.0, 1.0, 0.5)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let (point, normal) = ((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let (ao, status) = scene
+        .ambient_occlusion(point, normal, 64, 1.0, None)
+        .unwrap();
+    let (bent, _) = scene.bent_normal(point, normal, 64, 1.0, None).unwrap();
+    assert_eq!(status, BatchStatus::Completed);
+    assert!(ao > 0.0 && ao < 1.0);
+    assert_eq!(ao, bent.occlusion);
+    assert_eq!(
+        scene
+            .ambient_occlusion(point, normal, 64, 0.25, None)
+            .unwrap()
+            .0,
+        0.0
+    );
+
+    let cancel = AtomicBool::new(true);
+    assert_eq!(
+        scene
+            .ambient_occlusion(point, normal, 64, 1.0, Some(&cancel))
+            .unwrap(),
+        (0.0, BatchStatus::Cancelled)
+    );
+}
+
+#[test]
+fn ambient_occlusion_rejects_zero_normal() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    assert!(scene
+        .ambient_occlusion((0.0, 0.0, 0.0), (0.0, 0.0, 0.0), 64, 1.0, None)
+        .is_err());
+}
+
+#[test]
+fn bent_normal_half_occluded() {
+    let device = crate::Device::try_new(None).unwrap();
+    // A large quad above the half-space x > 0
+    let vertices = [
+        (0.0, -1000.0, 0.5),
+        (1000.0, -1000.0, 0.5),
+        (1000.0, 1000.0, 0.5),
+        (0.0, 1000.0, 0.5),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (0, 2, 3)])
+            .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let (point, normal) = ((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let (bent, status) = scene.bent_normal(point, normal, 256, 100.0, None).unwrap();
+    assert_eq!(status, BatchStatus::Completed);
+    assert!((bent.occlusion - 0.5).abs() < 0.1);
+    // The unoccluded samples all point towards -x
+    assert!(bent.normal.0 < -0.2 && bent.normal.2 > 0.0);
+
+    assert!(scene
+        .bent_normal(point, (0.0, 0.0, 0.0), 64, 1.0, None)
+        .is_err());
+    let cancel = AtomicBool::new(true);
+    let (bent, status) = scene
+        .bent_normal(point, (0.0, 0.0, 2.0), 64, 1.0, Some(&cancel))
+        .unwrap();
+    assert_eq!(status, BatchStatus::Cancelled);
+    assert_eq!((bent.normal, bent.occlusion), (normal, 0.0));
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+/// The number of queries processed between two checks of a cancellation token.
+pub(crate) const CANCEL_CHUNK_SIZE: usize = 256;
+
+/// The completion status of a batch query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchStatus {
+    /// All queries of the batch were processed.
+    Completed,
+    /// The batch was cancelled through its cancellation token before all queries were processed.
+    Cancelled,
+}
+
+/// The results of a batch query that may have been cancelled.
+///
+/// If the batch was cancelled, `results` holds the results for a prefix of the queries, in
+/// order. Otherwise it holds the results for all queries.
+#[derive(Clone, Debug)]
+pub struct Batch<T> {
+    pub results: Vec<T>,
+    pub status: BatchStatus,
+}
+
+impl<T> Batch<T> {
+    /// Returns `true` if the batch was cancelled before all queries were processed.
+    pub fn is_cancelled(&self) -> bool {
+        self.status == BatchStatus::Cancelled
+    }
+
+    /// Pairs each result with the ID of the ray it was computed for.
+    ///
+    /// Use this to correlate results, including misses, with the originating samples, e.g.
+    /// pixels, without relying on their position.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays the batch was computed for.
+    pub fn iter_with_ids<'b>(
+        &'b self,
+        rays: &'b [embree4_sys::RTCRay],
+    ) -> impl Iterator<Item = (u32, &'b T)> + 'b {
+        rays.iter().map(|ray| ray.id).zip(&self.results)
+    }
+}
+
+/// Returns `true` if the optional cancellation token has been set.
+pub(crate) fn is_cancelled(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+/// Runs `query` for each item in order, checking `cancel` between chunks of
+/// [`CANCEL_CHUNK_SIZE`] items.
+pub(crate) fn run_cancellable<I, T>(
+    items: &[I],
+    cancel: Option<&AtomicBool>,
+    mut query: impl FnMut(&I) -> Result<T>,
+) -> Result<Batch<T>> {
+    let mut results = Vec::with_capacity(items.len());
+    for chunk in items.chunks(CANCEL_CHUNK_SIZE) {
+        if is_cancelled(cancel) {
+            return Ok(Batch {
+                results,
+                status: BatchStatus::Cancelled,
+            });
+        }
+        for item in chunk {
+            results.push(query(item)?);
+        }
+    }
+    Ok(Batch {
+        results,
+        status: BatchStatus::Completed,
+    })
+}
+
+#[test]
+fn run_cancellable_cancelled() {
+    let items = vec![0; 4 * CANCEL_CHUNK_SIZE];
+    let cancel = AtomicBool::new(false);
+    let batch = run_cancellable(&items, Some(&cancel), |_| {
+        cancel.store(true, Ordering::Relaxed);
+        Ok(())
+    })
+    .unwrap();
+    assert!(batch.is_cancelled());
+    assert_eq!(batch.results.len(), CANCEL_CHUNK_SIZE);
+}
+
+#[test]
+fn run_cancellable_completed() {
+    let items = vec![1; 3 * CANCEL_CHUNK_SIZE + 1];
+    let batch = run_cancellable(&items, None, |i| Ok(*i)).unwrap();
+    assert_eq!(batch.status, BatchStatus::Completed);
+    assert_eq!(batch.results.len(), items.len());
+}
+
+#[test]
+fn iter_with_ids_pairs_rays() {
+    let rays: Vec<embree4_sys::RTCRay> = (0..4)
+        .map(|i| {
+            crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0))
+                .with_id(10 + i)
+                .into()
+        })
+        .collect();
+    let batch = Batch {
+        results: vec!['a', 'b', 'c'],
+        status: BatchStatus::Cancelled,
+    };
+    let pairs: Vec<_> = batch.iter_with_ids(&rays).collect();
+    assert_eq!(pairs, [(10, &'a'), (11, &'b'), (12, &'c')]);
+}
@@ -0,0 +1,162 @@
+use std::{ffi::c_void, mem::align_of};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{device_error, Device};
+
+/// A buffer backed by memory that is allocated and owned outside of Embree.
+///
+/// Shared buffers allow hybrid CPU/GPU pipelines to hand memory they already manage (e.g. SYCL
+/// USM allocations or pinned host memory) to Embree without duplicating mesh data.
+/// Geometries retain the underlying Embree buffer, so a `SharedBuffer` may be dropped once it has
+/// been assigned to a geometry. The memory itself must outlive every geometry using it.
+pub struct SharedBuffer {
+    pub(crate) handle: embree4_sys::RTCBuffer,
+    byte_size: usize,
+}
+
+impl SharedBuffer {
+    /// Constructs a new `SharedBuffer` over externally allocated memory.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `ptr` - A pointer to the start of the memory region.
+    /// * `byte_size` - The size of the memory region in bytes.
+    ///
+    /// # Returns
+    /// A `Result` containing the `SharedBuffer` if successful, or an error if the pointer is null,
+    /// misaligned, or the buffer could not be created.
+    ///
+    /// # Safety
+    /// * `ptr` must be valid for reads of `byte_size` bytes, and for writes if the buffer is ever
+    ///   modified through Embree.
+    /// * The memory must stay valid and must not be freed until every geometry using this buffer
+    ///   has been released.
+    /// * The memory must be accessible by the device, e.g. allocated as USM when using a SYCL
+    ///   device.
+    pub unsafe fn try_new(device: &Device, ptr: *mut c_void, byte_size: usize) -> Result<Self> {
+        if ptr.is_null() {
+            bail!("Could not create shared buffer: pointer is null");
+        }
+        if ptr as usize % align_of::<f32>() != 0 {
+            bail!("Could not create shared buffer: pointer must be aligned to 4 bytes");
+        }
+
+        let handle = embree4_sys::rtcNewSharedBuffer(device.handle, ptr, byte_size);
+        if handle.is_null() {
+            return Err(device_error(device, "Could not create shared buffer"));
+        }
+
+        Ok(Self { handle, byte_size })
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn byte_size(&self) -> usize {
+        self.byte_size
+    }
+}
+
+/// The location of fixed-size elements in a buffer, e.g. vertex positions in a buffer of
+/// interleaved vertex data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferLayout {
+    /// The byte offset of the first element. Must be a multiple of 4.
+    pub offset: usize,
+    /// The distance between the starts of two consecutive elements in bytes. Must be a multiple
+    /// of 4, and at least the size of an element.
+    pub stride: usize,
+}
+
+impl BufferLayout {
+    /// Returns the layout of tightly packed elements of `size` bytes, starting at offset 0.
+    pub fn packed(size: usize) -> Self {
+        Self {
+            offset: 0,
+            stride: size,
+        }
+    }
+
+    /// Returns the number of bytes needed to hold `count` elements of `size` bytes, or an error
+    /// if it does not fit in a `usize`.
+    pub(crate) fn required_size(&self, size: usize, count: usize) -> Result<usize> {
+        if count == 0 {
+            return Ok(0);
+        }
+        (count - 1)
+            .checked_mul(self.stride)
+            .and_then(|end| end.checked_add(self.offset))
+            .and_then(|end| end.checked_add(size))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Buffer layout with offset {} and stride {} overflows for {} elements",
+                    self.offset,
+                    self.stride,
+                    count
+                )
+            })
+    }
+
+    /// Returns `true` if consecutive elements of `size` bytes follow each other without gaps.
+    pub(crate) fn is_packed(&self, size: usize) -> bool {
+        self.stride == size
+    }
+
+    /// Checks that elements of `size` bytes can be read with this layout.
+    pub(crate) fn validate(&self, size: usize) -> Result<()> {
+        if self.offset % 4 != 0 || self.stride % 4 != 0 {
+            bail!(
+                "Buffer offset {} and stride {} must be multiples of 4",
+                self.offset,
+                self.stride
+            );
+        }
+        if self.stride < size {
+            bail!(
+                "Buffer stride {} is smaller than the element size {}",
+                self.stride,
+                size
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseBuffer(self.handle);
+        }
+    }
+}
+
+#[test]
+fn required_size_overflow() {
+    let layout = BufferLayout {
+        offset: 16,
+        stride: 32,
+    };
+    assert_eq!(layout.required_size(12, 0).unwrap(), 0);
+    assert_eq!(layout.required_size(12, 3).unwrap(), 16 + 2 * 32 + 12);
+    assert!(layout.required_size(12, usize::MAX / 16).is_err());
+    let offset = BufferLayout {
+        offset: 4,
+        stride: 12,
+    };
+    assert!(offset.required_size(usize::MAX, 1).is_err());
+}
+
+#[test]
+fn try_new_null_ptr() {
+    let device = Device::try_new(None).unwrap();
+    let buffer = unsafe { SharedBuffer::try_new(&device, std::ptr::null_mut(), 16) };
+    assert!(buffer.is_err());
+}
+
+#[test]
+fn try_new_misaligned_ptr() {
+    let device = Device::try_new(None).unwrap();
+    let mut data = [0u32; 4];
+    let ptr = unsafe { (data.as_mut_ptr() as *mut u8).add(1) };
+    let buffer = unsafe { SharedBuffer::try_new(&device, ptr as _, 8) };
+    assert!(buffer.is_err());
+}
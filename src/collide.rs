@@ -0,0 +1,178 @@
+//! Collision detection between the primitives of two scenes.
+
+use std::{
+    any::Any,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+
+use crate::{device_error_or, CommittedScene};
+
+/// A pair of primitives whose bounds overlap, see [`CommittedScene::collide`].
+///
+/// Has the same layout as [`RTCCollision`](embree4_sys::RTCCollision).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Collision {
+    /// The geometry ID of the primitive in the first scene.
+    pub geom_id0: u32,
+    /// The primitive ID of the primitive in the first scene.
+    pub prim_id0: u32,
+    /// The geometry ID of the primitive in the second scene.
+    pub geom_id1: u32,
+    /// The primitive ID of the primitive in the second scene.
+    pub prim_id1: u32,
+}
+
+struct CollideState<F> {
+    callback: F,
+    /// A panic raised by `callback`, resumed once the collision detection returns to Rust.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Finds all pairs of primitives of this scene and `other` whose bounds overlap.
+    ///
+    /// Embree only reports broadphase candidates, so the callback is expected to run the exact
+    /// collision test of the primitives. Both scenes may only contain user geometry. Pass the
+    /// same scene twice to detect self-collisions.
+    ///
+    /// # Arguments
+    /// * `other` - The second scene.
+    /// * `callback` - Called with batches of colliding primitive pairs. Embree may call it from
+    ///   multiple threads concurrently.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the collision detection completed, or an error if an error
+    /// occurred.
+    ///
+    /// # Panics
+    /// Panics raised by `callback` are propagated once collision detection has finished.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::sync::Mutex;
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let cloth = Scene::try_new(&device, Default::default()).unwrap();
+    /// let cloth = cloth.commit().unwrap();
+    /// let body = Scene::try_new(&device, Default::default()).unwrap();
+    /// let body = body.commit().unwrap();
+    ///
+    /// let pairs = Mutex::new(Vec::new());
+    /// cloth
+    ///     .collide(&body, |collisions| {
+    ///         pairs.lock().unwrap().extend_from_slice(collisions);
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn collide<F>(&self, other: &CommittedScene, callback: F) -> Result<()>
+    where
+        F: Fn(&[Collision]) + Sync,
+    {
+        let state = CollideState {
+            callback,
+            panic: Mutex::new(None),
+        };
+
+        unsafe {
+            embree4_sys::rtcCollide(
+                self.scene.handle,
+                other.scene.handle,
+                Some(collide_trampoline::<F>),
+                &state as *const CollideState<F> as *mut _,
+            );
+        }
+        if let Some(panic) = state.panic.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            resume_unwind(panic);
+        }
+        device_error_or(self.scene.device, (), "Could not collide scenes")
+    }
+}
+
+/// Forwards Embree's collision callback to the closure stored in the [`CollideState`].
+unsafe extern "C" fn collide_trampoline<F>(
+    user_ptr: *mut std::ffi::c_void,
+    collisions: *mut embree4_sys::RTCCollision,
+    num_collisions: u32,
+) where
+    F: Fn(&[Collision]) + Sync,
+{
+    let state = &*(user_ptr as *const CollideState<F>);
+    if num_collisions == 0 {
+        return;
+    }
+    let collisions =
+        std::slice::from_raw_parts(collisions as *const Collision, num_collisions as usize);
+
+    // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+    if let Err(panic) = catch_unwind(AssertUnwindSafe(|| (state.callback)(collisions))) {
+        let mut slot = state.panic.lock().unwrap_or_else(|e| e.into_inner());
+        slot.get_or_insert(panic);
+    }
+}
+
+#[test]
+fn collide_overlapping_boxes() {
+    use crate::geometry::{UserGeometry, UserGeometryImpl};
+
+    struct Aabb(embree4_sys::RTCBounds);
+
+    impl UserGeometryImpl for Aabb {
+        fn bounds(&self) -> embree4_sys::RTCBounds {
+            self.0
+        }
+
+        fn intersect(
+            &self,
+            _geom_id: u32,
+            _prim_id: u32,
+            _ctx: &embree4_sys::RTCRayQueryContext,
+            _ray_hit: &mut embree4_sys::RTCRayHit,
+        ) {
+        }
+    }
+
+    let unit_box = |offset: f32| {
+        Aabb(embree4_sys::RTCBounds {
+            lower_x: offset,
+            lower_y: 0.0,
+            lower_z: 0.0,
+            align0: 0.0,
+            upper_x: offset + 1.0,
+            upper_y: 1.0,
+            upper_z: 1.0,
+            align1: 0.0,
+        })
+    };
+
+    let device = crate::Device::try_new(None).unwrap();
+    let (box0, box1) = (unit_box(0.0), unit_box(0.5));
+    let geometry0 = UserGeometry::try_new(&device, box0).unwrap();
+    let geometry1 = UserGeometry::try_new(&device, box1).unwrap();
+    let scene0 = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene0.attach_geometry(&geometry0).unwrap();
+    let scene0 = scene0.commit().unwrap();
+    let scene1 = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene1.attach_geometry(&geometry1).unwrap();
+    let scene1 = scene1.commit().unwrap();
+
+    let pairs = Mutex::new(Vec::new());
+    scene0
+        .collide(&scene1, |collisions| {
+            pairs.lock().unwrap().extend_from_slice(collisions)
+        })
+        .unwrap();
+    assert_eq!(
+        pairs.into_inner().unwrap(),
+        [Collision {
+            geom_id0: 0,
+            prim_id0: 0,
+            geom_id1: 0,
+            prim_id1: 0,
+        }]
+    );
+}
@@ -0,0 +1,83 @@
+/// A ray query context, passed through traversal to user geometry callbacks.
+///
+/// Wraps an [`RTCRayQueryContext`](embree4_sys::RTCRayQueryContext) initialized like
+/// `rtcInitRayQueryContext`. During traversal, Embree maintains the stack of instance IDs the
+/// ray currently traverses in the context, which user geometry needs to report hits on
+/// instanced geometry.
+#[derive(Clone, Copy, Debug)]
+#[repr(transparent)]
+pub struct RayQueryContext {
+    raw: embree4_sys::RTCRayQueryContext,
+}
+
+impl RayQueryContext {
+    /// Constructs a new, empty `RayQueryContext`.
+    pub fn new() -> Self {
+        Self {
+            raw: embree4_sys::RTCRayQueryContext {
+                instID: [embree4_sys::RTC_INVALID_GEOMETRY_ID;
+                    embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize],
+            },
+        }
+    }
+
+    /// Returns the instance ID stack, from the outermost to the innermost instance.
+    ///
+    /// The stack is empty outside of instances.
+    pub fn instance_stack(&self) -> &[u32] {
+        let depth = self
+            .raw
+            .instID
+            .iter()
+            .position(|&id| id == embree4_sys::RTC_INVALID_GEOMETRY_ID)
+            .unwrap_or(self.raw.instID.len());
+        &self.raw.instID[..depth]
+    }
+
+    /// Returns the raw Embree context.
+    pub fn raw(&self) -> &embree4_sys::RTCRayQueryContext {
+        &self.raw
+    }
+
+    /// Returns the raw Embree context mutably.
+    pub fn raw_mut(&mut self) -> &mut embree4_sys::RTCRayQueryContext {
+        &mut self.raw
+    }
+}
+
+impl Default for RayQueryContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn instance_stack_empty() {
+    let mut context = RayQueryContext::new();
+    assert!(context.instance_stack().is_empty());
+
+    context.raw_mut().instID[0] = 3;
+    assert_eq!(context.instance_stack(), &[3]);
+}
+
+#[test]
+fn intersect_1_with_context() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut context = RayQueryContext::new();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene
+        .intersect_1_with_context(ray, &mut context, &Default::default())
+        .unwrap();
+    assert!(hit.is_some());
+    assert!(scene
+        .occluded_1_with_context(ray, &mut context, &Default::default())
+        .unwrap());
+    assert!(context.instance_stack().is_empty());
+}
@@ -1,4 +1,10 @@
-use std::ptr::null_mut;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr},
+    ptr::null_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use anyhow::{bail, Result};
 
@@ -6,14 +12,37 @@ use crate::device_error_raw;
 
 pub struct Device {
     pub(crate) handle: embree4_sys::RTCDevice,
+    /// The key of the device's pending error messages in [`ERROR_MESSAGES`]. Unlike the handle,
+    /// it is never reused by later devices.
+    id: usize,
 }
 
+/// The source of unique device IDs.
+static NEXT_DEVICE_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The message of the pending error of each device on this thread, if Embree reported one.
+    ///
+    /// Embree tracks error codes per thread, and keeps the first error until it is read, so only
+    /// the message of the first error is stored, and it is cleared along with the error code.
+    static ERROR_MESSAGES: RefCell<HashMap<usize, Option<String>>> =
+        RefCell::new(HashMap::new());
+}
+
+// Embree's device API is thread-safe, and errors are tracked per thread
+unsafe impl Send for Device {}
+unsafe impl Sync for Device {}
+
 impl Device {
     /// Constructs a new `Device` using the provided configuration string.
     ///
     /// # Arguments
     /// * `config` - A string representing the configuration for the device. Can be an empty string.
-    ///              See [rtcNewDevice](https://github.com/embree/embree/blob/master/doc/src/api/rtcNewDevice.md) for valid configuration values.
+    ///   See [rtcNewDevice](https://github.com/embree/embree/blob/master/doc/src/api/rtcNewDevice.md) for valid configuration values.
+    ///
+    /// The device records the messages of its errors through an error function, see
+    /// [`Device::error_message`]. Installing another error function with
+    /// `rtcSetDeviceErrorFunction` replaces it, after which errors no longer carry messages.
     ///
     /// # Returns
     /// A `Result` containing the created `Device` if successful, or an error if the device creation fails.
@@ -38,20 +67,78 @@ impl Device {
             bail!("Failed to create device: {:?}", error);
         }
 
-        Ok(Device { handle })
+        let id = NEXT_DEVICE_ID.fetch_add(1, Ordering::Relaxed);
+        unsafe {
+            // The ID is passed as the user pointer, it is never dereferenced
+            embree4_sys::rtcSetDeviceErrorFunction(handle, Some(error_fn), id as *mut c_void);
+        }
+        Ok(Device { handle, id })
     }
 
-    /// Returns the error code associated with the device, if any.
+    /// Returns the error code associated with the device, if any, and clears it along with its
+    /// message.
     ///
     /// # Returns
     /// `Some(error_code)` if there is an error associated with the device, otherwise `None`.
     pub fn error(&self) -> Option<embree4_sys::RTCError> {
+        self.take_error_message();
         device_error_raw(self.handle)
     }
+
+    /// Returns the message of the pending error of the device on the calling thread, if any,
+    /// without clearing it.
+    ///
+    /// Like error codes, messages are tracked per thread, so errors of concurrent queries on
+    /// other threads do not overwrite the message. Errors returned by this crate already include
+    /// the message, so this is only needed after calling Embree directly, e.g. through raw
+    /// handles. The message is cleared by [`Device::error`].
+    pub fn error_message(&self) -> Option<String> {
+        ERROR_MESSAGES.with(|messages| messages.borrow().get(&self.id).cloned().flatten())
+    }
+
+    /// Takes the message of the pending error on the calling thread, when its code is read.
+    pub(crate) fn take_error_message(&self) -> Option<String> {
+        ERROR_MESSAGES.with(|messages| messages.borrow_mut().remove(&self.id).flatten())
+    }
+
+    /// Returns the widest ray packet size natively supported by the device.
+    ///
+    /// # Returns
+    /// `16`, `8` or `4` if the device supports the respective packet kernels, otherwise `1`.
+    pub fn native_packet_width(&self) -> usize {
+        use embree4_sys::RTCDeviceProperty;
+
+        [
+            (RTCDeviceProperty::NATIVE_RAY16_SUPPORTED, 16),
+            (RTCDeviceProperty::NATIVE_RAY8_SUPPORTED, 8),
+            (RTCDeviceProperty::NATIVE_RAY4_SUPPORTED, 4),
+        ]
+        .into_iter()
+        .find(|(prop, _)| unsafe { embree4_sys::rtcGetDeviceProperty(self.handle, *prop) } != 0)
+        .map_or(1, |(_, width)| width)
+    }
+}
+
+/// Records the message of the first pending error reported by Embree for the thread which
+/// raised it.
+///
+/// Embree calls the error function on the thread of the failing API call.
+unsafe extern "C" fn error_fn(
+    user_ptr: *mut c_void,
+    _code: embree4_sys::RTCError,
+    message: *const c_char,
+) {
+    let id = user_ptr as usize;
+    let message =
+        (!message.is_null()).then(|| CStr::from_ptr(message).to_string_lossy().into_owned());
+    ERROR_MESSAGES.with(|messages| {
+        messages.borrow_mut().entry(id).or_insert(message);
+    });
 }
 
 impl Drop for Device {
     fn drop(&mut self) {
+        self.take_error_message();
         unsafe {
             embree4_sys::rtcReleaseDevice(self.handle);
         }
@@ -70,8 +157,36 @@ fn try_new_invalid_config() {
     assert!(err_device.is_err());
 }
 
+#[test]
+fn native_packet_width() {
+    let device = Device::try_new(None).unwrap();
+    assert!([1, 4, 8, 16].contains(&device.native_packet_width()));
+}
+
 #[test]
 fn try_new_no_config() {
     let ok_device = Device::try_new(None);
     assert!(ok_device.is_ok());
 }
+
+#[test]
+fn errors_include_message() {
+    let device = Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+
+    unsafe {
+        embree4_sys::rtcSetSceneBuildQuality(scene.handle, embree4_sys::RTCBuildQuality::REFIT);
+    }
+    let message = device.error_message().unwrap();
+    std::thread::scope(|s| {
+        s.spawn(|| assert!(device.error_message().is_none()));
+    });
+    assert!(device.error().is_some());
+    assert!(device.error_message().is_none());
+
+    let error = scene
+        .set_build_quality(embree4_sys::RTCBuildQuality::REFIT)
+        .unwrap_err();
+    assert!(error.to_string().ends_with(&message));
+    assert!(device.error_message().is_none());
+}
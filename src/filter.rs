@@ -0,0 +1,489 @@
+//! Argument filter functions, which invoke a Rust closure for every candidate hit of a query.
+
+use std::{
+    any::Any,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    device_error_or, ray::validate_ray, CommittedScene, Hit, QueryOptions, RayQueryContext,
+};
+
+/// A ray query context carrying a filter closure and the scene's hit policy.
+///
+/// Embree passes the context pointer of the query arguments through to the filter function,
+/// which recovers the closure by casting the pointer back to `FilterContext`. This requires
+/// the Embree context to be the first field.
+#[repr(C)]
+struct FilterContext<'p, F> {
+    context: embree4_sys::RTCRayQueryContext,
+    policy: Option<&'p dyn HitPolicy>,
+    filter: F,
+    /// Set once a candidate hit terminated traversal, rejecting all further candidates.
+    terminated: bool,
+    /// A panic raised by `filter`, resumed once the query returns to Rust.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+/// A ray query context carrying the scene's hit policy, for packet queries.
+///
+/// Like [`FilterContext`], the Embree context must be the first field.
+#[repr(C)]
+pub(crate) struct PolicyContext<'p> {
+    context: embree4_sys::RTCRayQueryContext,
+    policy: &'p dyn HitPolicy,
+    /// A panic raised by `policy`, resumed once the query returns to Rust.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<'p> PolicyContext<'p> {
+    /// Installs the policy in intersect arguments. The context must not move until the query
+    /// returns.
+    pub(crate) fn apply_intersect(&mut self, args: &mut embree4_sys::RTCIntersectArguments) {
+        args.flags |= embree4_sys::RTCRayQueryFlags::INVOKE_ARGUMENT_FILTER;
+        args.context = self as *mut PolicyContext as *mut _;
+        args.filter = Some(policy_trampoline);
+    }
+
+    /// Installs the policy in occluded arguments. The context must not move until the query
+    /// returns.
+    pub(crate) fn apply_occluded(&mut self, args: &mut embree4_sys::RTCOccludedArguments) {
+        args.flags |= embree4_sys::RTCRayQueryFlags::INVOKE_ARGUMENT_FILTER;
+        args.context = self as *mut PolicyContext as *mut _;
+        args.filter = Some(policy_trampoline);
+    }
+
+    /// Resumes a panic raised by the policy during the query.
+    pub(crate) fn resume_panic(self) {
+        if let Some(panic) = self.panic {
+            resume_unwind(panic);
+        }
+    }
+}
+
+/// The decision of a filter closure about a candidate hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Accepts the hit. Traversal continues to find a nearer hit.
+    Accept,
+    /// Rejects the hit, as if the ray had not hit the primitive.
+    Reject,
+    /// Accepts the hit and rejects all further candidates, so the hit is reported even if a
+    /// nearer one exists. Hit policies of packet queries treat this like
+    /// [`FilterDecision::Accept`].
+    Terminate,
+}
+
+/// A scene-wide policy deciding about every candidate hit of a scene's queries, see
+/// [`Scene::set_hit_policy`](crate::Scene::set_hit_policy).
+///
+/// Implemented for all closures `Fn(&Hit) -> FilterDecision`.
+pub trait HitPolicy: Send + Sync {
+    /// Decides about a candidate hit. The `tfar` of the hit's ray is the distance of the
+    /// candidate.
+    fn evaluate(&self, hit: &Hit) -> FilterDecision;
+}
+
+impl<F> HitPolicy for F
+where
+    F: Fn(&Hit) -> FilterDecision + Send + Sync,
+{
+    fn evaluate(&self, hit: &Hit) -> FilterDecision {
+        self(hit)
+    }
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Intersects a single ray with the scene, invoking `filter` for every candidate hit.
+    ///
+    /// Use this to reject individual hits for a single query, e.g. self-intersections with the
+    /// primitive the ray starts on, without attaching a filter function to the geometry.
+    /// Candidate hits are reported in traversal order, not sorted by distance. Candidates
+    /// rejected by the scene's hit policy are not passed to `filter`.
+    ///
+    /// The scene must have been created with
+    /// [`SceneFlags::filter_function_in_arguments`](crate::SceneFlags::filter_function_in_arguments).
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    /// * `filter` - Called with each candidate hit. The `tfar` of the hit's ray is the distance
+    ///   of the candidate.
+    ///
+    /// # Returns
+    /// A `Result` containing the nearest accepted hit, if any, or an error if an error occurred.
+    ///
+    /// # Panics
+    /// Panics raised by `filter` are propagated once traversal has finished.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::RTCRay;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let options = SceneOptions {
+    ///     flags: SceneFlags {
+    ///         filter_function_in_arguments: true,
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    /// let scene = Scene::try_new(&device, options).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let origin_prim_id = 42;
+    /// let ray = RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// let hit = scene
+    ///     .intersect_1_filtered(ray, |hit| {
+    ///         if hit.prim_id() == origin_prim_id {
+    ///             FilterDecision::Reject
+    ///         } else {
+    ///             FilterDecision::Accept
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn intersect_1_filtered<F>(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        filter: F,
+    ) -> Result<Option<Hit>>
+    where
+        F: FnMut(&Hit) -> FilterDecision,
+    {
+        self.intersect_1_with_filter(ray.into(), filter)
+    }
+
+    /// Intersects a ray with the scene, invoking `filter` for every candidate hit accepted by
+    /// the scene's hit policy.
+    ///
+    /// The scene must have been created with [`SceneFlags::filter_function_in_arguments`](crate::SceneFlags::filter_function_in_arguments).
+    pub(crate) fn intersect_1_with_filter<F>(
+        &self,
+        ray: embree4_sys::RTCRay,
+        filter: F,
+    ) -> Result<Option<Hit>>
+    where
+        F: FnMut(&Hit) -> FilterDecision,
+    {
+        validate_ray(&ray)?;
+        self.intersect_1_filtered_with_context(
+            ray,
+            &mut RayQueryContext::new(),
+            &QueryOptions::default(),
+            filter,
+        )
+    }
+
+    /// Intersects a ray with the scene, passing `context` through traversal and invoking
+    /// `filter` for every candidate hit accepted by the scene's hit policy.
+    pub(crate) fn intersect_1_filtered_with_context<F>(
+        &self,
+        ray: embree4_sys::RTCRay,
+        context: &mut RayQueryContext,
+        options: &QueryOptions,
+        filter: F,
+    ) -> Result<Option<Hit>>
+    where
+        F: FnMut(&Hit) -> FilterDecision,
+    {
+        self.check_filter_flag("Could not intersect ray")?;
+
+        let mut filter_context = self.filter_context(context, filter);
+        let mut args = options.intersect_arguments();
+        args.flags |= embree4_sys::RTCRayQueryFlags::INVOKE_ARGUMENT_FILTER;
+        args.context = &mut filter_context as *mut FilterContext<F> as *mut _;
+        args.filter = Some(filter_trampoline::<F>);
+        let mut ray_hit = embree4_sys::RTCRayHit {
+            ray,
+            hit: Default::default(),
+        };
+
+        unsafe {
+            embree4_sys::rtcIntersect1(self.scene.handle, &mut ray_hit, &mut args);
+        }
+        *context.raw_mut() = filter_context.context;
+        if let Some(panic) = filter_context.panic {
+            resume_unwind(panic);
+        }
+        device_error_or(self.scene.device, (), "Could not intersect ray")?;
+
+        Ok(Hit::from_ray_hit(ray_hit))
+    }
+
+    /// Checks whether a ray is occluded, passing `context` through traversal and invoking
+    /// `filter` for every candidate hit accepted by the scene's hit policy.
+    ///
+    /// The ray is occluded by the first candidate that is not rejected.
+    pub(crate) fn occluded_1_filtered_with_context<F>(
+        &self,
+        ray: embree4_sys::RTCRay,
+        context: &mut RayQueryContext,
+        options: &QueryOptions,
+        filter: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(&Hit) -> FilterDecision,
+    {
+        self.check_filter_flag("Could not test ray occlusion")?;
+
+        let mut filter_context = self.filter_context(context, filter);
+        let mut args = options.occluded_arguments();
+        args.flags |= embree4_sys::RTCRayQueryFlags::INVOKE_ARGUMENT_FILTER;
+        args.context = &mut filter_context as *mut FilterContext<F> as *mut _;
+        args.filter = Some(filter_trampoline::<F>);
+        let mut ray = ray;
+
+        unsafe {
+            embree4_sys::rtcOccluded1(self.scene.handle, &mut ray, &mut args);
+        }
+        *context.raw_mut() = filter_context.context;
+        if let Some(panic) = filter_context.panic {
+            resume_unwind(panic);
+        }
+        device_error_or(self.scene.device, (), "Could not test ray occlusion")?;
+
+        // Embree sets tfar to -inf for occluded rays
+        Ok(ray.tfar == f32::NEG_INFINITY)
+    }
+
+    fn check_filter_flag(&self, message: &str) -> Result<()> {
+        if !self.scene.flags().filter_function_in_arguments {
+            bail!(
+                "{}: filtered queries require the scene flag FILTER_FUNCTION_IN_ARGUMENTS",
+                message
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns a context evaluating the scene's hit policy in packet queries, if the scene has
+    /// one.
+    pub(crate) fn policy_context(&self) -> Option<PolicyContext<'_>> {
+        Some(PolicyContext {
+            context: *RayQueryContext::new().raw(),
+            policy: self.scene.hit_policy.as_deref()?,
+            panic: None,
+        })
+    }
+
+    fn filter_context<F>(&self, context: &RayQueryContext, filter: F) -> FilterContext<'_, F> {
+        FilterContext {
+            context: *context.raw(),
+            policy: self.scene.hit_policy.as_deref(),
+            filter,
+            terminated: false,
+            panic: None,
+        }
+    }
+}
+
+/// Forwards Embree's filter callback to the hit policy and closure stored in the
+/// [`FilterContext`].
+///
+/// Only used for single ray queries, so the `RTCRayN`/`RTCHitN` pointers have a packet width of 1
+/// and share the layout of `RTCRay`/`RTCHit`.
+unsafe extern "C" fn filter_trampoline<F>(args: *const embree4_sys::RTCFilterFunctionNArguments)
+where
+    F: FnMut(&Hit) -> FilterDecision,
+{
+    let args = &*args;
+    if args.N != 1 || *args.valid == 0 {
+        return;
+    }
+
+    let context = &mut *(args.context as *mut FilterContext<F>);
+    if context.panic.is_some() || context.terminated {
+        *args.valid = 0;
+        return;
+    }
+
+    let hit = filter_hit(args, 0);
+    let policy = context.policy;
+    let filter = &mut context.filter;
+    let decide = || {
+        let decision = policy.map_or(FilterDecision::Accept, |policy| policy.evaluate(&hit));
+        if decision == FilterDecision::Reject {
+            return decision;
+        }
+        match filter(&hit) {
+            FilterDecision::Accept => decision,
+            other => other,
+        }
+    };
+    // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+    match catch_unwind(AssertUnwindSafe(decide)) {
+        Ok(FilterDecision::Accept) => {}
+        Ok(FilterDecision::Reject) => *args.valid = 0,
+        Ok(FilterDecision::Terminate) => context.terminated = true,
+        Err(panic) => {
+            context.panic = Some(panic);
+            *args.valid = 0;
+        }
+    }
+}
+
+/// Forwards Embree's filter callback of packet queries to the hit policy stored in the
+/// [`PolicyContext`], for every active lane.
+unsafe extern "C" fn policy_trampoline(args: *const embree4_sys::RTCFilterFunctionNArguments) {
+    let args = &*args;
+    let context = &mut *(args.context as *mut PolicyContext);
+    let valid = std::slice::from_raw_parts_mut(args.valid, args.N as usize);
+
+    for (i, valid) in valid.iter_mut().enumerate() {
+        if *valid == 0 {
+            continue;
+        }
+        if context.panic.is_some() {
+            *valid = 0;
+            continue;
+        }
+
+        let hit = filter_hit(args, i);
+        // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+        match catch_unwind(AssertUnwindSafe(|| context.policy.evaluate(&hit))) {
+            Ok(FilterDecision::Reject) => *valid = 0,
+            Ok(_) => {}
+            Err(panic) => {
+                context.panic = Some(panic);
+                *valid = 0;
+            }
+        }
+    }
+}
+
+/// Extracts the candidate hit of lane `i` from the arguments of a filter callback.
+///
+/// `RTCRayN` and `RTCHitN` store each field as an array of `N` lanes.
+pub(crate) unsafe fn filter_hit(args: &embree4_sys::RTCFilterFunctionNArguments, i: usize) -> Hit {
+    let n = args.N as usize;
+    let ray_n = args.ray as *const f32;
+    let hit_n = args.hit as *const f32;
+    let ray = |field: usize| *ray_n.add(field * n + i);
+    let ray_u32 = |field: usize| *(ray_n.add(field * n + i) as *const u32);
+    let hit = |field: usize| *hit_n.add(field * n + i);
+    let hit_u32 = |field: usize| *(hit_n.add(field * n + i) as *const u32);
+
+    Hit {
+        ray_hit: embree4_sys::RTCRayHit {
+            ray: embree4_sys::RTCRay {
+                org_x: ray(0),
+                org_y: ray(1),
+                org_z: ray(2),
+                tnear: ray(3),
+                dir_x: ray(4),
+                dir_y: ray(5),
+                dir_z: ray(6),
+                time: ray(7),
+                tfar: ray(8),
+                mask: ray_u32(9),
+                id: ray_u32(10),
+                flags: ray_u32(11),
+            },
+            hit: embree4_sys::RTCHit {
+                Ng_x: hit(0),
+                Ng_y: hit(1),
+                Ng_z: hit(2),
+                u: hit(3),
+                v: hit(4),
+                primID: hit_u32(5),
+                geomID: hit_u32(6),
+                instID: std::array::from_fn(|level| hit_u32(7 + level)),
+            },
+        },
+    }
+}
+
+#[test]
+fn intersect_1_filtered_rejects() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 2.0),
+        (1.0, -1.0, 2.0),
+        (0.0, 1.0, 2.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let options = crate::SceneOptions {
+        flags: crate::SceneFlags {
+            filter_function_in_arguments: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let scene = crate::Scene::try_new(&device, options).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let hit = scene
+        .intersect_1_filtered(ray, |hit| {
+            if hit.prim_id() == 0 {
+                FilterDecision::Reject
+            } else {
+                FilterDecision::Accept
+            }
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(hit.prim_id(), 1);
+    assert_eq!(hit.t(), 2.0);
+}
+
+#[test]
+fn intersect_1_filtered_requires_scene_flag() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+    let ray = embree4_sys::RTCRay::default();
+    let result = scene.intersect_1_filtered(ray, |_| FilterDecision::Accept);
+    assert!(result.is_err());
+}
+
+#[test]
+fn intersect_1_filtered_terminates() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 2.0),
+        (1.0, -1.0, 2.0),
+        (0.0, 1.0, 2.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let options = crate::SceneOptions {
+        flags: crate::SceneFlags {
+            filter_function_in_arguments: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let scene = crate::Scene::try_new(&device, options).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut first = None;
+    let hit = scene
+        .intersect_1_filtered(crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)), |hit| {
+            first.get_or_insert(hit.prim_id());
+            FilterDecision::Terminate
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(Some(hit.prim_id()), first);
+}
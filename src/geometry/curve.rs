@@ -0,0 +1,815 @@
+use std::{mem::size_of, slice};
+
+use anyhow::{bail, Result};
+
+use crate::{device_error, device_error_or, Device};
+
+use super::Geometry;
+
+/// A set of linear curve segments with a radius at each vertex, e.g. for hair and wires.
+///
+/// Each index references the first of two consecutive vertices forming a segment. Hits report
+/// the segment index as the primitive ID, and the position along the segment as `u`, ranging
+/// from `0` at the first vertex to `1` at the second.
+pub struct LinearCurveGeometry {
+    handle: embree4_sys::RTCGeometry,
+    vertex_count: usize,
+    time_step_count: u32,
+}
+
+impl LinearCurveGeometry {
+    /// Constructs a new `LinearCurveGeometry` instance of flat, ray-facing ribbons from the
+    /// given vertices and indices.
+    ///
+    /// Flat curves are the fastest to trace, but only look correct from a distance, where each
+    /// ribbon covers a few pixels at most.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions and radii, as `(x, y, z, radius)`.
+    /// * `indices` - The index of the first vertex of each segment.
+    ///
+    /// # Returns
+    /// A `Result` containing the `LinearCurveGeometry` if successful, or an error if an index is
+    /// out of range or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// // A wire of two segments
+    /// let vertices = [(0.0, 0.0, 0.0, 0.01), (0.0, 1.0, 0.0, 0.01), (1.0, 1.0, 0.0, 0.01)];
+    /// let indices = [0, 1];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = LinearCurveGeometry::try_new(&device, &vertices, &indices).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry);
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        Self::try_new_with_type(
+            device,
+            embree4_sys::RTCGeometryType::FLAT_LINEAR_CURVE,
+            vertices,
+            indices,
+        )
+    }
+
+    /// Constructs a new `LinearCurveGeometry` instance of round segments from the given vertices
+    /// and indices.
+    ///
+    /// Each segment is a cone between spheres at its vertices, so chains of segments form
+    /// closed sweep volumes with exact round cross-sections, e.g. for ropes, cables and capsule
+    /// collision proxies.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions and radii, as `(x, y, z, radius)`.
+    /// * `indices` - The index of the first vertex of each segment.
+    ///
+    /// # Returns
+    /// A `Result` containing the `LinearCurveGeometry` if successful, or an error if an index is
+    /// out of range or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// // A capsule of radius 0.5
+    /// let vertices = [(0.0, 0.0, 0.0, 0.5), (0.0, 2.0, 0.0, 0.5)];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let capsule = LinearCurveGeometry::try_new_round(&device, &vertices, &[0]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&capsule);
+    /// ```
+    pub fn try_new_round(
+        device: &Device,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        Self::try_new_with_type(
+            device,
+            embree4_sys::RTCGeometryType::ROUND_LINEAR_CURVE,
+            vertices,
+            indices,
+        )
+    }
+
+    /// Sets the maximum factor by which Embree's min-width feature may scale the curve radii, and
+    /// recommits the geometry.
+    ///
+    /// The min-width feature enlarges distant curves during traversal, and is only available if
+    /// Embree was compiled with `EMBREE_CURVE_MINWIDTH`. Bounds are enlarged by this factor, so
+    /// the enlarged curves are not clipped by the acceleration structure. This has no effect on
+    /// radii which change over time, see [`LinearCurveGeometry::set_time_step_count`] for motion blur.
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `scale` - The maximum radius scale, at least `1.0`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the scale was set, or an error if an error occurred.
+    pub fn set_max_radius_scale(&mut self, device: &Device, scale: f32) -> Result<()> {
+        set_max_radius_scale(self.handle, device, scale)
+    }
+
+    /// Sets the number of ray-facing quads each curve is tessellated into, and recommits the
+    /// geometry.
+    ///
+    /// Only flat curves, see [`LinearCurveGeometry::try_new`], are tessellated, so lower rates trade
+    /// accuracy for memory and build time. The default rate is `4.0`. Scenes the geometry is
+    /// attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `rate` - The number of quads per curve, at least `1.0`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the rate was set, or an error if an error occurred.
+    pub fn set_tessellation_rate(&mut self, device: &Device, rate: f32) -> Result<()> {
+        set_tessellation_rate(self.handle, device, rate)
+    }
+
+    /// Sets the number of time steps for motion blur, and recommits the geometry.
+    ///
+    /// Time steps are evenly distributed over the ray time range `[0, 1]`, and Embree
+    /// interpolates positions and radii linearly between them, so the bounds cover the curves
+    /// at every time. Newly added time steps start out with the vertices of the first time step,
+    /// use [`LinearCurveGeometry::set_vertices`] to move them. Scenes the geometry is attached to must
+    /// be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `count` - The number of time steps, from 1 to
+    ///   [`RTC_MAX_TIME_STEP_COUNT`](embree4_sys::RTC_MAX_TIME_STEP_COUNT).
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the count was set, or an error if the count is out of range
+    /// or an error occurred.
+    pub fn set_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        set_time_step_count(
+            self.handle,
+            device,
+            self.vertex_count,
+            &mut self.time_step_count,
+            count,
+        )
+    }
+
+    /// Replaces the vertex positions and radii of a time step, and recommits the geometry.
+    ///
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to set the vertices for, see
+    ///   [`LinearCurveGeometry::set_time_step_count`].
+    /// * `vertices` - The vertex positions and radii, as many as the geometry was created with.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the vertices were set, or an error if the time step or vertex
+    /// count is out of range or an error occurred.
+    pub fn set_vertices(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        vertices: &[(f32, f32, f32, f32)],
+    ) -> Result<()> {
+        set_vertices(
+            self.handle,
+            device,
+            self.vertex_count,
+            self.time_step_count,
+            time_step,
+            vertices,
+        )
+    }
+
+    fn try_new_with_type(
+        device: &Device,
+        geometry_type: embree4_sys::RTCGeometryType,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        if let Some(&index) = indices.iter().find(|&&i| i as usize + 1 >= vertices.len()) {
+            bail!(
+                "Curve segment index {} is out of range for {} vertices",
+                index,
+                vertices.len()
+            );
+        }
+
+        let handle = new_curve(device, geometry_type, vertices, indices)?;
+        let geometry = Self {
+            handle,
+            vertex_count: vertices.len(),
+            time_step_count: 1,
+        };
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit curve geometry")
+    }
+}
+
+/// A set of cubic Bézier curves with a radius at each control point, e.g. for hair grooms.
+///
+/// Each index references the first of four consecutive control points forming a curve. Hits
+/// report the curve index as the primitive ID, and the curve parameter as `u`, ranging from `0`
+/// at the first control point to `1` at the last.
+pub struct BezierCurveGeometry {
+    handle: embree4_sys::RTCGeometry,
+    vertex_count: usize,
+    time_step_count: u32,
+}
+
+impl BezierCurveGeometry {
+    /// Constructs a new `BezierCurveGeometry` instance of flat, ray-facing ribbons from the given
+    /// control points and indices.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The control point positions and radii, as `(x, y, z, radius)`.
+    /// * `indices` - The index of the first control point of each curve.
+    ///
+    /// # Returns
+    /// A `Result` containing the `BezierCurveGeometry` if successful, or an error if an index is
+    /// out of range or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// // A single strand of hair
+    /// let vertices = [
+    ///     (0.0, 0.0, 0.0, 0.01),
+    ///     (0.0, 1.0, 0.0, 0.01),
+    ///     (0.5, 1.5, 0.0, 0.005),
+    ///     (1.0, 1.5, 0.0, 0.001),
+    /// ];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = BezierCurveGeometry::try_new(&device, &vertices, &[0]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry);
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        Self::try_new_with_type(
+            device,
+            embree4_sys::RTCGeometryType::FLAT_BEZIER_CURVE,
+            vertices,
+            indices,
+        )
+    }
+
+    /// Constructs a new `BezierCurveGeometry` instance of round tubes from the given control
+    /// points and indices.
+    ///
+    /// Round curves look correct up close, at a higher cost than flat curves.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The control point positions and radii, as `(x, y, z, radius)`.
+    /// * `indices` - The index of the first control point of each curve.
+    ///
+    /// # Returns
+    /// A `Result` containing the `BezierCurveGeometry` if successful, or an error if an index is
+    /// out of range or an error occurred.
+    pub fn try_new_round(
+        device: &Device,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        Self::try_new_with_type(
+            device,
+            embree4_sys::RTCGeometryType::ROUND_BEZIER_CURVE,
+            vertices,
+            indices,
+        )
+    }
+
+    /// Sets the maximum factor by which Embree's min-width feature may scale the curve radii, and
+    /// recommits the geometry.
+    ///
+    /// The min-width feature enlarges distant curves during traversal, and is only available if
+    /// Embree was compiled with `EMBREE_CURVE_MINWIDTH`. Bounds are enlarged by this factor, so
+    /// the enlarged curves are not clipped by the acceleration structure. This has no effect on
+    /// radii which change over time, see [`BezierCurveGeometry::set_time_step_count`] for motion blur.
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `scale` - The maximum radius scale, at least `1.0`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the scale was set, or an error if an error occurred.
+    pub fn set_max_radius_scale(&mut self, device: &Device, scale: f32) -> Result<()> {
+        set_max_radius_scale(self.handle, device, scale)
+    }
+
+    /// Sets the number of ray-facing quads each curve is tessellated into, and recommits the
+    /// geometry.
+    ///
+    /// Only flat curves, see [`BezierCurveGeometry::try_new`], are tessellated, so lower rates trade
+    /// accuracy for memory and build time. The default rate is `4.0`. Scenes the geometry is
+    /// attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `rate` - The number of quads per curve, at least `1.0`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the rate was set, or an error if an error occurred.
+    pub fn set_tessellation_rate(&mut self, device: &Device, rate: f32) -> Result<()> {
+        set_tessellation_rate(self.handle, device, rate)
+    }
+
+    /// Sets the number of time steps for motion blur, and recommits the geometry.
+    ///
+    /// Time steps are evenly distributed over the ray time range `[0, 1]`, and Embree
+    /// interpolates positions and radii linearly between them, so the bounds cover the curves
+    /// at every time. Newly added time steps start out with the vertices of the first time step,
+    /// use [`BezierCurveGeometry::set_vertices`] to move them. Scenes the geometry is attached to must
+    /// be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `count` - The number of time steps, from 1 to
+    ///   [`RTC_MAX_TIME_STEP_COUNT`](embree4_sys::RTC_MAX_TIME_STEP_COUNT).
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the count was set, or an error if the count is out of range
+    /// or an error occurred.
+    pub fn set_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        set_time_step_count(
+            self.handle,
+            device,
+            self.vertex_count,
+            &mut self.time_step_count,
+            count,
+        )
+    }
+
+    /// Replaces the vertex positions and radii of a time step, and recommits the geometry.
+    ///
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to set the vertices for, see
+    ///   [`BezierCurveGeometry::set_time_step_count`].
+    /// * `vertices` - The vertex positions and radii, as many as the geometry was created with.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the vertices were set, or an error if the time step or vertex
+    /// count is out of range or an error occurred.
+    pub fn set_vertices(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        vertices: &[(f32, f32, f32, f32)],
+    ) -> Result<()> {
+        set_vertices(
+            self.handle,
+            device,
+            self.vertex_count,
+            self.time_step_count,
+            time_step,
+            vertices,
+        )
+    }
+
+    fn try_new_with_type(
+        device: &Device,
+        geometry_type: embree4_sys::RTCGeometryType,
+        vertices: &[(f32, f32, f32, f32)],
+        indices: &[u32],
+    ) -> Result<Self> {
+        if let Some(&index) = indices.iter().find(|&&i| i as usize + 3 >= vertices.len()) {
+            bail!(
+                "Bezier curve index {} is out of range for {} control points",
+                index,
+                vertices.len()
+            );
+        }
+
+        let handle = new_curve(device, geometry_type, vertices, indices)?;
+        let geometry = Self {
+            handle,
+            vertex_count: vertices.len(),
+            time_step_count: 1,
+        };
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit curve geometry")
+    }
+}
+
+// Embree geometry handles are reference counted and may be used from any thread
+unsafe impl Send for BezierCurveGeometry {}
+unsafe impl Sync for BezierCurveGeometry {}
+
+impl Drop for BezierCurveGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for BezierCurveGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+}
+
+/// A capsule, as its end points `p0` and `p1` and its radius.
+pub type Capsule = ((f32, f32, f32), (f32, f32, f32), f32);
+
+/// A set of capsules, e.g. for physics debug shapes and character colliders.
+///
+/// Built on round linear curves, with one segment per capsule. Hits report the index of the
+/// capsule as the primitive ID.
+pub struct CapsulesGeometry {
+    curve: LinearCurveGeometry,
+}
+
+impl CapsulesGeometry {
+    /// Constructs a new `CapsulesGeometry` from the end points and radii of the capsules.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `capsules` - The capsules, as `(p0, p1, radius)`.
+    ///
+    /// # Returns
+    /// A `Result` containing the `CapsulesGeometry` if successful, or an error if an error
+    /// occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let colliders = [((0.0, 0.5, 0.0), (0.0, 1.5, 0.0), 0.5)];
+    /// let geometry = CapsulesGeometry::try_new(&device, &colliders).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// ```
+    pub fn try_new(device: &Device, capsules: &[Capsule]) -> Result<Self> {
+        let vertices: Vec<_> = capsules
+            .iter()
+            .flat_map(|&(p0, p1, radius)| [(p0.0, p0.1, p0.2, radius), (p1.0, p1.1, p1.2, radius)])
+            .collect();
+        let indices: Vec<_> = (0..capsules.len() as u32).map(|i| 2 * i).collect();
+        let curve = LinearCurveGeometry::try_new_round(device, &vertices, &indices)?;
+        Ok(Self { curve })
+    }
+}
+
+impl Geometry for CapsulesGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.curve.geometry()
+    }
+}
+
+fn set_max_radius_scale(
+    handle: embree4_sys::RTCGeometry,
+    device: &Device,
+    scale: f32,
+) -> Result<()> {
+    if scale.is_nan() || scale < 1.0 {
+        bail!("Curve max radius scale must be at least 1.0, got {}", scale);
+    }
+
+    unsafe {
+        embree4_sys::rtcSetGeometryMaxRadiusScale(handle, scale);
+        embree4_sys::rtcCommitGeometry(handle);
+    }
+    device_error_or(device, (), "Failed to set curve max radius scale")
+}
+
+fn set_tessellation_rate(
+    handle: embree4_sys::RTCGeometry,
+    device: &Device,
+    rate: f32,
+) -> Result<()> {
+    if rate.is_nan() || rate < 1.0 {
+        bail!("Tessellation rate must be at least 1.0, got {}", rate);
+    }
+
+    unsafe {
+        embree4_sys::rtcSetGeometryTessellationRate(handle, rate);
+        embree4_sys::rtcCommitGeometry(handle);
+    }
+    device_error_or(device, (), "Failed to set curve tessellation rate")
+}
+
+fn set_time_step_count(
+    handle: embree4_sys::RTCGeometry,
+    device: &Device,
+    vertex_count: usize,
+    time_step_count: &mut u32,
+    count: u32,
+) -> Result<()> {
+    if count == 0 || count > embree4_sys::RTC_MAX_TIME_STEP_COUNT {
+        bail!(
+            "Time step count must be from 1 to {}, got {}",
+            embree4_sys::RTC_MAX_TIME_STEP_COUNT,
+            count
+        );
+    }
+
+    let first_ptr = unsafe {
+        embree4_sys::rtcGetGeometryBufferData(handle, embree4_sys::RTCBufferType::VERTEX, 0)
+    };
+    if first_ptr.is_null() {
+        return Err(device_error(device, "Failed to get curve vertex buffer"));
+    }
+    let first: Vec<_> =
+        unsafe { slice::from_raw_parts(first_ptr as *const [f32; 4], vertex_count) }
+            .iter()
+            .map(|&[x, y, z, radius]| (x, y, z, radius))
+            .collect();
+
+    unsafe {
+        embree4_sys::rtcSetGeometryTimeStepCount(handle, count);
+    }
+    device_error_or(device, (), "Failed to set curve time step count")?;
+
+    for time_step in *time_step_count..count {
+        write_vertices(handle, device, time_step, &first)?;
+    }
+    *time_step_count = count;
+
+    unsafe {
+        embree4_sys::rtcCommitGeometry(handle);
+    }
+    device_error_or(device, (), "Failed to commit curve geometry")
+}
+
+fn set_vertices(
+    handle: embree4_sys::RTCGeometry,
+    device: &Device,
+    vertex_count: usize,
+    time_step_count: u32,
+    time_step: u32,
+    vertices: &[(f32, f32, f32, f32)],
+) -> Result<()> {
+    if time_step >= time_step_count {
+        bail!(
+            "Time step {} is out of range for {} time steps",
+            time_step,
+            time_step_count
+        );
+    }
+    if vertices.len() != vertex_count {
+        bail!("Expected {} vertices, got {}", vertex_count, vertices.len());
+    }
+
+    write_vertices(handle, device, time_step, vertices)?;
+
+    unsafe {
+        embree4_sys::rtcCommitGeometry(handle);
+    }
+    device_error_or(device, (), "Failed to commit curve geometry")
+}
+
+/// Allocates the vertex buffer of a time step and fills it with `(x, y, z, radius)` vertices.
+fn write_vertices(
+    handle: embree4_sys::RTCGeometry,
+    device: &Device,
+    time_step: u32,
+    vertices: &[(f32, f32, f32, f32)],
+) -> Result<()> {
+    let vertex_buf_ptr = unsafe {
+        embree4_sys::rtcSetNewGeometryBuffer(
+            handle,
+            embree4_sys::RTCBufferType::VERTEX,
+            time_step,
+            embree4_sys::RTCFormat::FLOAT4,
+            4 * size_of::<f32>(),
+            vertices.len(),
+        )
+    };
+    if vertex_buf_ptr.is_null() {
+        return Err(device_error(device, "Failed to create curve vertex buffer"));
+    }
+
+    let vertex_buf =
+        unsafe { slice::from_raw_parts_mut(vertex_buf_ptr as *mut f32, 4 * vertices.len()) };
+    for (i, v) in vertices.iter().enumerate() {
+        vertex_buf[4 * i] = v.0;
+        vertex_buf[4 * i + 1] = v.1;
+        vertex_buf[4 * i + 2] = v.2;
+        vertex_buf[4 * i + 3] = v.3;
+    }
+
+    Ok(())
+}
+
+/// Creates a curve geometry of the given type with `(x, y, z, radius)` vertices and one index
+/// per curve, without committing it.
+fn new_curve(
+    device: &Device,
+    geometry_type: embree4_sys::RTCGeometryType,
+    vertices: &[(f32, f32, f32, f32)],
+    indices: &[u32],
+) -> Result<embree4_sys::RTCGeometry> {
+    let handle = unsafe { embree4_sys::rtcNewGeometry(device.handle, geometry_type) };
+    if handle.is_null() {
+        return Err(device_error(device, "Failed to create geometry"));
+    }
+
+    if let Err(e) = write_vertices(handle, device, 0, vertices) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(handle);
+        }
+        return Err(e);
+    }
+
+    let index_buf_ptr = unsafe {
+        embree4_sys::rtcSetNewGeometryBuffer(
+            handle,
+            embree4_sys::RTCBufferType::INDEX,
+            0,
+            embree4_sys::RTCFormat::UINT,
+            size_of::<u32>(),
+            indices.len(),
+        )
+    };
+    if index_buf_ptr.is_null() {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(handle);
+        }
+        return Err(device_error(device, "Failed to create curve index buffer"));
+    }
+
+    let index_buf = unsafe { slice::from_raw_parts_mut(index_buf_ptr as *mut u32, indices.len()) };
+    index_buf.copy_from_slice(indices);
+
+    Ok(handle)
+}
+
+// Embree geometry handles are reference counted and may be used from any thread
+unsafe impl Send for LinearCurveGeometry {}
+unsafe impl Sync for LinearCurveGeometry {}
+
+impl Drop for LinearCurveGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for LinearCurveGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+}
+
+#[test]
+fn flat_linear_curve_hit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(0.0, -1.0, 5.0, 0.1), (0.0, 1.0, 5.0, 0.1)];
+    let wire = LinearCurveGeometry::try_new(&device, &vertices, &[0]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&wire).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let hit = scene
+        .intersect_1(crate::Ray::new((0.05, 0.5, 0.0), (0.0, 0.0, 1.0)))
+        .unwrap()
+        .unwrap();
+    assert!((hit.t() - 5.0).abs() < 1e-4);
+    assert!((hit.uv().0 - 0.75).abs() < 1e-4);
+
+    let miss = crate::Ray::new((0.2, 0.5, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.intersect_1(miss).unwrap().is_none());
+
+    let mut wire = wire;
+    wire.set_max_radius_scale(&device, 2.0).unwrap();
+    assert!(wire.set_max_radius_scale(&device, 0.5).is_err());
+
+    assert!(LinearCurveGeometry::try_new(&device, &vertices, &[1]).is_err());
+}
+
+#[test]
+fn round_linear_curve_capsule() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(0.0, 0.0, 5.0, 0.5), (0.0, 2.0, 5.0, 0.5)];
+    let capsule = LinearCurveGeometry::try_new_round(&device, &vertices, &[0]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&capsule).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let t = |origin| {
+        let ray = crate::Ray::new(origin, (0.0, 0.0, 1.0));
+        scene.intersect_1(ray).unwrap().map(|hit| hit.t())
+    };
+
+    // The cylinder bulges towards the ray, and the end caps are hemispheres
+    assert!((t((0.0, 1.0, 0.0)).unwrap() - 4.5).abs() < 1e-3);
+    assert!((t((0.3, 1.0, 0.0)).unwrap() - 4.6).abs() < 1e-3);
+    assert!((t((0.0, -0.3, 0.0)).unwrap() - 4.6).abs() < 1e-3);
+    assert!(t((0.0, -0.6, 0.0)).is_none());
+}
+
+#[test]
+fn curve_radius_motion_blur() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(0.0, 0.0, 5.0, 0.1), (0.0, 2.0, 5.0, 0.1)];
+    let mut curve = LinearCurveGeometry::try_new_round(&device, &vertices, &[0]).unwrap();
+    curve.set_time_step_count(&device, 2).unwrap();
+    curve
+        .set_vertices(&device, 1, &vertices.map(|(x, y, z, _)| (x, y, z, 1.0)))
+        .unwrap();
+    assert!(curve.set_vertices(&device, 2, &vertices).is_err());
+    assert!(curve.set_vertices(&device, 1, &vertices[..1]).is_err());
+    assert!(curve.set_time_step_count(&device, 0).is_err());
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&curve).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let t = |x, time| {
+        let ray = crate::Ray::new((x, 1.0, 0.0), (0.0, 0.0, 1.0)).with_time(time);
+        scene.intersect_1(ray).unwrap().map(|hit| hit.t())
+    };
+    assert!((t(0.0, 0.0).unwrap() - 4.9).abs() < 1e-3);
+    assert!((t(0.0, 0.5).unwrap() - 4.45).abs() < 1e-3);
+    assert!((t(0.0, 1.0).unwrap() - 4.0).abs() < 1e-3);
+    // The growing radius is covered by the bounds
+    assert!(t(0.5, 0.0).is_none());
+    assert!(t(0.5, 1.0).is_some());
+}
+
+#[test]
+fn bezier_curve_hit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (0.0, 0.0, 5.0, 0.5),
+        (0.0, 1.0, 5.0, 0.5),
+        (0.0, 2.0, 5.0, 0.5),
+        (0.0, 3.0, 5.0, 0.5),
+    ];
+    let ray = crate::Ray::new((0.0, 1.5, 0.0), (0.0, 0.0, 1.0));
+
+    let flat = BezierCurveGeometry::try_new(&device, &vertices, &[0]).unwrap();
+    let round = BezierCurveGeometry::try_new_round(&device, &vertices, &[0]).unwrap();
+    for (geometry, expected_t) in [(&flat, 5.0), (&round, 4.5)] {
+        let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+        scene.attach_geometry(geometry).unwrap();
+        let scene = scene.commit().unwrap();
+
+        let hit = scene.intersect_1(ray).unwrap().unwrap();
+        assert!((hit.t() - expected_t).abs() < 1e-2);
+        assert!((hit.uv().0 - 0.5).abs() < 1e-2);
+    }
+
+    let mut flat = flat;
+    flat.set_tessellation_rate(&device, 8.0).unwrap();
+    assert!(flat.set_tessellation_rate(&device, 0.5).is_err());
+
+    assert!(BezierCurveGeometry::try_new(&device, &vertices, &[1]).is_err());
+}
+
+#[test]
+fn capsules_are_separate() {
+    let device = Device::try_new(None).unwrap();
+    let capsules = [
+        ((0.0, 0.0, 5.0), (0.0, 1.0, 5.0), 0.5),
+        ((3.0, 0.0, 5.0), (3.0, 1.0, 5.0), 0.25),
+    ];
+    let geometry = CapsulesGeometry::try_new(&device, &capsules).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let hit = |x| {
+        let ray = crate::Ray::new((x, 0.5, 0.0), (0.0, 0.0, 1.0));
+        scene
+            .intersect_1(ray)
+            .unwrap()
+            .map(|hit| (hit.t(), hit.prim_id()))
+    };
+    let (t, prim_id) = hit(0.0).unwrap();
+    assert!((t - 4.5).abs() < 1e-3 && prim_id == 0);
+    let (t, prim_id) = hit(3.0).unwrap();
+    assert!((t - 4.75).abs() < 1e-3 && prim_id == 1);
+    // No segment connects the capsules
+    assert!(hit(1.5).is_none());
+}
@@ -0,0 +1,254 @@
+use std::{mem::size_of, slice};
+
+use anyhow::{bail, Result};
+
+use crate::{device_error, device_error_or, Device};
+
+use super::Geometry;
+
+/// The maximum width and height of a grid in vertices supported by Embree.
+const MAX_GRID_SIZE: usize = 32767;
+
+/// A set of regular grids of vertices, e.g. heightfields for terrain, tessellated by Embree
+/// on the fly.
+///
+/// Each grid references a `width` x `height` block of the vertex buffer, see
+/// [`RTCGrid`](embree4_sys::RTCGrid). Hits report the index of the grid as the primitive ID,
+/// and `(u, v)` coordinates ranging from `(0, 0)` at the first vertex of the grid to `(1, 1)`
+/// at the last, with `u` running along the width and `v` along the height.
+pub struct GridGeometry {
+    handle: embree4_sys::RTCGeometry,
+}
+
+impl GridGeometry {
+    /// Constructs a new `GridGeometry` instance from the given vertices and grids.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions.
+    /// * `grids` - The grids, each with a width and height of at least 2 and at most 32767
+    ///   vertices.
+    ///
+    /// # Returns
+    /// A `Result` containing the `GridGeometry` if successful, or an error if a grid is too small,
+    /// too large or exceeds the vertex buffer, or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCGrid;
+    ///
+    /// let vertices = [
+    ///   (0.0, 0.0, 0.0),
+    ///   (1.0, 0.0, 0.0),
+    ///   (0.0, 0.0, 1.0),
+    ///   (1.0, 0.0, 1.0),
+    /// ];
+    /// let grid = RTCGrid {
+    ///     startVertexID: 0,
+    ///     stride: 2,
+    ///     width: 2,
+    ///     height: 2,
+    /// };
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = GridGeometry::try_new(&device, &vertices, &[grid]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry);
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        vertices: &[(f32, f32, f32)],
+        grids: &[embree4_sys::RTCGrid],
+    ) -> Result<Self> {
+        for grid in grids {
+            if grid.width < 2 || grid.height < 2 {
+                bail!("Failed to create grid geometry: grids must be at least 2x2 vertices");
+            }
+            if grid.width as usize > MAX_GRID_SIZE || grid.height as usize > MAX_GRID_SIZE {
+                bail!(
+                    "Failed to create grid geometry: grids must be at most {}x{} vertices",
+                    MAX_GRID_SIZE,
+                    MAX_GRID_SIZE
+                );
+            }
+            let last = grid.startVertexID as usize
+                + (grid.height as usize - 1) * grid.stride as usize
+                + grid.width as usize
+                - 1;
+            if grid.stride < grid.width as u32 || last >= vertices.len() {
+                bail!("Failed to create grid geometry: grid exceeds the vertex buffer");
+            }
+        }
+
+        let handle = unsafe {
+            embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::GRID)
+        };
+        if handle.is_null() {
+            return Err(device_error(device, "Failed to create geometry"));
+        }
+        let geometry = Self { handle };
+
+        let vertex_buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::VERTEX,
+                0,
+                embree4_sys::RTCFormat::FLOAT3,
+                3 * size_of::<f32>(),
+                vertices.len(),
+            )
+        };
+        if vertex_buf_ptr.is_null() {
+            return Err(device_error(device, "Failed to create grid vertex buffer"));
+        }
+
+        let vertex_buf =
+            unsafe { slice::from_raw_parts_mut(vertex_buf_ptr as *mut f32, 3 * vertices.len()) };
+        for (i, v) in vertices.iter().enumerate() {
+            vertex_buf[3 * i] = v.0;
+            vertex_buf[3 * i + 1] = v.1;
+            vertex_buf[3 * i + 2] = v.2;
+        }
+
+        let grid_buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::GRID,
+                0,
+                embree4_sys::RTCFormat::GRID,
+                size_of::<embree4_sys::RTCGrid>(),
+                grids.len(),
+            )
+        };
+        if grid_buf_ptr.is_null() {
+            return Err(device_error(device, "Failed to create grid buffer"));
+        }
+
+        let grid_buf = unsafe {
+            slice::from_raw_parts_mut(grid_buf_ptr as *mut embree4_sys::RTCGrid, grids.len())
+        };
+        grid_buf.copy_from_slice(grids);
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit grid geometry")
+    }
+
+    /// Constructs a heightfield in the xz-plane from a row-major grid of heights, as a single
+    /// grid.
+    ///
+    /// The vertex at column `x` and row `z` is placed at
+    /// `(x * spacing.0, heights[z * width + x], z * spacing.1)`, so hit `u` and `v` coordinates
+    /// map to the x and z extent of the heightfield.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `heights` - The heights, `width` per row.
+    /// * `width` - The number of vertices per row, at least 2 and at most 32767.
+    /// * `spacing` - The distance between neighbouring vertices along x and z.
+    ///
+    /// # Returns
+    /// A `Result` containing the `GridGeometry` if successful, or an error if the heightfield has
+    /// fewer than 2 or more than 32767 vertices along either side, or an error occurred. Use
+    /// [`TerrainGeometry`](super::TerrainGeometry) for larger heightmaps.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let heights = [0.0, 0.5, 0.2, 0.1, 0.7, 0.3, 0.0, 0.4, 0.6];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let terrain = GridGeometry::heightfield(&device, &heights, 3, (10.0, 10.0)).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&terrain);
+    /// ```
+    pub fn heightfield(
+        device: &Device,
+        heights: &[f32],
+        width: usize,
+        spacing: (f32, f32),
+    ) -> Result<Self> {
+        if width == 0 || heights.len() % width != 0 {
+            bail!("Failed to create heightfield: heights must contain whole rows of width {width}");
+        }
+        let height = heights.len() / width;
+        if width > MAX_GRID_SIZE || height > MAX_GRID_SIZE {
+            bail!(
+                "Failed to create heightfield: {}x{} vertices exceed the grid size of {}x{}",
+                width,
+                height,
+                MAX_GRID_SIZE,
+                MAX_GRID_SIZE
+            );
+        }
+
+        let vertices = heightfield_vertices(heights, width, spacing);
+        let grid = embree4_sys::RTCGrid {
+            startVertexID: 0,
+            stride: width as u32,
+            width: width as u16,
+            height: height as u16,
+        };
+        Self::try_new(device, &vertices, &[grid])
+    }
+}
+
+/// Places the row-major `heights` in the xz-plane, the sample at column `x` and row `z` at
+/// `(x * spacing.0, heights[z * width + x], z * spacing.1)`.
+pub(crate) fn heightfield_vertices(
+    heights: &[f32],
+    width: usize,
+    spacing: (f32, f32),
+) -> Vec<(f32, f32, f32)> {
+    heights
+        .iter()
+        .enumerate()
+        .map(|(i, &y)| {
+            let (x, z) = (i % width, i / width);
+            (x as f32 * spacing.0, y, z as f32 * spacing.1)
+        })
+        .collect()
+}
+
+// Embree geometry handles are reference counted and may be used from any thread
+unsafe impl Send for GridGeometry {}
+unsafe impl Sync for GridGeometry {}
+
+impl Drop for GridGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for GridGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+}
+
+#[test]
+fn heightfield_hit_uv() {
+    let device = Device::try_new(None).unwrap();
+    let heights = [1.0; 9];
+    let terrain = GridGeometry::heightfield(&device, &heights, 3, (1.0, 1.0)).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&terrain).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.5, 5.0, 1.5), (0.0, -1.0, 0.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 4.0).abs() < 1e-5);
+    assert_eq!(hit.prim_id(), 0);
+    let (u, v) = hit.uv();
+    assert!((u - 0.25).abs() < 1e-5 && (v - 0.75).abs() < 1e-5);
+
+    assert!(GridGeometry::heightfield(&device, &heights, 2, (1.0, 1.0)).is_err());
+    assert!(GridGeometry::heightfield(&device, &heights[..3], 3, (1.0, 1.0)).is_err());
+    assert!(GridGeometry::heightfield(&device, &[0.0; 2 * 32768], 32768, (1.0, 1.0)).is_err());
+    assert!(GridGeometry::heightfield(&device, &[0.0; 2 * 32768], 2, (1.0, 1.0)).is_err());
+}
@@ -0,0 +1,231 @@
+//! Loading hair strands into curve geometry.
+
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::Device;
+
+use super::LinearCurveGeometry;
+
+/// The header flag for a per-strand segment count array.
+const HAS_SEGMENTS: u32 = 1 << 0;
+/// The header flag for a point array.
+const HAS_POINTS: u32 = 1 << 1;
+/// The header flag for a per-point thickness array.
+const HAS_THICKNESS: u32 = 1 << 2;
+/// The header flag for a per-point transparency array.
+const HAS_TRANSPARENCY: u32 = 1 << 3;
+/// The header flag for a per-point color array.
+const HAS_COLOR: u32 = 1 << 4;
+
+/// Hair strands as polylines with a radius at each point, ready to be turned into curve
+/// geometry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Strands {
+    /// The points of all strands, as `(x, y, z, radius)`.
+    pub vertices: Vec<(f32, f32, f32, f32)>,
+    /// The index of the first point of each segment, as expected by [`LinearCurveGeometry`].
+    pub indices: Vec<u32>,
+}
+
+impl Strands {
+    /// Builds strands from polylines of points.
+    ///
+    /// # Arguments
+    /// * `strands` - The points of each strand, as `(x, y, z, radius)`. Strands with fewer than
+    ///   two points are skipped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let strands = Strands::from_polylines([
+    ///     vec![(0.0, 0.0, 0.0, 0.01), (0.0, 1.0, 0.0, 0.01), (0.1, 2.0, 0.0, 0.005)],
+    ///     vec![(1.0, 0.0, 0.0, 0.01), (1.0, 1.0, 0.0, 0.005)],
+    /// ]);
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = strands.to_geometry(&device).unwrap();
+    /// ```
+    pub fn from_polylines<I, S>(strands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[(f32, f32, f32, f32)]>,
+    {
+        let mut result = Self::default();
+        for strand in strands {
+            result.push(strand.as_ref());
+        }
+        result
+    }
+
+    /// Reads strands from a file in Cem Yuksel's `.hair` format.
+    ///
+    /// Thickness values are interpreted as diameters. Transparency and color are ignored.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the `.hair` file.
+    ///
+    /// # Returns
+    /// A `Result` containing the strands, or an error if the file could not be read or is
+    /// malformed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let strands = Strands::load_hair("wStraight.hair").unwrap();
+    /// let geometry = strands.to_geometry(&device).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// ```
+    pub fn load_hair(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Could not open {:?}", path))?;
+        Self::read_hair(std::io::BufReader::new(file))
+            .with_context(|| format!("Could not read {:?}", path))
+    }
+
+    /// Like [`Strands::load_hair`], but reads from any reader.
+    pub fn read_hair(mut reader: impl Read) -> Result<Self> {
+        let mut header = [0u8; 128];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != b"HAIR" {
+            bail!("Not a .hair file");
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+        let strand_count = u32_at(4) as usize;
+        let point_count = u32_at(8) as usize;
+        let flags = u32_at(12);
+        let default_segments = u32_at(16) as usize;
+        let default_thickness = f32::from_bits(u32_at(20));
+
+        if flags & HAS_POINTS == 0 {
+            bail!("The .hair file has no points");
+        }
+
+        let segments = if flags & HAS_SEGMENTS != 0 {
+            read_array(&mut reader, strand_count, 2, |b| {
+                u16::from_le_bytes([b[0], b[1]]) as usize
+            })?
+        } else {
+            vec![default_segments; strand_count]
+        };
+        let expected_points: usize = segments.iter().map(|&s| s + 1).sum();
+        if expected_points != point_count {
+            bail!(
+                "The .hair file declares {} points, but its strands have {}",
+                point_count,
+                expected_points
+            );
+        }
+
+        let points = read_array(&mut reader, 3 * point_count, 4, f32_from_le)?;
+        let thickness = if flags & HAS_THICKNESS != 0 {
+            read_array(&mut reader, point_count, 4, f32_from_le)?
+        } else {
+            vec![default_thickness; point_count]
+        };
+        // Transparency and color follow, but are not needed for geometry
+        let _ = (HAS_TRANSPARENCY, HAS_COLOR);
+
+        let mut strands = Self::default();
+        let mut first = 0;
+        for segment_count in segments {
+            let strand: Vec<_> = (first..first + segment_count + 1)
+                .map(|i| {
+                    (
+                        points[3 * i],
+                        points[3 * i + 1],
+                        points[3 * i + 2],
+                        0.5 * thickness[i],
+                    )
+                })
+                .collect();
+            strands.push(&strand);
+            first += segment_count + 1;
+        }
+        Ok(strands)
+    }
+
+    /// Creates a flat [`LinearCurveGeometry`] of the strands, the usual representation for fur
+    /// and hair seen from a distance.
+    pub fn to_geometry(&self, device: &Device) -> Result<LinearCurveGeometry> {
+        LinearCurveGeometry::try_new(device, &self.vertices, &self.indices)
+    }
+
+    /// Creates a round [`LinearCurveGeometry`] of the strands, for close-ups.
+    pub fn to_round_geometry(&self, device: &Device) -> Result<LinearCurveGeometry> {
+        LinearCurveGeometry::try_new_round(device, &self.vertices, &self.indices)
+    }
+
+    fn push(&mut self, strand: &[(f32, f32, f32, f32)]) {
+        if strand.len() < 2 {
+            return;
+        }
+        let first = self.vertices.len() as u32;
+        self.indices
+            .extend((0..strand.len() as u32 - 1).map(|i| first + i));
+        self.vertices.extend_from_slice(strand);
+    }
+}
+
+fn f32_from_le(bytes: &[u8]) -> f32 {
+    f32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+/// Reads `count` values of `size` bytes each.
+fn read_array<T>(
+    reader: &mut impl Read,
+    count: usize,
+    size: usize,
+    parse: impl Fn(&[u8]) -> T,
+) -> Result<Vec<T>> {
+    let mut bytes = vec![0u8; count * size];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.chunks_exact(size).map(parse).collect())
+}
+
+#[test]
+fn read_hair_file() {
+    let mut file = Vec::new();
+    file.extend_from_slice(b"HAIR");
+    for value in [2u32, 5, HAS_SEGMENTS | HAS_POINTS | HAS_THICKNESS, 0] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.resize(128, 0);
+    for segments in [2u16, 1] {
+        file.extend_from_slice(&segments.to_le_bytes());
+    }
+    for i in 0..5 {
+        for coordinate in [i as f32, 0.0, 1.0] {
+            file.extend_from_slice(&coordinate.to_le_bytes());
+        }
+    }
+    for i in 0..5 {
+        file.extend_from_slice(&(0.1 * i as f32).to_le_bytes());
+    }
+
+    let strands = Strands::read_hair(file.as_slice()).unwrap();
+    assert_eq!(strands.vertices.len(), 5);
+    assert_eq!(strands.vertices[4], (4.0, 0.0, 1.0, 0.2));
+    // Segments start at each point but the last of each strand
+    assert_eq!(strands.indices, [0, 1, 3]);
+
+    assert!(Strands::read_hair(&file[..100]).is_err());
+    assert!(Strands::read_hair(&file[..140]).is_err());
+}
+
+#[test]
+fn from_polylines_skips_degenerate_strands() {
+    let strands = Strands::from_polylines([
+        vec![(0.0, 0.0, 0.0, 0.1), (0.0, 1.0, 0.0, 0.1)],
+        vec![(5.0, 0.0, 0.0, 0.1)],
+        vec![(1.0, 0.0, 0.0, 0.1), (1.0, 1.0, 0.0, 0.1)],
+    ]);
+    assert_eq!(strands.vertices.len(), 4);
+    assert_eq!(strands.indices, [0, 2]);
+}
@@ -0,0 +1,365 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::Result;
+
+use crate::{device_error, device_error_or, scene::AttachedGeometries, CommittedScene, Device};
+
+use super::Geometry;
+
+/// The identity transform, as a 3x4 column-major matrix.
+const IDENTITY: [f32; 12] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+
+/// An instance of a committed scene, placed with an affine transform.
+///
+/// Instancing lets many copies of the same scene share one acceleration structure. Hits on
+/// instanced geometry report the geometry ID within the instanced scene, and the geometry ID of
+/// the instance in [`Hit::inst_id`](crate::Hit::inst_id).
+///
+/// Nested instancing, i.e. instancing a scene which itself contains instances, is not
+/// supported: the Embree build these bindings link against supports a single instance level,
+/// see [`MAX_INSTANCE_LEVEL_COUNT`](crate::MAX_INSTANCE_LEVEL_COUNT). Flatten nested hierarchies
+/// by multiplying the transforms into one level of instances instead.
+///
+/// Each instance is a separate geometry. Embree 4.3 adds instance arrays, which attach many
+/// transforms of the same scene as a single geometry, but the Embree 4.2 bindings this crate
+/// builds on do not expose them yet.
+pub struct InstanceGeometry {
+    handle: embree4_sys::RTCGeometry,
+    /// The geometries of the instanced scene, whose data must live as long as the instance.
+    instanced: AttachedGeometries,
+}
+
+impl InstanceGeometry {
+    /// Constructs a new `InstanceGeometry` of `scene`, with the identity transform.
+    ///
+    /// Embree keeps the instanced scene alive as long as the instance, and the instance keeps
+    /// the data of the instanced scene's geometries alive.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `scene` - The scene to instance.
+    ///
+    /// # Returns
+    /// A `Result` containing the `InstanceGeometry` if successful, or an error if an error
+    /// occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let tree = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let tree = tree.commit().unwrap();
+    ///
+    /// let forest = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let mut instances = Vec::new();
+    /// for i in 0..100 {
+    ///     let mut instance = InstanceGeometry::try_new(&device, &tree).unwrap();
+    ///     #[rustfmt::skip]
+    ///     instance.set_transform(&device, 0, &[
+    ///         1.0, 0.0, 0.0,
+    ///         0.0, 1.0, 0.0,
+    ///         0.0, 0.0, 1.0,
+    ///         10.0 * i as f32, 0.0, 0.0,
+    ///     ]).unwrap();
+    ///     forest.attach_geometry(&instance).unwrap();
+    ///     instances.push(instance);
+    /// }
+    /// let forest = forest.commit().unwrap();
+    /// ```
+    pub fn try_new(device: &Device, scene: &CommittedScene) -> Result<Self> {
+        let handle = unsafe {
+            embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::INSTANCE)
+        };
+        if handle.is_null() {
+            return Err(device_error(device, "Failed to create geometry"));
+        }
+        let mut geometry = Self {
+            handle,
+            instanced: scene.scene.attached.clone(),
+        };
+
+        unsafe {
+            embree4_sys::rtcSetGeometryInstancedScene(handle, scene.scene.handle);
+        }
+        device_error_or(device, (), "Failed to set instanced scene")?;
+
+        geometry.set_transform(device, 0, &IDENTITY)?;
+        Ok(geometry)
+    }
+
+    /// Sets the transform of the instance at a time step, and recommits the geometry.
+    ///
+    /// Scenes the instance is attached to must be committed again for the change to take
+    /// effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to set the transform for, `0` without motion blur.
+    /// * `transform` - The 3x4 column-major affine transform from instance to world space, i.e.
+    ///   the columns of the linear part followed by the translation.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the transform was set, or an error if an error occurred.
+    pub fn set_transform(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        transform: &[f32; 12],
+    ) -> Result<()> {
+        self.set_transform_with_format(
+            device,
+            time_step,
+            embree4_sys::RTCFormat::FLOAT3X4_COLUMN_MAJOR,
+            transform,
+        )
+    }
+
+    /// Like [`InstanceGeometry::set_transform`], but takes a 4x4 column-major matrix, as used by
+    /// most math libraries. The last row is ignored.
+    pub fn set_transform_4x4(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        transform: &[f32; 16],
+    ) -> Result<()> {
+        self.set_transform_with_format(
+            device,
+            time_step,
+            embree4_sys::RTCFormat::FLOAT4X4_COLUMN_MAJOR,
+            transform,
+        )
+    }
+
+    /// Sets the number of time steps for motion blur.
+    ///
+    /// The transforms of all time steps must be set afterwards, which also recommits the
+    /// geometry. Embree interpolates the transform between time steps, which are evenly distributed
+    /// over the ray time range `[0, 1]`.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `count` - The number of time steps, from 1 to
+    ///   [`RTC_MAX_TIME_STEP_COUNT`](embree4_sys::RTC_MAX_TIME_STEP_COUNT).
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the count was set, or an error if an error occurred.
+    pub fn set_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcSetGeometryTimeStepCount(self.handle, count);
+        }
+        device_error_or(device, (), "Failed to set instance time step count")
+    }
+
+    /// Sets the transform of the instance at a time step as a quaternion decomposition, and
+    /// recommits the geometry.
+    ///
+    /// Between time steps set this way, Embree interpolates the rotation spherically instead of
+    /// linearly interpolating the matrices, so rotating instances like propellers and wheels
+    /// get correct curved motion blur. All time steps must use the same representation.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to set the transform for.
+    /// * `decomposition` - The transform, applied as scale and skew, then shift, then rotation
+    ///   by the unit quaternion, then translation.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the transform was set, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCQuaternionDecomposition;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let propeller = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let propeller = propeller.commit().unwrap();
+    ///
+    /// // Half a turn around the z axis over the shutter interval
+    /// let rotation = |angle: f32| RTCQuaternionDecomposition {
+    ///     scale_x: 1.0,
+    ///     scale_y: 1.0,
+    ///     scale_z: 1.0,
+    ///     skew_xy: 0.0,
+    ///     skew_xz: 0.0,
+    ///     skew_yz: 0.0,
+    ///     shift_x: 0.0,
+    ///     shift_y: 0.0,
+    ///     shift_z: 0.0,
+    ///     quaternion_r: (angle / 2.0).cos(),
+    ///     quaternion_i: 0.0,
+    ///     quaternion_j: 0.0,
+    ///     quaternion_k: (angle / 2.0).sin(),
+    ///     translation_x: 0.0,
+    ///     translation_y: 0.0,
+    ///     translation_z: 0.0,
+    /// };
+    ///
+    /// let mut instance = InstanceGeometry::try_new(&device, &propeller).unwrap();
+    /// instance.set_time_step_count(&device, 3).unwrap();
+    /// for step in 0..3 {
+    ///     let angle = step as f32 * std::f32::consts::FRAC_PI_2;
+    ///     instance.set_transform_quaternion(&device, step, &rotation(angle)).unwrap();
+    /// }
+    /// ```
+    pub fn set_transform_quaternion(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        decomposition: &embree4_sys::RTCQuaternionDecomposition,
+    ) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcSetGeometryTransformQuaternion(self.handle, time_step, decomposition);
+        }
+        device_error_or(device, (), "Failed to set instance transform")?;
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Failed to commit instance geometry")
+    }
+
+    fn set_transform_with_format(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        format: embree4_sys::RTCFormat,
+        transform: &[f32],
+    ) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcSetGeometryTransform(
+                self.handle,
+                time_step,
+                format,
+                transform.as_ptr() as *const _,
+            );
+        }
+        device_error_or(device, (), "Failed to set instance transform")?;
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Failed to commit instance geometry")
+    }
+}
+
+// The instanced scene is retained by Embree, and geometry handles may be used from any thread
+unsafe impl Send for InstanceGeometry {}
+unsafe impl Sync for InstanceGeometry {}
+
+impl Drop for InstanceGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for InstanceGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        Some(self.instanced.clone())
+    }
+}
+
+#[test]
+fn instance_transform_and_inst_id() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    let triangle = super::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let object = crate::Scene::try_new(&device, Default::default()).unwrap();
+    object.attach_geometry(&triangle).unwrap();
+    let object = object.commit().unwrap();
+
+    let mut near = InstanceGeometry::try_new(&device, &object).unwrap();
+    near.set_transform(
+        &device,
+        0,
+        &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 2.0],
+    )
+    .unwrap();
+    let mut far = InstanceGeometry::try_new(&device, &object).unwrap();
+    #[rustfmt::skip]
+    far.set_transform_4x4(&device, 0, &[
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        10.0, 0.0, 5.0, 1.0,
+    ])
+    .unwrap();
+
+    let world = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let near_id = world.attach_geometry(&near).unwrap();
+    let far_id = world.attach_geometry(&far).unwrap();
+    let world = world.commit().unwrap();
+
+    let hit = world
+        .intersect_1(crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (hit.t(), hit.geom_id(), hit.inst_id()),
+        (2.0, 0, Some(near_id))
+    );
+
+    let hit = world
+        .intersect_1(crate::Ray::new((10.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (hit.t(), hit.geom_id(), hit.inst_id()),
+        (5.0, 0, Some(far_id))
+    );
+}
+
+#[test]
+fn quaternion_motion_blur_rotates() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(1.8, -0.2, 5.0), (2.2, -0.2, 5.0), (2.0, 0.2, 5.0)];
+    let triangle = super::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let object = crate::Scene::try_new(&device, Default::default()).unwrap();
+    object.attach_geometry(&triangle).unwrap();
+    let object = object.commit().unwrap();
+
+    let rotation = |angle: f32| embree4_sys::RTCQuaternionDecomposition {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        scale_z: 1.0,
+        skew_xy: 0.0,
+        skew_xz: 0.0,
+        skew_yz: 0.0,
+        shift_x: 0.0,
+        shift_y: 0.0,
+        shift_z: 0.0,
+        quaternion_r: (angle / 2.0).cos(),
+        quaternion_i: 0.0,
+        quaternion_j: 0.0,
+        quaternion_k: (angle / 2.0).sin(),
+        translation_x: 0.0,
+        translation_y: 0.0,
+        translation_z: 0.0,
+    };
+    let mut instance = InstanceGeometry::try_new(&device, &object).unwrap();
+    instance.set_time_step_count(&device, 2).unwrap();
+    instance
+        .set_transform_quaternion(&device, 0, &rotation(0.0))
+        .unwrap();
+    instance
+        .set_transform_quaternion(&device, 1, &rotation(std::f32::consts::FRAC_PI_2))
+        .unwrap();
+
+    let world = crate::Scene::try_new(&device, Default::default()).unwrap();
+    world.attach_geometry(&instance).unwrap();
+    let world = world.commit().unwrap();
+
+    // Halfway through, the triangle is rotated by 45 degrees, not linearly interpolated
+    let r = std::f32::consts::SQRT_2;
+    let ray = crate::Ray::new((r, r, 0.0), (0.0, 0.0, 1.0)).with_time(0.5);
+    assert!(world.intersect_1(ray).unwrap().is_some());
+    let ray = crate::Ray::new((1.0, 1.0, 0.0), (0.0, 0.0, 1.0)).with_time(0.5);
+    assert!(world.intersect_1(ray).unwrap().is_none());
+}
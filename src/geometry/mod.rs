@@ -1,8 +1,42 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+
+use crate::{device_error_or, Device};
+
+mod curve;
+mod grid;
+#[cfg(feature = "hair")]
+mod hair;
+mod instance;
+mod plane;
+mod quad_mesh;
+mod sdf;
+mod subdivision;
+mod terrain;
 mod tri_mesh;
 mod user;
+mod user_packet;
+mod validate;
 
+pub use curve::*;
+pub use grid::*;
+#[cfg(feature = "hair")]
+pub use hair::*;
+pub use instance::*;
+pub use plane::*;
+pub use quad_mesh::*;
+pub use sdf::*;
+pub use subdivision::*;
+pub use terrain::*;
 pub use tri_mesh::*;
 pub use user::*;
+pub use user_packet::*;
+pub use validate::*;
 
 /// A trait implemented by all geometry types.
 /// If you want to implement your own geometry type, you must implement this trait.
@@ -12,4 +46,184 @@ pub use user::*;
 pub trait Geometry {
     /// Returns the handle of the geometry.
     fn geometry(&self) -> embree4_sys::RTCGeometry;
+
+    /// Returns the data owned by the geometry which Embree points to, e.g. callbacks or vertex
+    /// buffers.
+    ///
+    /// Scenes keep this data alive while the geometry is attached, so the geometry may be
+    /// dropped while scenes still use it. Geometries which do not own such data return `None`,
+    /// which is the default.
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        None
+    }
+
+    /// Commits the geometry after changes to it, e.g. by [`Geometry::set_mask`].
+    ///
+    /// The setters of this trait do not commit the geometry, so several changes can be applied
+    /// at once, like [`TriangleMeshBuilder`] does. Scenes the geometry is attached to must be
+    /// committed again for the changes to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the geometry was committed, or an error if an error occurred.
+    fn commit(&self, device: &Device) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.geometry());
+        }
+        device_error_or(device, (), "Failed to commit geometry")
+    }
+
+    /// Sets the geometry mask.
+    ///
+    /// Rays only hit the geometry if the bitwise AND of the ray mask (see [`crate::Ray::mask`])
+    /// and the geometry mask is non-zero. The default mask is `u32::MAX`. The geometry and the
+    /// scenes it is attached to must be committed again for the change to take effect, see
+    /// [`Geometry::commit`].
+    ///
+    /// Ray masks must be enabled in the Embree build, see
+    /// [`RTCDeviceProperty::RAY_MASK_SUPPORTED`](embree4_sys::RTCDeviceProperty::RAY_MASK_SUPPORTED).
+    /// Errors are reported through [`crate::Device::error`].
+    ///
+    /// # Arguments
+    /// * `mask` - The geometry mask.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// const CAMERA: u32 = 0b01;
+    /// const SHADOW: u32 = 0b10;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let light_proxy = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///
+    /// // Visible to the camera, but casts no shadows
+    /// light_proxy.set_mask(CAMERA);
+    /// light_proxy.commit(&device).unwrap();
+    ///
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&light_proxy).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// assert!(scene.intersect_1(ray.with_mask(CAMERA)).unwrap().is_some());
+    /// assert!(!scene.occluded_1(ray.with_mask(SHADOW)).unwrap());
+    /// ```
+    fn set_mask(&self, mask: u32) {
+        unsafe {
+            embree4_sys::rtcSetGeometryMask(self.geometry(), mask);
+        }
+    }
+
+    /// Sets the quality of the BVH built for the geometry.
+    ///
+    /// Use [`RTCBuildQuality::REFIT`](embree4_sys::RTCBuildQuality::REFIT) for deforming
+    /// geometry whose topology does not change, so [`crate::Scene::refit`] only updates the
+    /// bounds of the existing BVH instead of rebuilding it. The geometry and the scenes it is
+    /// attached to must be committed again for the change to take effect, see
+    /// [`Geometry::commit`].
+    ///
+    /// Errors are reported through [`crate::Device::error`].
+    ///
+    /// # Arguments
+    /// * `quality` - The build quality.
+    fn set_build_quality(&self, quality: embree4_sys::RTCBuildQuality) {
+        unsafe {
+            embree4_sys::rtcSetGeometryBuildQuality(self.geometry(), quality);
+        }
+    }
+
+    /// Enables the geometry, so rays hit it again after [`Geometry::disable`].
+    ///
+    /// Geometries are enabled by default. Scenes the geometry is attached to must be committed
+    /// again for the change to take effect. Errors are reported through
+    /// [`crate::Device::error`].
+    fn enable(&self) {
+        unsafe {
+            embree4_sys::rtcEnableGeometry(self.geometry());
+        }
+    }
+
+    /// Disables the geometry, so rays ignore it without detaching it from its scenes.
+    ///
+    /// Scenes the geometry is attached to must be committed again for the change to take
+    /// effect. Errors are reported through [`crate::Device::error`].
+    fn disable(&self) {
+        unsafe {
+            embree4_sys::rtcDisableGeometry(self.geometry());
+        }
+    }
+
+    /// Sets the time range over which the time steps of the geometry are distributed.
+    ///
+    /// By default, time steps span the whole ray time range `[0, 1]`. Use this for geometry
+    /// animated over only part of the shutter interval, or with key times that differ from
+    /// other geometry. The geometry and the scenes it is attached to must be committed again for
+    /// the change to take effect, see [`Geometry::commit`].
+    ///
+    /// Errors are reported through [`crate::Device::error`].
+    ///
+    /// # Arguments
+    /// * `start` - The ray time of the first time step.
+    /// * `end` - The ray time of the last time step.
+    fn set_time_range(&self, start: f32, end: f32) {
+        unsafe {
+            embree4_sys::rtcSetGeometryTimeRange(self.geometry(), start, end);
+        }
+    }
+}
+
+/// Data owned by a geometry which Embree points to, see [`Geometry::retained_data`].
+///
+/// Pointers to retained data stay valid as long as the geometry or any scene it is attached to.
+/// Data in a slot, e.g. a callback, is freed when the slot is replaced, so the geometry must
+/// point Embree to the new data before it is queried or built again.
+#[derive(Default)]
+pub(crate) struct RetainedData {
+    /// Data which is only added, e.g. owned vertex buffers.
+    data: Mutex<Vec<Box<dyn Any + Send + Sync>>>,
+    /// Data which replaces earlier data of the same slot, e.g. callbacks.
+    slots: Mutex<HashMap<&'static str, Box<dyn Any + Send + Sync>>>,
+    /// The first panic raised by a callback of the geometry while Embree built a scene, resumed
+    /// by [`Scene::commit`](crate::Scene::commit).
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl RetainedData {
+    /// Moves `data` into the retained data, and returns a stable pointer to it.
+    pub(crate) fn retain<T: Any + Send + Sync>(&self, data: T) -> *const T {
+        let data = Box::new(data);
+        let ptr = &*data as *const T;
+        self.data
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(data);
+        ptr
+    }
+
+    /// Moves `data` into `slot`, freeing the data previously in it, and returns a stable pointer
+    /// to it.
+    pub(crate) fn replace<T: Any + Send + Sync>(&self, slot: &'static str, data: T) -> *const T {
+        let data = Box::new(data);
+        let ptr = &*data as *const T;
+        self.slots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(slot, data);
+        ptr
+    }
+
+    /// Stores a panic raised by a callback, unless an earlier one is pending.
+    pub(crate) fn store_panic(&self, panic: Box<dyn Any + Send>) {
+        let mut slot = self.panic.lock().unwrap_or_else(|e| e.into_inner());
+        slot.get_or_insert(panic);
+    }
+
+    /// Takes the pending panic, if any.
+    pub(crate) fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        self.panic.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
 }
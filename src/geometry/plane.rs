@@ -0,0 +1,210 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    math::{add, dot, length, normalize, orthonormal_basis, scale, sub, Vec3},
+    Device,
+};
+
+use super::{Geometry, UserGeometry, UserGeometryImpl};
+
+/// The radius of planes without extent. Embree requires finite bounds, so "infinite" planes are
+/// large discs.
+const UNBOUNDED_RADIUS: f32 = 1e6;
+
+/// A plane through a point, e.g. a ground plane for quick tests and simulations.
+///
+/// The plane is either unbounded or limited to a disc around its point. Hits report the plane
+/// normal as the geometric normal.
+///
+/// Unlike the barycentric `(u, v)` coordinates of meshes, `(u, v)` of a plane hit are the signed
+/// distances of the hit point from the plane point along the tangent and bitangent, see
+/// [`PlaneGeometry::tangent_frame`]. They are in world units and not limited to `[0, 1]`, so
+/// they can be used directly as planar texture coordinates, e.g. for a tiled ground.
+pub struct PlaneGeometry {
+    geometry: UserGeometry<Plane>,
+}
+
+struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    radius: f32,
+}
+
+impl PlaneGeometry {
+    /// Constructs a new `PlaneGeometry` through `point`, facing `normal`.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `point` - A point on the plane.
+    /// * `normal` - The plane normal. Does not need to be normalized.
+    /// * `radius` - Limits the plane to a disc of this radius around `point`, or `None` for an
+    ///   unbounded plane. Unbounded planes extend to a radius of `1e6`, as Embree requires
+    ///   finite bounds.
+    ///
+    /// # Returns
+    /// A `Result` containing the `PlaneGeometry` if successful, or an error if `normal` is zero
+    /// or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let ground = PlaneGeometry::try_new(&device, (0.0, 0.0, 0.0), (0.0, 1.0, 0.0), None).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&ground).unwrap();
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        point: (f32, f32, f32),
+        normal: (f32, f32, f32),
+        radius: Option<f32>,
+    ) -> Result<Self> {
+        let normal_length = length(normal);
+        if !normal_length.is_normal() {
+            bail!("Plane normal {:?} must be non-zero and finite", normal);
+        }
+        let radius = radius.unwrap_or(UNBOUNDED_RADIUS);
+        if radius.is_nan() || radius <= 0.0 {
+            bail!("Plane radius must be positive, got {}", radius);
+        }
+
+        let normal = normalize(normal);
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let plane = Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            radius,
+        };
+        let geometry = UserGeometry::try_new(device, plane)?;
+        Ok(Self { geometry })
+    }
+
+    /// Returns the unit normal of the plane.
+    pub fn normal(&self) -> (f32, f32, f32) {
+        self.geometry.data().normal
+    }
+
+    /// Returns the unit tangent and bitangent of the plane, along which hits report `u` and `v`.
+    ///
+    /// Together with [`PlaneGeometry::normal`], they form a right-handed orthonormal basis.
+    pub fn tangent_frame(&self) -> ((f32, f32, f32), (f32, f32, f32)) {
+        let plane = self.geometry.data();
+        (plane.tangent, plane.bitangent)
+    }
+}
+
+impl Geometry for PlaneGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.geometry.geometry()
+    }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.geometry.retained_data()
+    }
+}
+
+impl UserGeometryImpl for Plane {
+    fn bounds(&self) -> embree4_sys::RTCBounds {
+        // The bounds of a disc extend by `radius * sqrt(1 - n_i^2)` along each axis
+        let extent = |n: f32| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let (ex, ey, ez) = (
+            extent(self.normal.0),
+            extent(self.normal.1),
+            extent(self.normal.2),
+        );
+        embree4_sys::RTCBounds {
+            lower_x: self.point.0 - ex,
+            lower_y: self.point.1 - ey,
+            lower_z: self.point.2 - ez,
+            align0: 0.0,
+            upper_x: self.point.0 + ex,
+            upper_y: self.point.1 + ey,
+            upper_z: self.point.2 + ez,
+            align1: 0.0,
+        }
+    }
+
+    fn intersect(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        ctx: &embree4_sys::RTCRayQueryContext,
+        ray_hit: &mut embree4_sys::RTCRayHit,
+    ) {
+        let ray = &mut ray_hit.ray;
+        let origin = (ray.org_x, ray.org_y, ray.org_z);
+        let direction = (ray.dir_x, ray.dir_y, ray.dir_z);
+
+        let denom = dot(direction, self.normal);
+        if denom == 0.0 {
+            return;
+        }
+        let t = dot(sub(self.point, origin), self.normal) / denom;
+        if !(t >= ray.tnear && t <= ray.tfar) {
+            return;
+        }
+        let local = sub(add(origin, scale(direction, t)), self.point);
+        if length(local) > self.radius {
+            return;
+        }
+
+        ray.tfar = t;
+        let hit = &mut ray_hit.hit;
+        (hit.Ng_x, hit.Ng_y, hit.Ng_z) = self.normal;
+        hit.u = dot(local, self.tangent);
+        hit.v = dot(local, self.bitangent);
+        hit.primID = prim_id;
+        hit.geomID = geom_id;
+        hit.instID = ctx.instID;
+    }
+
+    fn closest_point(&self, _prim_id: u32, point: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+        let offset = sub(point, self.point);
+        let in_plane = sub(offset, scale(self.normal, dot(offset, self.normal)));
+        let distance = length(in_plane);
+        let in_disc = if distance > self.radius {
+            scale(in_plane, self.radius / distance)
+        } else {
+            in_plane
+        };
+        Some(add(self.point, in_disc))
+    }
+}
+
+#[test]
+fn plane_hits_and_extent() {
+    let device = Device::try_new(None).unwrap();
+    let ground = PlaneGeometry::try_new(&device, (0.0, -1.0, 0.0), (0.0, 2.0, 0.0), None).unwrap();
+    let disc =
+        PlaneGeometry::try_new(&device, (0.0, 0.0, 5.0), (0.0, 0.0, -1.0), Some(1.0)).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let ground_id = scene.attach_geometry(&ground).unwrap();
+    let disc_id = scene.attach_geometry(&disc).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let down = crate::Ray::new((100.0, 3.0, -50.0), (0.0, -1.0, 0.0));
+    let hit = scene.intersect_1(down).unwrap().unwrap();
+    assert_eq!((hit.t(), hit.geom_id()), (4.0, ground_id));
+    assert_eq!(hit.normal(), (0.0, 1.0, 0.0));
+
+    let forward = crate::Ray::new((0.5, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(forward).unwrap().unwrap();
+    assert_eq!((hit.t(), hit.geom_id()), (5.0, disc_id));
+    let (tangent, bitangent) = disc.tangent_frame();
+    let (u, v) = hit.uv();
+    assert!((u - 0.5 * tangent.0).abs() < 1e-6 && (v - 0.5 * bitangent.0).abs() < 1e-6);
+    let beside = crate::Ray::new((1.5, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.intersect_1(beside).unwrap().is_none());
+
+    let closest = scene.point_query((0.0, 2.0, 7.0), 2.5).unwrap().unwrap();
+    assert_eq!((closest.point, closest.geom_id), ((0.0, 1.0, 5.0), disc_id));
+
+    assert!(PlaneGeometry::try_new(&device, (0.0, 0.0, 0.0), (0.0, 0.0, 0.0), None).is_err());
+}
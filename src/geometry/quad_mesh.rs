@@ -0,0 +1,277 @@
+use std::{mem::size_of, slice};
+
+use anyhow::{bail, Result};
+
+use crate::{device_error, device_error_or, Device};
+
+use super::Geometry;
+
+/// A mesh of quads, each split by Embree into the triangles `(v0, v1, v3)` and `(v2, v3, v1)`.
+///
+/// Hits report the `(u, v)` coordinates of the quad, which range from `(0, 0)` at `v0` over
+/// `(1, 0)` at `v1` and `(1, 1)` at `v2` to `(0, 1)` at `v3`.
+pub struct QuadMeshGeometry {
+    handle: embree4_sys::RTCGeometry,
+}
+
+impl QuadMeshGeometry {
+    /// Constructs a new `QuadMeshGeometry` instance from the given vertices and indices.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions.
+    /// * `indices` - The quad indices, in counter-clockwise or clockwise order around the quad.
+    ///
+    /// # Returns
+    /// A `Result` containing the `QuadMeshGeometry` if successful, or an error if an index is out
+    /// of range or the geometry could not be created.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let vertices = [
+    ///   (-1.0, -1.0, 0.0),
+    ///   (1.0, -1.0, 0.0),
+    ///   (1.0, 1.0, 0.0),
+    ///   (-1.0, 1.0, 0.0),
+    /// ];
+    /// let indices = [(0, 1, 2, 3)];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = QuadMeshGeometry::try_new(&device, &vertices, &indices).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry);
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        vertices: &[(f32, f32, f32)],
+        indices: &[(u32, u32, u32, u32)],
+    ) -> Result<Self> {
+        // Embree does not check indices, and reads vertices out of bounds for out-of-range ones
+        for idx in indices {
+            for index in [idx.0, idx.1, idx.2, idx.3] {
+                if index as usize >= vertices.len() {
+                    bail!(
+                        "Quad index {} is out of range for {} vertices",
+                        index,
+                        vertices.len()
+                    );
+                }
+            }
+        }
+
+        let handle = unsafe {
+            embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::QUAD)
+        };
+        if handle.is_null() {
+            return Err(device_error(device, "Failed to create geometry"));
+        }
+        let geometry = Self { handle };
+
+        let vertex_buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::VERTEX,
+                0,
+                embree4_sys::RTCFormat::FLOAT3,
+                3 * size_of::<f32>(),
+                vertices.len(),
+            )
+        };
+        if vertex_buf_ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to create quad mesh vertex buffer",
+            ));
+        }
+
+        let vertex_buf =
+            unsafe { slice::from_raw_parts_mut(vertex_buf_ptr as *mut f32, 3 * vertices.len()) };
+        for (i, v) in vertices.iter().enumerate() {
+            vertex_buf[3 * i] = v.0;
+            vertex_buf[3 * i + 1] = v.1;
+            vertex_buf[3 * i + 2] = v.2;
+        }
+
+        let index_buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::INDEX,
+                0,
+                embree4_sys::RTCFormat::UINT4,
+                4 * size_of::<u32>(),
+                indices.len(),
+            )
+        };
+        if index_buf_ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to create quad mesh index buffer",
+            ));
+        }
+
+        let index_buf =
+            unsafe { slice::from_raw_parts_mut(index_buf_ptr as *mut u32, 4 * indices.len()) };
+        for (i, idx) in indices.iter().enumerate() {
+            index_buf[4 * i] = idx.0;
+            index_buf[4 * i + 1] = idx.1;
+            index_buf[4 * i + 2] = idx.2;
+            index_buf[4 * i + 3] = idx.3;
+        }
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit quad mesh geometry")
+    }
+}
+
+// Embree geometry handles are reference counted and may be used from any thread
+unsafe impl Send for QuadMeshGeometry {}
+unsafe impl Sync for QuadMeshGeometry {}
+
+impl Drop for QuadMeshGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for QuadMeshGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+}
+
+/// Splits quads into triangles the way Embree does, so hits on the triangles can be mapped
+/// back to quad `(u, v)` coordinates with [`triangle_uv_to_quad_uv`].
+///
+/// Quad `i` becomes the triangles `2 * i` with the vertices `(v0, v1, v3)` and `2 * i + 1` with
+/// the vertices `(v2, v3, v1)`.
+///
+/// # Arguments
+/// * `indices` - The quad indices.
+///
+/// # Returns
+/// The triangle indices, two per quad.
+pub fn quads_to_triangles(indices: &[(u32, u32, u32, u32)]) -> Vec<(u32, u32, u32)> {
+    indices
+        .iter()
+        .flat_map(|&(v0, v1, v2, v3)| [(v0, v1, v3), (v2, v3, v1)])
+        .collect()
+}
+
+/// Merges triangles into quads, the inverse of [`quads_to_triangles`].
+///
+/// Consecutive pairs of triangles that were split from a quad by [`quads_to_triangles`] are
+/// merged back into that quad. Any other triangle becomes a degenerate quad `(v0, v1, v2, v2)`,
+/// which Embree treats as a triangle with the same `(u, v)` coordinates.
+///
+/// # Arguments
+/// * `indices` - The triangle indices.
+///
+/// # Returns
+/// The quad indices, at most one per triangle.
+///
+/// # Example
+/// ```
+/// use embree4_rs::geometry::*;
+///
+/// let quads = [(0, 1, 2, 3)];
+/// let triangles = quads_to_triangles(&quads);
+/// assert_eq!(triangles, [(0, 1, 3), (2, 3, 1)]);
+/// assert_eq!(triangles_to_quads(&triangles), quads);
+/// assert_eq!(triangles_to_quads(&[(4, 5, 6)]), [(4, 5, 6, 6)]);
+/// ```
+pub fn triangles_to_quads(indices: &[(u32, u32, u32)]) -> Vec<(u32, u32, u32, u32)> {
+    let mut quads = Vec::with_capacity(indices.len());
+    let mut i = 0;
+    while i < indices.len() {
+        let (v0, v1, v3) = indices[i];
+        match indices.get(i + 1) {
+            Some(&(v2, w3, w1)) if w3 == v3 && w1 == v1 => {
+                quads.push((v0, v1, v2, v3));
+                i += 2;
+            }
+            _ => {
+                quads.push((v0, v1, v3, v3));
+                i += 1;
+            }
+        }
+    }
+    quads
+}
+
+/// Maps a hit on a triangle produced by [`quads_to_triangles`] to its quad.
+///
+/// # Arguments
+/// * `prim_id` - The ID of the hit triangle.
+/// * `uv` - The barycentric `(u, v)` coordinates of the hit on the triangle.
+///
+/// # Returns
+/// The ID of the quad, and the `(u, v)` coordinates of the hit on the quad, following the
+/// convention of [`QuadMeshGeometry`].
+pub fn triangle_uv_to_quad_uv(prim_id: u32, uv: (f32, f32)) -> (u32, (f32, f32)) {
+    let (u, v) = uv;
+    if prim_id % 2 == 0 {
+        (prim_id / 2, (u, v))
+    } else {
+        (prim_id / 2, (1.0 - u, 1.0 - v))
+    }
+}
+
+#[test]
+fn quad_uv_convention() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    let geometry = QuadMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2, 3)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let uv = |x, y| {
+        let ray = crate::Ray::new((x, y, 0.0), (0.0, 0.0, 1.0));
+        let (u, v) = scene.intersect_1(ray).unwrap().unwrap().uv();
+        ((u * 4.0).round() / 4.0, (v * 4.0).round() / 4.0)
+    };
+    assert_eq!(uv(-0.5, -0.5), (0.25, 0.25));
+    assert_eq!(uv(0.5, -0.5), (0.75, 0.25));
+    assert_eq!(uv(0.5, 0.5), (0.75, 0.75));
+    assert_eq!(uv(-0.5, 0.5), (0.25, 0.75));
+}
+
+#[test]
+fn triangulated_quads_match_quad_uvs() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    let triangles = quads_to_triangles(&[(0, 1, 2, 3)]);
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &triangles).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let uv = |x, y| {
+        let ray = crate::Ray::new((x, y, 0.0), (0.0, 0.0, 1.0));
+        let hit = scene.intersect_1(ray).unwrap().unwrap();
+        let (quad, (u, v)) = triangle_uv_to_quad_uv(hit.prim_id(), hit.uv());
+        assert_eq!(quad, 0);
+        ((u * 4.0).round() / 4.0, (v * 4.0).round() / 4.0)
+    };
+    assert_eq!(uv(-0.5, -0.5), (0.25, 0.25));
+    assert_eq!(uv(0.5, -0.5), (0.75, 0.25));
+    assert_eq!(uv(0.5, 0.5), (0.75, 0.75));
+    assert_eq!(uv(-0.5, 0.5), (0.25, 0.75));
+}
@@ -0,0 +1,254 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    math::{add, dot, length, normalize, scale, Vec3},
+    Device,
+};
+
+use super::{Geometry, UserGeometry, UserGeometryImpl};
+
+/// Options for sphere tracing an [`SdfGeometry`].
+#[derive(Clone, Copy, Debug)]
+pub struct SdfOptions {
+    /// The distance below which the surface counts as hit. Defaults to `1e-4`.
+    pub epsilon: f32,
+    /// The maximum number of sphere tracing steps per ray. Rays which do not converge are
+    /// reported as misses. Defaults to `256`.
+    pub max_steps: u32,
+}
+
+impl Default for SdfOptions {
+    fn default() -> Self {
+        Self {
+            epsilon: 1e-4,
+            max_steps: 256,
+        }
+    }
+}
+
+/// A surface defined by a signed distance function, traced by sphere tracing within fixed
+/// bounds, e.g. for hybrid scenes of procedural SDFs and meshes.
+///
+/// The function must return the distance to the surface, negative inside, or a lower bound of
+/// it, so sphere tracing does not step through the surface. Hits report the normalized gradient
+/// of the function as the geometric normal.
+pub struct SdfGeometry<F>
+where
+    F: Fn((f32, f32, f32)) -> f32 + Send + Sync + 'static,
+{
+    geometry: UserGeometry<Sdf<F>>,
+}
+
+struct Sdf<F> {
+    distance: F,
+    lower: Vec3,
+    upper: Vec3,
+    options: SdfOptions,
+}
+
+impl<F> SdfGeometry<F>
+where
+    F: Fn((f32, f32, f32)) -> f32 + Send + Sync + 'static,
+{
+    /// Constructs a new `SdfGeometry` from a signed distance function.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `distance` - The signed distance function. Embree may call it from multiple threads
+    ///   concurrently.
+    /// * `lower` - The lower corner of the bounds of the surface.
+    /// * `upper` - The upper corner of the bounds of the surface.
+    /// * `options` - The sphere tracing options.
+    ///
+    /// # Returns
+    /// A `Result` containing the `SdfGeometry` if successful, or an error if the bounds are
+    /// empty or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// // A torus around the y axis
+    /// let torus = |(x, y, z): (f32, f32, f32)| {
+    ///     let q = ((x * x + z * z).sqrt() - 1.0, y);
+    ///     (q.0 * q.0 + q.1 * q.1).sqrt() - 0.25
+    /// };
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = SdfGeometry::try_new(
+    ///     &device,
+    ///     torus,
+    ///     (-1.25, -0.25, -1.25),
+    ///     (1.25, 0.25, 1.25),
+    ///     SdfOptions::default(),
+    /// )
+    /// .unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        distance: F,
+        lower: (f32, f32, f32),
+        upper: (f32, f32, f32),
+        options: SdfOptions,
+    ) -> Result<Self> {
+        if !(lower.0 <= upper.0 && lower.1 <= upper.1 && lower.2 <= upper.2) {
+            bail!("SDF bounds {:?} to {:?} are empty", lower, upper);
+        }
+
+        let sdf = Sdf {
+            distance,
+            lower,
+            upper,
+            options,
+        };
+        let geometry = UserGeometry::try_new(device, sdf)?;
+        Ok(Self { geometry })
+    }
+
+    /// Evaluates the signed distance function at `point`.
+    pub fn distance(&self, point: (f32, f32, f32)) -> f32 {
+        (self.geometry.data().distance)(point)
+    }
+}
+
+impl<F> Geometry for SdfGeometry<F>
+where
+    F: Fn((f32, f32, f32)) -> f32 + Send + Sync + 'static,
+{
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.geometry.geometry()
+    }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.geometry.retained_data()
+    }
+}
+
+impl<F> Sdf<F>
+where
+    F: Fn((f32, f32, f32)) -> f32,
+{
+    /// Clips the ray to the bounds, returning the entry and exit distance.
+    fn clip(&self, origin: Vec3, direction: Vec3, tnear: f32, tfar: f32) -> Option<(f32, f32)> {
+        let mut range = (tnear, tfar);
+        for (o, d, lower, upper) in [
+            (origin.0, direction.0, self.lower.0, self.upper.0),
+            (origin.1, direction.1, self.lower.1, self.upper.1),
+            (origin.2, direction.2, self.lower.2, self.upper.2),
+        ] {
+            let (t0, t1) = ((lower - o) / d, (upper - o) / d);
+            range.0 = range.0.max(t0.min(t1));
+            range.1 = range.1.min(t0.max(t1));
+        }
+        (range.0 <= range.1).then_some(range)
+    }
+
+    /// Estimates the normal at `p` from the central differences of the distance function.
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let h = self.options.epsilon;
+        let d = &self.distance;
+        normalize((
+            d((p.0 + h, p.1, p.2)) - d((p.0 - h, p.1, p.2)),
+            d((p.0, p.1 + h, p.2)) - d((p.0, p.1 - h, p.2)),
+            d((p.0, p.1, p.2 + h)) - d((p.0, p.1, p.2 - h)),
+        ))
+    }
+}
+
+impl<F> UserGeometryImpl for Sdf<F>
+where
+    F: Fn((f32, f32, f32)) -> f32,
+{
+    fn bounds(&self) -> embree4_sys::RTCBounds {
+        embree4_sys::RTCBounds {
+            lower_x: self.lower.0,
+            lower_y: self.lower.1,
+            lower_z: self.lower.2,
+            align0: 0.0,
+            upper_x: self.upper.0,
+            upper_y: self.upper.1,
+            upper_z: self.upper.2,
+            align1: 0.0,
+        }
+    }
+
+    fn intersect(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        ctx: &embree4_sys::RTCRayQueryContext,
+        ray_hit: &mut embree4_sys::RTCRayHit,
+    ) {
+        let ray = &mut ray_hit.ray;
+        let origin = (ray.org_x, ray.org_y, ray.org_z);
+        let direction = (ray.dir_x, ray.dir_y, ray.dir_z);
+        let Some((mut t, t_exit)) = self.clip(origin, direction, ray.tnear, ray.tfar) else {
+            return;
+        };
+
+        // Distances are in world units, while t is in multiples of the direction's length
+        let inv_speed = 1.0 / length(direction);
+        for _ in 0..self.options.max_steps {
+            let p = add(origin, scale(direction, t));
+            let distance = (self.distance)(p);
+            if distance < self.options.epsilon {
+                let normal = self.normal(p);
+                ray.tfar = t;
+                let hit = &mut ray_hit.hit;
+                (hit.Ng_x, hit.Ng_y, hit.Ng_z) = normal;
+                hit.u = 0.0;
+                hit.v = 0.0;
+                hit.primID = prim_id;
+                hit.geomID = geom_id;
+                hit.instID = ctx.instID;
+                return;
+            }
+
+            t += distance * inv_speed;
+            if t > t_exit {
+                return;
+            }
+        }
+    }
+
+    fn closest_point(&self, _prim_id: u32, point: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+        // Step along the gradient, exact for true distance functions
+        let normal = self.normal(point);
+        let distance = (self.distance)(point);
+        (dot(normal, normal) > 0.0).then(|| add(point, scale(normal, -distance)))
+    }
+}
+
+#[test]
+fn sdf_sphere() {
+    let device = Device::try_new(None).unwrap();
+    let sphere = |(x, y, z): (f32, f32, f32)| (x * x + y * y + (z - 5.0) * (z - 5.0)).sqrt() - 1.0;
+    let geometry = SdfGeometry::try_new(
+        &device,
+        sphere,
+        (-1.0, -1.0, 4.0),
+        (1.0, 1.0, 6.0),
+        SdfOptions::default(),
+    )
+    .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 2.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 2.0).abs() < 1e-3);
+    let normal = hit.normal();
+    assert!((normal.2 + 1.0).abs() < 1e-3);
+
+    let miss = crate::Ray::new((1.5, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.intersect_1(miss).unwrap().is_none());
+    assert!(scene.occluded_1(ray).unwrap());
+
+    let closest = scene.point_query((0.0, 0.0, 8.0), 5.0).unwrap().unwrap();
+    assert!((closest.distance - 2.0).abs() < 1e-3);
+}
@@ -0,0 +1,652 @@
+use std::{
+    any::Any,
+    mem::size_of,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+    sync::{Arc, Weak},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{device_error, device_error_or, Device};
+
+use super::{Geometry, RetainedData};
+
+/// A Catmull-Clark subdivision surface, built from a control mesh of polygons with an arbitrary
+/// number of vertices each.
+///
+/// Sharp features are tagged the way OpenSubdiv does it, with
+/// [`SubdivisionGeometry::set_edge_creases`], [`SubdivisionGeometry::set_vertex_creases`] and
+/// [`SubdivisionGeometry::set_holes`].
+pub struct SubdivisionGeometry {
+    handle: embree4_sys::RTCGeometry,
+    vertex_count: usize,
+    face_count: usize,
+    /// The displacement callback, retained by the scenes the geometry is attached to.
+    retained: Arc<RetainedData>,
+}
+
+/// A displacement callback, with the retained data of its geometry to store its panics in.
+struct Displacement {
+    callback: Box<dyn Fn(&mut DisplacementPoint) + Send + Sync>,
+    retained: Weak<RetainedData>,
+}
+
+/// A point on the limit surface of a [`SubdivisionGeometry`], passed to its displacement
+/// callback.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplacementPoint {
+    /// The ID of the face the point lies on.
+    pub prim_id: u32,
+    /// The time step of the vertex buffer being displaced.
+    pub time_step: u32,
+    /// The `(u, v)` coordinates of the point on the face.
+    pub uv: (f32, f32),
+    /// The surface normal at the point, not necessarily normalized.
+    pub normal: (f32, f32, f32),
+    /// The position of the point, to be displaced in place.
+    pub position: (f32, f32, f32),
+}
+
+impl SubdivisionGeometry {
+    /// Constructs a new `SubdivisionGeometry` instance from the given control mesh.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The control vertex positions.
+    /// * `face_vertex_counts` - The number of vertices of each face.
+    /// * `indices` - The vertex indices of all faces, concatenated in face order.
+    ///
+    /// # Returns
+    /// A `Result` containing the `SubdivisionGeometry` if successful, or an error if the face
+    /// vertex counts do not match the indices, an index is out of range or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let vertices = [
+    ///   (-1.0, -1.0, 0.0),
+    ///   (1.0, -1.0, 0.0),
+    ///   (1.0, 1.0, 0.0),
+    ///   (-1.0, 1.0, 0.0),
+    /// ];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry);
+    /// ```
+    pub fn try_new(
+        device: &Device,
+        vertices: &[(f32, f32, f32)],
+        face_vertex_counts: &[u32],
+        indices: &[u32],
+    ) -> Result<Self> {
+        let index_count: usize = face_vertex_counts.iter().map(|&n| n as usize).sum();
+        if index_count != indices.len() {
+            bail!(
+                "Expected {} subdivision indices for the face vertex counts, got {}",
+                index_count,
+                indices.len()
+            );
+        }
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            bail!(
+                "Subdivision index {} is out of range for {} vertices",
+                index,
+                vertices.len()
+            );
+        }
+
+        let handle = unsafe {
+            embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::SUBDIVISION)
+        };
+        if handle.is_null() {
+            return Err(device_error(device, "Failed to create geometry"));
+        }
+        let geometry = Self {
+            handle,
+            vertex_count: vertices.len(),
+            face_count: face_vertex_counts.len(),
+            retained: Default::default(),
+        };
+
+        let vertex_buf = geometry.new_buffer::<f32>(
+            device,
+            embree4_sys::RTCBufferType::VERTEX,
+            embree4_sys::RTCFormat::FLOAT3,
+            3,
+            vertices.len(),
+        )?;
+        for (i, v) in vertices.iter().enumerate() {
+            vertex_buf[3 * i] = v.0;
+            vertex_buf[3 * i + 1] = v.1;
+            vertex_buf[3 * i + 2] = v.2;
+        }
+
+        geometry
+            .new_buffer::<u32>(
+                device,
+                embree4_sys::RTCBufferType::FACE,
+                embree4_sys::RTCFormat::UINT,
+                1,
+                face_vertex_counts.len(),
+            )?
+            .copy_from_slice(face_vertex_counts);
+        geometry
+            .new_buffer::<u32>(
+                device,
+                embree4_sys::RTCBufferType::INDEX,
+                embree4_sys::RTCFormat::UINT,
+                1,
+                indices.len(),
+            )?
+            .copy_from_slice(indices);
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit subdivision geometry")
+    }
+
+    /// Sets the creased edges, replacing any previously set, and recommits the geometry.
+    ///
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `edges` - The creased edges, as pairs of vertex indices.
+    /// * `weights` - The crease weight of each edge. `0.0` is smooth, `f32::INFINITY` is
+    ///   infinitely sharp.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the creases were set, or an error if the number of edges and
+    /// weights differ, a vertex index is out of range or an error occurred.
+    pub fn set_edge_creases(
+        &mut self,
+        device: &Device,
+        edges: &[(u32, u32)],
+        weights: &[f32],
+    ) -> Result<()> {
+        if edges.len() != weights.len() {
+            bail!(
+                "Expected {} edge crease weights, got {}",
+                edges.len(),
+                weights.len()
+            );
+        }
+        if let Some(&edge) = edges
+            .iter()
+            .find(|&&(a, b)| a as usize >= self.vertex_count || b as usize >= self.vertex_count)
+        {
+            bail!(
+                "Edge crease {:?} is out of range for {} vertices",
+                edge,
+                self.vertex_count
+            );
+        }
+
+        let index_buf = self.new_buffer::<u32>(
+            device,
+            embree4_sys::RTCBufferType::EDGE_CREASE_INDEX,
+            embree4_sys::RTCFormat::UINT2,
+            2,
+            edges.len(),
+        )?;
+        for (i, edge) in edges.iter().enumerate() {
+            index_buf[2 * i] = edge.0;
+            index_buf[2 * i + 1] = edge.1;
+        }
+        self.new_buffer::<f32>(
+            device,
+            embree4_sys::RTCBufferType::EDGE_CREASE_WEIGHT,
+            embree4_sys::RTCFormat::FLOAT,
+            1,
+            weights.len(),
+        )?
+        .copy_from_slice(weights);
+
+        self.recommit(device)
+    }
+
+    /// Sets the creased vertices, replacing any previously set, and recommits the geometry.
+    ///
+    /// Corners are vertex creases with a weight of `f32::INFINITY`. Scenes the geometry is
+    /// attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The indices of the creased vertices.
+    /// * `weights` - The crease weight of each vertex. `0.0` is smooth, `f32::INFINITY` is a
+    ///   corner.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the creases were set, or an error if the number of vertices
+    /// and weights differ or an error occurred.
+    pub fn set_vertex_creases(
+        &mut self,
+        device: &Device,
+        vertices: &[u32],
+        weights: &[f32],
+    ) -> Result<()> {
+        if vertices.len() != weights.len() {
+            bail!(
+                "Expected {} vertex crease weights, got {}",
+                vertices.len(),
+                weights.len()
+            );
+        }
+        if let Some(&vertex) = vertices.iter().find(|&&v| v as usize >= self.vertex_count) {
+            bail!(
+                "Vertex crease index {} is out of range for {} vertices",
+                vertex,
+                self.vertex_count
+            );
+        }
+
+        self.new_buffer::<u32>(
+            device,
+            embree4_sys::RTCBufferType::VERTEX_CREASE_INDEX,
+            embree4_sys::RTCFormat::UINT,
+            1,
+            vertices.len(),
+        )?
+        .copy_from_slice(vertices);
+        self.new_buffer::<f32>(
+            device,
+            embree4_sys::RTCBufferType::VERTEX_CREASE_WEIGHT,
+            embree4_sys::RTCFormat::FLOAT,
+            1,
+            weights.len(),
+        )?
+        .copy_from_slice(weights);
+
+        self.recommit(device)
+    }
+
+    /// Sets the faces which are cut out of the surface, replacing any previously set, and
+    /// recommits the geometry.
+    ///
+    /// Hole faces still influence the shape of their neighbours. Scenes the geometry is attached
+    /// to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `faces` - The indices of the hole faces.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the holes were set, or an error if an error occurred.
+    pub fn set_holes(&mut self, device: &Device, faces: &[u32]) -> Result<()> {
+        if let Some(&face) = faces.iter().find(|&&f| f as usize >= self.face_count) {
+            bail!(
+                "Hole face index {} is out of range for {} faces",
+                face,
+                self.face_count
+            );
+        }
+
+        self.new_buffer::<u32>(
+            device,
+            embree4_sys::RTCBufferType::HOLE,
+            embree4_sys::RTCFormat::UINT,
+            1,
+            faces.len(),
+        )?
+        .copy_from_slice(faces);
+
+        self.recommit(device)
+    }
+
+    /// Sets the tessellation rate and recommits the geometry.
+    ///
+    /// Each edge of the control mesh is split into `rate` segments, so lower rates trade
+    /// accuracy for memory and build time. The default rate is `2.0`. Scenes the geometry is
+    /// attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `rate` - The number of segments per edge, at least `1.0`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the rate was set, or an error if an error occurred.
+    pub fn set_tessellation_rate(&mut self, device: &Device, rate: f32) -> Result<()> {
+        if rate.is_nan() || rate < 1.0 {
+            bail!("Tessellation rate must be at least 1.0, got {}", rate);
+        }
+
+        unsafe {
+            embree4_sys::rtcSetGeometryTessellationRate(self.handle, rate);
+        }
+        device_error_or(device, (), "Failed to set subdivision tessellation rate")?;
+
+        self.recommit(device)
+    }
+
+    /// Sets how the boundaries of a topology are interpolated, and recommits the geometry.
+    ///
+    /// Use this to match the boundary rules of the authoring tool. Topology `0` is the topology
+    /// of the vertex positions, and defaults to
+    /// [`RTCSubdivisionMode::SMOOTH_BOUNDARY`](embree4_sys::RTCSubdivisionMode::SMOOTH_BOUNDARY).
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `topology_id` - The topology to set the mode for.
+    /// * `mode` - The boundary interpolation mode.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the mode was set, or an error if the topology does not exist
+    /// or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCSubdivisionMode;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (1.0, 1.0, 0.0), (-1.0, 1.0, 0.0)];
+    /// let mut geometry =
+    ///     SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    /// geometry
+    ///     .set_subdivision_mode(&device, 0, RTCSubdivisionMode::PIN_BOUNDARY)
+    ///     .unwrap();
+    /// ```
+    pub fn set_subdivision_mode(
+        &mut self,
+        device: &Device,
+        topology_id: u32,
+        mode: embree4_sys::RTCSubdivisionMode,
+    ) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcSetGeometrySubdivisionMode(self.handle, topology_id, mode);
+        }
+        device_error_or(device, (), "Failed to set subdivision mode")?;
+
+        self.recommit(device)
+    }
+
+    /// Sets a displacement callback, which moves points of the limit surface during tessellation,
+    /// and recommits the geometry.
+    ///
+    /// Use this for procedural or texture-driven displacement without baking a dense mesh.
+    /// Embree calls `displacement` while building the scenes the geometry is attached to, which
+    /// must be committed again for the change to take effect.
+    ///
+    /// Scenes the geometry is attached to keep `displacement` alive. If `displacement` panics,
+    /// the point keeps its position, and the panic is resumed once the commit of the scene
+    /// returns.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `displacement` - Called with each point to displace, possibly from multiple threads
+    ///   concurrently.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the callback was set, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (1.0, 1.0, 0.0), (-1.0, 1.0, 0.0)];
+    /// let mut geometry =
+    ///     SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    ///
+    /// // Ripple the surface along its normal
+    /// geometry
+    ///     .set_displacement_fn(&device, |point| {
+    ///         let (x, y, z) = point.normal;
+    ///         let length = (x * x + y * y + z * z).sqrt();
+    ///         let height = 0.1 * (10.0 * point.uv.0).sin() / length;
+    ///         point.position.0 += height * x;
+    ///         point.position.1 += height * y;
+    ///         point.position.2 += height * z;
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn set_displacement_fn<F>(&mut self, device: &Device, displacement: F) -> Result<()>
+    where
+        F: Fn(&mut DisplacementPoint) + Send + Sync + 'static,
+    {
+        let displacement = self.retained.replace(
+            "displacement",
+            Displacement {
+                callback: Box::new(displacement),
+                retained: Arc::downgrade(&self.retained),
+            },
+        );
+        unsafe {
+            embree4_sys::rtcSetGeometryUserData(self.handle, displacement as _);
+            embree4_sys::rtcSetGeometryDisplacementFunction(self.handle, Some(displacement_fn));
+        }
+        device_error_or(
+            device,
+            (),
+            "Failed to set subdivision displacement function",
+        )?;
+
+        self.recommit(device)
+    }
+
+    /// Allocates a new geometry buffer of `count` items with `components` values of `T` each,
+    /// and returns it as a slice to fill in.
+    #[allow(clippy::mut_from_ref)]
+    fn new_buffer<T>(
+        &self,
+        device: &Device,
+        buffer_type: embree4_sys::RTCBufferType,
+        format: embree4_sys::RTCFormat,
+        components: usize,
+        count: usize,
+    ) -> Result<&mut [T]> {
+        let buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                self.handle,
+                buffer_type,
+                0,
+                format,
+                components * size_of::<T>(),
+                count,
+            )
+        };
+        if count == 0 {
+            return Ok(&mut []);
+        }
+        if buf_ptr.is_null() {
+            return Err(device_error(
+                device,
+                &format!("Failed to create subdivision {:?} buffer", buffer_type),
+            ));
+        }
+        Ok(unsafe { slice::from_raw_parts_mut(buf_ptr as *mut T, components * count) })
+    }
+
+    fn recommit(&self, device: &Device) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Failed to commit subdivision geometry")
+    }
+}
+
+/// The displacement function of subdivision geometry with a displacement callback.
+unsafe extern "C" fn displacement_fn(args: *const embree4_sys::RTCDisplacementFunctionNArguments) {
+    let args = &*args;
+    let displacement = &*(args.geometryUserPtr as *const Displacement);
+
+    for i in 0..args.N as usize {
+        let mut point = DisplacementPoint {
+            prim_id: args.primID,
+            time_step: args.timeStep,
+            uv: (*args.u.add(i), *args.v.add(i)),
+            normal: (*args.Ng_x.add(i), *args.Ng_y.add(i), *args.Ng_z.add(i)),
+            position: (*args.P_x.add(i), *args.P_y.add(i), *args.P_z.add(i)),
+        };
+        // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+        match catch_unwind(AssertUnwindSafe(|| (displacement.callback)(&mut point))) {
+            Ok(()) => {
+                *args.P_x.add(i) = point.position.0;
+                *args.P_y.add(i) = point.position.1;
+                *args.P_z.add(i) = point.position.2;
+            }
+            Err(panic) => {
+                if let Some(retained) = displacement.retained.upgrade() {
+                    retained.store_panic(panic);
+                }
+            }
+        }
+    }
+}
+
+// Besides the handle, which may be used from any thread, the geometry only owns its retained
+// data, which is Send + Sync
+unsafe impl Send for SubdivisionGeometry {}
+unsafe impl Sync for SubdivisionGeometry {}
+
+impl Drop for SubdivisionGeometry {
+    fn drop(&mut self) {
+        unsafe {
+            embree4_sys::rtcReleaseGeometry(self.handle);
+        }
+    }
+}
+
+impl Geometry for SubdivisionGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        Some(self.retained.clone())
+    }
+}
+
+#[test]
+fn subdivision_holes_and_corners() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, -1.0),
+        (1.0, -1.0, -1.0),
+        (1.0, 1.0, -1.0),
+        (-1.0, 1.0, -1.0),
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    #[rustfmt::skip]
+    let indices = [
+        0, 3, 2, 1,
+        4, 5, 6, 7,
+        0, 1, 5, 4,
+        1, 2, 6, 5,
+        2, 3, 7, 6,
+        3, 0, 4, 7,
+    ];
+    let mut cube = SubdivisionGeometry::try_new(&device, &vertices, &[4; 6], &indices).unwrap();
+
+    let distance = |cube: &SubdivisionGeometry| {
+        let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+        scene.attach_geometry(cube).unwrap();
+        let scene = scene.commit().unwrap();
+        let ray = crate::Ray::new((0.0, 0.0, 5.0), (0.0, 0.0, -1.0));
+        scene.intersect_1(ray).unwrap().unwrap().t()
+    };
+
+    // The smooth limit surface shrinks away from the control cage
+    let smooth = distance(&cube);
+    assert!(smooth > 4.0 && smooth < 5.0);
+
+    cube.set_holes(&device, &[1]).unwrap();
+    assert!(distance(&cube) > 5.0);
+    cube.set_holes(&device, &[]).unwrap();
+
+    // Sharp edges around the front face pull the surface back to the cage
+    cube.set_edge_creases(
+        &device,
+        &[(4, 5), (5, 6), (6, 7), (7, 4)],
+        &[f32::INFINITY; 4],
+    )
+    .unwrap();
+    cube.set_vertex_creases(&device, &[4, 5, 6, 7], &[f32::INFINITY; 4])
+        .unwrap();
+    assert!((distance(&cube) - 4.0).abs() < 1e-3);
+
+    cube.set_tessellation_rate(&device, 8.0).unwrap();
+    assert!((distance(&cube) - 4.0).abs() < 1e-3);
+
+    cube.set_edge_creases(&device, &[], &[]).unwrap();
+    cube.set_vertex_creases(&device, &[], &[]).unwrap();
+    cube.set_subdivision_mode(&device, 0, embree4_sys::RTCSubdivisionMode::NO_BOUNDARY)
+        .unwrap();
+    assert!((distance(&cube) - smooth).abs() < 1e-3);
+
+    assert!(cube.set_tessellation_rate(&device, 0.5).is_err());
+    assert!(cube.set_holes(&device, &[6]).is_err());
+    assert!(cube.set_vertex_creases(&device, &[0], &[]).is_err());
+}
+
+#[test]
+fn displacement_fn_moves_surface() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    let mut patch = SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    patch
+        .set_displacement_fn(&device, |point| point.position.2 += 0.5)
+        .unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&patch).unwrap();
+    let scene = scene.commit().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 1.5).abs() < 1e-3);
+}
+
+#[test]
+fn subdivision_rejects_out_of_range_indices() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 0.0),
+        (1.0, -1.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (-1.0, 1.0, 0.0),
+    ];
+    assert!(SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 4]).is_err());
+
+    let mut geometry =
+        SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    assert!(geometry
+        .set_edge_creases(&device, &[(0, 4)], &[f32::INFINITY])
+        .is_err());
+    assert!(geometry
+        .set_edge_creases(&device, &[(0, 1)], &[f32::INFINITY])
+        .is_ok());
+}
+
+#[test]
+#[should_panic(expected = "displacement failed")]
+fn displacement_fn_panic_resumes_on_commit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    let mut patch = SubdivisionGeometry::try_new(&device, &vertices, &[4], &[0, 1, 2, 3]).unwrap();
+    patch
+        .set_displacement_fn(&device, |_| panic!("displacement failed"))
+        .unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&patch).unwrap();
+    let _ = scene.commit();
+}
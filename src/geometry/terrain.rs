@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+
+use crate::{Device, Hit};
+
+use super::{grid::heightfield_vertices, Geometry, GridGeometry};
+
+/// The number of quads along each side of a terrain tile.
+const TILE_SIZE: usize = 256;
+
+/// A terrain built from a heightmap, for GIS and game terrain.
+///
+/// The heightmap is split into tiles of at most 256 x 256 quads, each an Embree grid sharing the
+/// vertices of its neighbours, so heightmaps of any size can be traced without building a
+/// triangle mesh. Use [`TerrainGeometry::heightmap_position`] to map hits back to heightmap
+/// coordinates.
+pub struct TerrainGeometry {
+    grid: GridGeometry,
+    width: usize,
+    height: usize,
+    tiles_x: usize,
+}
+
+impl TerrainGeometry {
+    /// Constructs a new `TerrainGeometry` in the xz-plane from a row-major heightmap.
+    ///
+    /// The sample at column `x` and row `z` is placed at
+    /// `(x * spacing.0, heights[z * width + x], z * spacing.1)`.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `heights` - The heightmap samples, `width` per row.
+    /// * `width` - The number of samples per row, at least 2.
+    /// * `height` - The number of rows, at least 2.
+    /// * `spacing` - The distance between neighbouring samples along x and z.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TerrainGeometry` if successful, or an error if the heightmap
+    /// size does not match or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let (width, height) = (1024, 1024);
+    /// let heights: Vec<f32> = (0..width * height)
+    ///     .map(|i| ((i % width) as f32 * 0.01).sin() * ((i / width) as f32 * 0.01).cos())
+    ///     .collect();
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let terrain =
+    ///     TerrainGeometry::from_heightmap(&device, &heights, width, height, (1.0, 1.0)).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&terrain).unwrap();
+    /// ```
+    pub fn from_heightmap(
+        device: &Device,
+        heights: &[f32],
+        width: usize,
+        height: usize,
+        spacing: (f32, f32),
+    ) -> Result<Self> {
+        if width < 2 || height < 2 {
+            bail!(
+                "Heightmap must be at least 2x2 samples, got {}x{}",
+                width,
+                height
+            );
+        }
+        if heights.len() != width * height {
+            bail!(
+                "Expected {} heights for a {}x{} heightmap, got {}",
+                width * height,
+                width,
+                height,
+                heights.len()
+            );
+        }
+        if u32::try_from(heights.len()).is_err() {
+            bail!("Heightmap of {}x{} samples is too large", width, height);
+        }
+
+        let vertices = heightfield_vertices(heights, width, spacing);
+
+        // Neighbouring tiles share their edge samples
+        let tile_starts = |samples: usize| (0..samples - 1).step_by(TILE_SIZE);
+        let tile_len = |start: usize, samples: usize| (samples - start).min(TILE_SIZE + 1);
+        let mut grids = Vec::new();
+        for z in tile_starts(height) {
+            for x in tile_starts(width) {
+                grids.push(embree4_sys::RTCGrid {
+                    startVertexID: (z * width + x) as u32,
+                    stride: width as u32,
+                    width: tile_len(x, width) as u16,
+                    height: tile_len(z, height) as u16,
+                });
+            }
+        }
+
+        let grid = GridGeometry::try_new(device, &vertices, &grids)?;
+        Ok(Self {
+            grid,
+            width,
+            height,
+            tiles_x: tile_starts(width).len(),
+        })
+    }
+
+    /// Returns the continuous heightmap coordinates `(x, z)` of a hit on this terrain, in units
+    /// of samples.
+    ///
+    /// # Arguments
+    /// * `hit` - A hit on this terrain.
+    pub fn heightmap_position(&self, hit: &Hit) -> (f32, f32) {
+        let tile = hit.prim_id() as usize;
+        let (start_x, start_z) = (
+            (tile % self.tiles_x) * TILE_SIZE,
+            (tile / self.tiles_x) * TILE_SIZE,
+        );
+        let quads_x = (self.width - 1 - start_x).min(TILE_SIZE);
+        let quads_z = (self.height - 1 - start_z).min(TILE_SIZE);
+        let (u, v) = hit.uv();
+        (
+            start_x as f32 + u * quads_x as f32,
+            start_z as f32 + v * quads_z as f32,
+        )
+    }
+}
+
+impl Geometry for TerrainGeometry {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.grid.geometry()
+    }
+}
+
+#[test]
+fn terrain_tiles_large_heightmaps() {
+    let device = Device::try_new(None).unwrap();
+    let (width, height) = (600, 3);
+    let heights = vec![1.0; width * height];
+    let terrain =
+        TerrainGeometry::from_heightmap(&device, &heights, width, height, (0.5, 0.5)).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&terrain).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((250.25, 5.0, 0.5), (0.0, -1.0, 0.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 4.0).abs() < 1e-4);
+    assert_eq!(hit.prim_id(), 1);
+    let (x, z) = terrain.heightmap_position(&hit);
+    assert!((x - 500.5).abs() < 1e-2 && (z - 1.0).abs() < 1e-2);
+
+    assert!(TerrainGeometry::from_heightmap(&device, &heights, width, 2, (1.0, 1.0)).is_err());
+}
@@ -1,13 +1,47 @@
-use std::{mem::size_of, slice};
+use std::{
+    any::Any,
+    mem::size_of,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+    sync::Arc,
+};
 
 use anyhow::{bail, Result};
 
-use crate::{device_error_or, Device};
+use crate::{
+    device_error, device_error_or, filter::filter_hit, math::closest_point_on_triangle,
+    BufferLayout, Device, Hit, SharedBuffer,
+};
 
-use super::Geometry;
+use super::{validate_mesh, Geometry, MeshValidation, RetainedData};
 
 pub struct TriangleMeshGeometry {
     handle: embree4_sys::RTCGeometry,
+    vertex_count: usize,
+    triangle_count: usize,
+    vertex_attribute_count: u32,
+    time_step_count: u32,
+    /// The layout of the vertex positions of the first time step, which may be read from a
+    /// strided shared buffer. Further time steps are always packed.
+    vertex_layout: BufferLayout,
+    /// The layout of the triangle indices.
+    index_layout: BufferLayout,
+    /// The opacity callback, see [`TriangleMeshGeometry::set_opacity_fn`].
+    opacity: Option<OpacityFn>,
+    /// The opacity callback and the vertex and index data of owned shared buffers, retained by
+    /// the scenes the geometry is attached to.
+    retained: Arc<RetainedData>,
+}
+
+type OpacityFn = Arc<dyn Fn(&Hit) -> bool + Send + Sync>;
+
+/// The data the geometry user pointer of a triangle mesh points to, read by its callbacks.
+///
+/// Meshes with packed buffers and without an opacity callback have no callback data.
+struct CallbackData {
+    vertex_stride: usize,
+    index_stride: usize,
+    opacity: Option<OpacityFn>,
 }
 
 impl TriangleMeshGeometry {
@@ -35,12 +69,178 @@ impl TriangleMeshGeometry {
         device: &Device,
         vertices: &[(f32, f32, f32)],
         indices: &[(u32, u32, u32)],
+    ) -> Result<Self> {
+        Self::new_uncommitted(
+            device,
+            vertices.len(),
+            indices.len(),
+            |vertex_buf, index_buf| {
+                for (i, v) in vertices.iter().enumerate() {
+                    vertex_buf[3 * i] = v.0;
+                    vertex_buf[3 * i + 1] = v.1;
+                    vertex_buf[3 * i + 2] = v.2;
+                }
+                for (i, idx) in indices.iter().enumerate() {
+                    index_buf[3 * i] = idx.0;
+                    index_buf[3 * i + 1] = idx.1;
+                    index_buf[3 * i + 2] = idx.2;
+                }
+            },
+        )?
+        .committed(device)
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance like [`TriangleMeshGeometry::try_new`],
+    /// after checking the mesh data with [`validate_mesh`].
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions.
+    /// * `indices` - The triangle indices.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` and the degenerate triangles found, or
+    /// an error if the mesh is invalid or the geometry could not be created. Validation errors
+    /// can be inspected by downcasting to [`MeshError`](super::MeshError).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    ///
+    /// match TriangleMeshGeometry::try_new_validated(&device, &vertices, &[(0, 1, 2)]) {
+    ///     Ok((geometry, validation)) => {
+    ///         for triangle in validation.degenerate_triangles {
+    ///             eprintln!("warning: triangle {} is degenerate", triangle);
+    ///         }
+    ///     }
+    ///     Err(e) => match e.downcast_ref::<MeshError>() {
+    ///         Some(MeshError::IndexOutOfRange { triangle, .. }) => {
+    ///             eprintln!("corrupt triangle {}", triangle)
+    ///         }
+    ///         _ => eprintln!("{}", e),
+    ///     },
+    /// }
+    /// ```
+    pub fn try_new_validated(
+        device: &Device,
+        vertices: &[(f32, f32, f32)],
+        indices: &[(u32, u32, u32)],
+    ) -> Result<(Self, MeshValidation)> {
+        let validation = validate_mesh(vertices, indices)?;
+        Ok((Self::try_new(device, vertices, indices)?, validation))
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance from flattened vertex positions and
+    /// indices, as produced by most mesh loaders.
+    ///
+    /// Both slices are copied into the Embree buffers as a whole.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `positions` - The vertex positions as `[x0, y0, z0, x1, y1, z1, ...]`.
+    /// * `indices` - The triangle indices as `[a0, b0, c0, a1, b1, c1, ...]`.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` if successful, or an error if a slice
+    /// length is not a multiple of 3, an index is out of range or the geometry could not be
+    /// created.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let positions = [-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0];
+    /// let indices = [0, 1, 2];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = TriangleMeshGeometry::try_from_flat(&device, &positions, &indices).unwrap();
+    /// ```
+    pub fn try_from_flat(device: &Device, positions: &[f32], indices: &[u32]) -> Result<Self> {
+        if positions.len() % 3 != 0 {
+            bail!("Position count {} is not a multiple of 3", positions.len());
+        }
+        if indices.len() % 3 != 0 {
+            bail!("Index count {} is not a multiple of 3", indices.len());
+        }
+        Self::new_uncommitted(
+            device,
+            positions.len() / 3,
+            indices.len() / 3,
+            |vertex_buf, index_buf| {
+                vertex_buf.copy_from_slice(positions);
+                index_buf.copy_from_slice(indices);
+            },
+        )?
+        .committed(device)
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance from [`glam`] vectors.
+    ///
+    /// The vectors are reinterpreted as flat slices, see
+    /// [`TriangleMeshGeometry::try_from_flat`], so no conversion copy is made.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use glam::{UVec3, Vec3};
+    ///
+    /// let vertices = [Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::Y];
+    /// let indices = [UVec3::new(0, 1, 2)];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = TriangleMeshGeometry::try_new_glam(&device, &vertices, &indices).unwrap();
+    /// ```
+    #[cfg(feature = "glam")]
+    pub fn try_new_glam(
+        device: &Device,
+        vertices: &[glam::Vec3],
+        indices: &[glam::UVec3],
+    ) -> Result<Self> {
+        Self::try_from_flat(
+            device,
+            bytemuck::cast_slice(vertices),
+            bytemuck::cast_slice(indices),
+        )
+    }
+
+    /// Like [`TriangleMeshGeometry::try_new_glam`], but for 16-byte aligned [`glam::Vec3A`]
+    /// vertices, whose padding is dropped while copying them into the Embree buffer.
+    #[cfg(feature = "glam")]
+    pub fn try_new_glam_a(
+        device: &Device,
+        vertices: &[glam::Vec3A],
+        indices: &[glam::UVec3],
+    ) -> Result<Self> {
+        Self::new_uncommitted(
+            device,
+            vertices.len(),
+            indices.len(),
+            |vertex_buf, index_buf| {
+                for (dst, v) in vertex_buf.chunks_exact_mut(3).zip(vertices) {
+                    dst.copy_from_slice(&v.to_array());
+                }
+                index_buf.copy_from_slice(bytemuck::cast_slice(indices));
+            },
+        )?
+        .committed(device)
+    }
+
+    /// Creates the geometry with vertex and index buffers of the given sizes, which `fill`
+    /// writes as flat `f32` and `u32` slices, without committing it.
+    fn new_uncommitted(
+        device: &Device,
+        vertex_count: usize,
+        triangle_count: usize,
+        fill: impl FnOnce(&mut [f32], &mut [u32]),
     ) -> Result<Self> {
         let geometry = unsafe {
             embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::TRIANGLE)
         };
         if geometry.is_null() {
-            bail!("Failed to create geometry: {:?}", device.error());
+            return Err(device_error(device, "Failed to create geometry"));
         }
 
         let vertex_buf_ptr = unsafe {
@@ -50,27 +250,17 @@ impl TriangleMeshGeometry {
                 0,
                 embree4_sys::RTCFormat::FLOAT3,
                 3 * size_of::<f32>(),
-                vertices.len(),
+                vertex_count,
             )
         };
         if vertex_buf_ptr.is_null() {
-            bail!(
-                "Failed to create triangle mesh vertex buffer: {:?}",
-                device.error()
-            );
+            return Err(device_error(
+                device,
+                "Failed to create triangle mesh vertex buffer",
+            ));
         }
         device_error_or(device, (), "Failed not create triangle mesh vertex buffer")?;
 
-        let vertex_buf =
-            unsafe { slice::from_raw_parts_mut(vertex_buf_ptr as *mut f32, 3 * vertices.len()) };
-
-        // copy vertices into buffer
-        for (i, v) in vertices.iter().enumerate() {
-            vertex_buf[3 * i] = v.0;
-            vertex_buf[3 * i + 1] = v.1;
-            vertex_buf[3 * i + 2] = v.2;
-        }
-
         let index_buf_ptr = unsafe {
             embree4_sys::rtcSetNewGeometryBuffer(
                 geometry,
@@ -78,36 +268,1064 @@ impl TriangleMeshGeometry {
                 0,
                 embree4_sys::RTCFormat::UINT3,
                 3 * size_of::<u32>(),
-                indices.len(),
+                triangle_count,
             )
         };
         if index_buf_ptr.is_null() {
-            bail!(
-                "Failed to create triangle mesh index buffer: {:?}",
-                device.error()
-            );
+            return Err(device_error(
+                device,
+                "Failed to create triangle mesh index buffer",
+            ));
         }
         device_error_or(device, (), "Failed to create triangle mesh index buffer")?;
 
+        let vertex_buf =
+            unsafe { slice::from_raw_parts_mut(vertex_buf_ptr as *mut f32, 3 * vertex_count) };
         let index_buf =
-            unsafe { slice::from_raw_parts_mut(index_buf_ptr as *mut u32, 3 * indices.len()) };
+            unsafe { slice::from_raw_parts_mut(index_buf_ptr as *mut u32, 3 * triangle_count) };
+        fill(vertex_buf, index_buf);
+
+        unsafe {
+            embree4_sys::rtcSetGeometryPointQueryFunction(geometry, Some(point_query_fn));
+        }
+        device_error_or(
+            device,
+            (),
+            "Failed to set triangle mesh point query function",
+        )?;
+
+        let geometry = Self {
+            handle: geometry,
+            vertex_count,
+            triangle_count,
+            vertex_attribute_count: 0,
+            time_step_count: 1,
+            vertex_layout: BufferLayout::packed(3 * size_of::<f32>()),
+            index_layout: BufferLayout::packed(3 * size_of::<u32>()),
+            opacity: None,
+            retained: Default::default(),
+        };
+        geometry.check_indices(device)?;
+        Ok(geometry)
+    }
+
+    /// Returns an error if any triangle index is out of range for the vertex count.
+    ///
+    /// Embree does not check indices, and reads vertices out of bounds for out-of-range ones.
+    fn check_indices(&self, device: &Device) -> Result<()> {
+        let ptr = self.buffer_data(device, embree4_sys::RTCBufferType::INDEX, 0)? as *const u8;
+        for i in 0..self.triangle_count {
+            let triangle = unsafe {
+                (ptr.add(i * self.index_layout.stride) as *const [u32; 3]).read_unaligned()
+            };
+            if let Some(&index) = triangle
+                .iter()
+                .find(|&&index| index as usize >= self.vertex_count)
+            {
+                bail!(
+                    "Triangle index {} is out of range for {} vertices",
+                    index,
+                    self.vertex_count
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Points the geometry user pointer to the current buffer layouts and opacity callback.
+    fn update_callback_data(&self) {
+        let data = self.retained.replace(
+            "callback",
+            CallbackData {
+                vertex_stride: self.vertex_layout.stride,
+                index_stride: self.index_layout.stride,
+                opacity: self.opacity.clone(),
+            },
+        );
+        unsafe {
+            embree4_sys::rtcSetGeometryUserData(self.handle, data as _);
+        }
+    }
+
+    /// Commits a newly created geometry.
+    fn committed(self, device: &Device) -> Result<Self> {
+        self.recommit(device)?;
+        Ok(self)
+    }
+
+    /// Commits the geometry after a change.
+    fn recommit(&self, device: &Device) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Failed to commit triangle mesh geometry")
+    }
+
+    /// Returns a builder for a `TriangleMeshGeometry`, which is only committed once fully
+    /// configured.
+    ///
+    /// The setters of `TriangleMeshGeometry` recommit the geometry after every change. The
+    /// builder applies masks, build quality, time steps and vertex attributes first, and commits
+    /// only once in [`TriangleMeshBuilder::commit`].
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The vertex positions.
+    /// * `indices` - The triangle indices.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCBuildQuality;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let moved = vertices.map(|(x, y, z)| (x + 1.0, y, z));
+    ///
+    /// let geometry = TriangleMeshGeometry::builder(&device, &vertices, &[(0, 1, 2)])
+    ///     .mask(0b10)
+    ///     .build_quality(RTCBuildQuality::HIGH)
+    ///     .time_step_count(2)
+    ///     .vertices(1, &moved)
+    ///     .commit()
+    ///     .unwrap();
+    /// ```
+    pub fn builder<'a>(
+        device: &'a Device,
+        vertices: &[(f32, f32, f32)],
+        indices: &[(u32, u32, u32)],
+    ) -> TriangleMeshBuilder<'a> {
+        let geometry = Self::new_uncommitted(
+            device,
+            vertices.len(),
+            indices.len(),
+            |vertex_buf, index_buf| {
+                for (i, v) in vertices.iter().enumerate() {
+                    vertex_buf[3 * i] = v.0;
+                    vertex_buf[3 * i + 1] = v.1;
+                    vertex_buf[3 * i + 2] = v.2;
+                }
+                for (i, idx) in indices.iter().enumerate() {
+                    index_buf[3 * i] = idx.0;
+                    index_buf[3 * i + 1] = idx.1;
+                    index_buf[3 * i + 2] = idx.2;
+                }
+            },
+        );
+        TriangleMeshBuilder { device, geometry }
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance from double-precision vertices.
+    ///
+    /// Each vertex is translated into the local frame around `origin` in double precision and
+    /// only then rounded to the nearest `f32`, so meshes far away from the world origin keep
+    /// full `f32` precision relative to `origin`. Use the scene's
+    /// [`world_origin`](crate::Scene::world_origin) to match rays traced with
+    /// [`CommittedScene::intersect_1_f64`](crate::CommittedScene::intersect_1_f64).
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The world-space vertex positions.
+    /// * `indices` - The triangle indices.
+    /// * `origin` - The origin of the local frame. Pass `(0.0, 0.0, 0.0)` to keep world
+    ///   coordinates.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` if successful, or an error if a vertex
+    /// is out of `f32` range in the local frame, an index is out of range or the geometry could
+    /// not be created.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let origin = (6_378_137.0, 0.0, 0.0);
+    /// let vertices = [
+    ///     (6_378_137.0, -1.0, 1.0),
+    ///     (6_378_138.0, -1.0, 1.0),
+    ///     (6_378_137.5, 1.0, 1.0),
+    /// ];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry =
+    ///     TriangleMeshGeometry::try_new_f64(&device, &vertices, &[(0, 1, 2)], origin).unwrap();
+    /// ```
+    pub fn try_new_f64(
+        device: &Device,
+        vertices: &[(f64, f64, f64)],
+        indices: &[(u32, u32, u32)],
+        origin: (f64, f64, f64),
+    ) -> Result<Self> {
+        let local = vertices
+            .iter()
+            .map(|&vertex| {
+                let local = crate::math::to_local(vertex, origin);
+                if !(local.0.is_finite() && local.1.is_finite() && local.2.is_finite()) {
+                    bail!(
+                        "Vertex {:?} is out of f32 range relative to origin {:?}",
+                        vertex,
+                        origin
+                    );
+                }
+                Ok(local)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::try_new(device, &local, indices)
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance from shared vertex and index buffers.
+    ///
+    /// The vertex buffer must contain `vertex_count` tightly packed `f32` triples, followed by
+    /// at least 4 bytes of padding, as Embree reads the last vertex with a 16-byte load.
+    /// The index buffer must contain `triangle_count` tightly packed `u32` triples.
+    ///
+    /// No data is copied. See [`SharedBuffer`] for the requirements on the underlying memory.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The shared buffer holding the vertex positions.
+    /// * `vertex_count` - The number of vertices in `vertices`.
+    /// * `indices` - The shared buffer holding the triangle indices.
+    /// * `triangle_count` - The number of triangles in `indices`.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` if successful, or an error if a buffer is
+    /// too small, an index is out of range or the geometry could not be created.
+    pub fn try_new_shared(
+        device: &Device,
+        vertices: &SharedBuffer,
+        vertex_count: usize,
+        indices: &SharedBuffer,
+        triangle_count: usize,
+    ) -> Result<Self> {
+        Self::try_new_shared_strided(
+            device,
+            vertices,
+            BufferLayout::packed(3 * size_of::<f32>()),
+            vertex_count,
+            indices,
+            BufferLayout::packed(3 * size_of::<u32>()),
+            triangle_count,
+        )
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance from shared vertex and index buffers with
+    /// custom layouts.
+    ///
+    /// Use this for interleaved vertex data, e.g. position, normal and texture coordinates in
+    /// one buffer as produced by most engines, without de-interleaving it. Each vertex position
+    /// is an `f32` triple and each triangle a `u32` triple, located by the respective
+    /// [`BufferLayout`]. Embree reads vertices with 16-byte loads, so the buffer must extend at
+    /// least 4 bytes past the position of the last vertex.
+    ///
+    /// No data is copied. See [`SharedBuffer`] for the requirements on the underlying memory.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `vertices` - The shared buffer holding the vertex positions.
+    /// * `vertex_layout` - The layout of the vertex positions in `vertices`.
+    /// * `vertex_count` - The number of vertices in `vertices`.
+    /// * `indices` - The shared buffer holding the triangle indices.
+    /// * `index_layout` - The layout of the triangle indices in `indices`.
+    /// * `triangle_count` - The number of triangles in `indices`.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` if successful, or an error if a layout is
+    /// invalid, a buffer is too small, an index is out of range or the geometry could not be
+    /// created.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// // Interleaved position, normal and uv, 8 floats per vertex
+    /// let mut vertices: Vec<f32> = vec![
+    ///     -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0,
+    ///     1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0,
+    ///     0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.5, 1.0,
+    /// ];
+    /// let mut indices: Vec<u32> = vec![0, 1, 2];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertex_buf = unsafe {
+    ///     SharedBuffer::try_new(&device, vertices.as_mut_ptr() as _, vertices.len() * 4).unwrap()
+    /// };
+    /// let index_buf = unsafe {
+    ///     SharedBuffer::try_new(&device, indices.as_mut_ptr() as _, indices.len() * 4).unwrap()
+    /// };
+    ///
+    /// let geometry = TriangleMeshGeometry::try_new_shared_strided(
+    ///     &device,
+    ///     &vertex_buf,
+    ///     BufferLayout { offset: 0, stride: 8 * 4 },
+    ///     3,
+    ///     &index_buf,
+    ///     BufferLayout::packed(3 * 4),
+    ///     1,
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn try_new_shared_strided(
+        device: &Device,
+        vertices: &SharedBuffer,
+        vertex_layout: BufferLayout,
+        vertex_count: usize,
+        indices: &SharedBuffer,
+        index_layout: BufferLayout,
+        triangle_count: usize,
+    ) -> Result<Self> {
+        let vertex_size = 3 * size_of::<f32>();
+        // The last vertex is read with a 16-byte load, i.e. 4 bytes past its end
+        if vertex_layout.required_size(vertex_size + size_of::<f32>(), vertex_count)?
+            > vertices.byte_size()
+        {
+            bail!(
+                "Shared vertex buffer of {} bytes is too small for {} vertices",
+                vertices.byte_size(),
+                vertex_count
+            );
+        }
+        vertex_layout.validate(vertex_size)?;
+
+        let index_size = 3 * size_of::<u32>();
+        if index_layout.required_size(index_size, triangle_count)? > indices.byte_size() {
+            bail!(
+                "Shared index buffer of {} bytes is too small for {} triangles",
+                indices.byte_size(),
+                triangle_count
+            );
+        }
+        index_layout.validate(index_size)?;
+
+        let handle = unsafe {
+            embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::TRIANGLE)
+        };
+        if handle.is_null() {
+            return Err(device_error(device, "Failed to create geometry"));
+        }
+        let geometry = Self {
+            handle,
+            vertex_count,
+            triangle_count,
+            vertex_attribute_count: 0,
+            time_step_count: 1,
+            vertex_layout,
+            index_layout,
+            opacity: None,
+            retained: Default::default(),
+        };
+        geometry.update_callback_data();
+
+        unsafe {
+            embree4_sys::rtcSetGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::VERTEX,
+                0,
+                embree4_sys::RTCFormat::FLOAT3,
+                vertices.handle,
+                vertex_layout.offset,
+                vertex_layout.stride,
+                vertex_count,
+            );
+        }
+        device_error_or(device, (), "Failed to set triangle mesh vertex buffer")?;
+
+        unsafe {
+            embree4_sys::rtcSetGeometryBuffer(
+                handle,
+                embree4_sys::RTCBufferType::INDEX,
+                0,
+                embree4_sys::RTCFormat::UINT3,
+                indices.handle,
+                index_layout.offset,
+                index_layout.stride,
+                triangle_count,
+            );
+        }
+        device_error_or(device, (), "Failed to set triangle mesh index buffer")?;
+        geometry.check_indices(device)?;
+
+        unsafe {
+            embree4_sys::rtcSetGeometryPointQueryFunction(handle, Some(point_query_fn));
+        }
+        device_error_or(
+            device,
+            (),
+            "Failed to set triangle mesh point query function",
+        )?;
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(handle);
+        }
+        device_error_or(device, geometry, "Failed to commit triangle mesh geometry")
+    }
+
+    /// Constructs a new `TriangleMeshGeometry` instance that takes ownership of flattened vertex
+    /// positions and indices.
+    ///
+    /// Embree reads the data in place through shared buffers, so even very large meshes are
+    /// never copied. The vectors are kept alive by the geometry and the scenes it is attached
+    /// to, and freed when all of them are dropped.
+    ///
+    /// Embree reads the last vertex with a 16-byte load, so one `f32` of padding is appended to
+    /// `positions`. Reserve one extra element of capacity to avoid a reallocation.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `positions` - The vertex positions as `[x0, y0, z0, x1, y1, z1, ...]`.
+    /// * `indices` - The triangle indices as `[a0, b0, c0, a1, b1, c1, ...]`.
+    ///
+    /// # Returns
+    /// A `Result` containing the `TriangleMeshGeometry` if successful, or an error if a vector
+    /// length is not a multiple of 3, an index is out of range or the geometry could not be
+    /// created.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let mut positions = Vec::with_capacity(9 + 1);
+    /// positions.extend_from_slice(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]);
+    /// let indices = vec![0, 1, 2];
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let geometry = TriangleMeshGeometry::try_from_vecs(&device, positions, indices).unwrap();
+    /// ```
+    pub fn try_from_vecs(
+        device: &Device,
+        mut positions: Vec<f32>,
+        mut indices: Vec<u32>,
+    ) -> Result<Self> {
+        if positions.len() % 3 != 0 {
+            bail!("Position count {} is not a multiple of 3", positions.len());
+        }
+        if indices.len() % 3 != 0 {
+            bail!("Index count {} is not a multiple of 3", indices.len());
+        }
+        let vertex_count = positions.len() / 3;
+        let triangle_count = indices.len() / 3;
+        positions.push(0.0);
+
+        // Moving the vectors into the geometry below does not move their heap allocations
+        let vertex_buf = unsafe {
+            SharedBuffer::try_new(
+                device,
+                positions.as_mut_ptr() as *mut _,
+                positions.len() * size_of::<f32>(),
+            )?
+        };
+        let index_buf = unsafe {
+            SharedBuffer::try_new(
+                device,
+                indices.as_mut_ptr() as *mut _,
+                indices.len() * size_of::<u32>(),
+            )?
+        };
+
+        let geometry = Self::try_new_shared(
+            device,
+            &vertex_buf,
+            vertex_count,
+            &index_buf,
+            triangle_count,
+        )?;
+        geometry.retained.retain((positions, indices));
+        Ok(geometry)
+    }
+
+    /// Sets a per-vertex attribute, e.g. shading normals or texture coordinates, and recommits
+    /// the geometry.
+    ///
+    /// Attributes can be interpolated at hits with [`CommittedScene::interpolate`](crate::CommittedScene::interpolate).
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `slot` - The attribute slot.
+    /// * `components` - The number of `f32` components per vertex, from 1 to 4.
+    /// * `values` - The tightly packed attribute values, `components` per vertex.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the attribute was set, or an error if the values do not match
+    /// the vertex count or the attribute could not be set.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///
+    /// let uvs = [0.0, 0.0, 1.0, 0.0, 0.5, 1.0];
+    /// geometry.set_vertex_attribute(&device, 0, 2, &uvs).unwrap();
+    /// ```
+    pub fn set_vertex_attribute(
+        &mut self,
+        device: &Device,
+        slot: u32,
+        components: usize,
+        values: &[f32],
+    ) -> Result<()> {
+        self.write_vertex_attribute(device, slot, components, values)?;
+        self.recommit(device)
+    }
+
+    /// Like [`TriangleMeshGeometry::set_vertex_attribute`], without recommitting.
+    fn write_vertex_attribute(
+        &mut self,
+        device: &Device,
+        slot: u32,
+        components: usize,
+        values: &[f32],
+    ) -> Result<()> {
+        let format = match components {
+            1 => embree4_sys::RTCFormat::FLOAT,
+            2 => embree4_sys::RTCFormat::FLOAT2,
+            3 => embree4_sys::RTCFormat::FLOAT3,
+            4 => embree4_sys::RTCFormat::FLOAT4,
+            _ => bail!(
+                "Vertex attributes must have 1 to 4 components, got {}",
+                components
+            ),
+        };
+        if values.len() != components * self.vertex_count {
+            bail!(
+                "Expected {} vertex attribute values for {} vertices, got {}",
+                components * self.vertex_count,
+                self.vertex_count,
+                values.len()
+            );
+        }
+
+        if slot >= self.vertex_attribute_count {
+            unsafe {
+                embree4_sys::rtcSetGeometryVertexAttributeCount(self.handle, slot + 1);
+            }
+            device_error_or(device, (), "Failed to set vertex attribute count")?;
+            self.vertex_attribute_count = slot + 1;
+        }
+
+        let buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                self.handle,
+                embree4_sys::RTCBufferType::VERTEX_ATTRIBUTE,
+                slot,
+                format,
+                components * size_of::<f32>(),
+                self.vertex_count,
+            )
+        };
+        if buf_ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to create vertex attribute buffer",
+            ));
+        }
+
+        let buf = unsafe { slice::from_raw_parts_mut(buf_ptr as *mut f32, values.len()) };
+        buf.copy_from_slice(values);
+
+        Ok(())
+    }
+
+    /// Sets the number of time steps for vertex motion blur, and recommits the geometry.
+    ///
+    /// Time steps are evenly distributed over the ray time range `[0, 1]`, and Embree
+    /// interpolates the vertices linearly between them. Newly added time steps start out with
+    /// the vertices of the first time step, use [`TriangleMeshGeometry::set_vertices`] to
+    /// deform them. Scenes the geometry is attached to must be committed again for the change to
+    /// take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `count` - The number of time steps, from 1 to
+    ///   [`RTC_MAX_TIME_STEP_COUNT`](embree4_sys::RTC_MAX_TIME_STEP_COUNT).
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the count was set, or an error if the count is out of range
+    /// or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///
+    /// // Move the triangle by one unit along x over the shutter interval
+    /// geometry.set_time_step_count(&device, 2).unwrap();
+    /// let moved = vertices.map(|(x, y, z)| (x + 1.0, y, z));
+    /// geometry.set_vertices(&device, 1, &moved).unwrap();
+    /// ```
+    pub fn set_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        self.write_time_step_count(device, count)?;
+        self.recommit(device)
+    }
+
+    /// Like [`TriangleMeshGeometry::set_time_step_count`], without recommitting.
+    fn write_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        if count == 0 || count > embree4_sys::RTC_MAX_TIME_STEP_COUNT {
+            bail!(
+                "Time step count must be from 1 to {}, got {}",
+                embree4_sys::RTC_MAX_TIME_STEP_COUNT,
+                count
+            );
+        }
+
+        let first_ptr = unsafe {
+            embree4_sys::rtcGetGeometryBufferData(
+                self.handle,
+                embree4_sys::RTCBufferType::VERTEX,
+                0,
+            )
+        };
+        if first_ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to get triangle mesh vertex buffer",
+            ));
+        }
+        // The first time step may be strided, the new ones are packed
+        let first: Vec<f32> = (0..self.vertex_count)
+            .flat_map(|i| unsafe {
+                *((first_ptr as *const u8).add(i * self.vertex_layout.stride) as *const [f32; 3])
+            })
+            .collect();
+
+        unsafe {
+            embree4_sys::rtcSetGeometryTimeStepCount(self.handle, count);
+        }
+        device_error_or(device, (), "Failed to set triangle mesh time step count")?;
+
+        for time_step in self.time_step_count..count {
+            self.new_vertex_buffer(device, time_step)?
+                .copy_from_slice(&first);
+        }
+        self.time_step_count = count;
+
+        Ok(())
+    }
+
+    /// Replaces the vertex positions of a time step, and recommits the geometry.
+    ///
+    /// Scenes the geometry is attached to must be committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to set the vertices for, see
+    ///   [`TriangleMeshGeometry::set_time_step_count`].
+    /// * `vertices` - The vertex positions, as many as the geometry was created with.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the vertices were set, or an error if the time step or vertex
+    /// count is out of range or an error occurred.
+    pub fn set_vertices(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        vertices: &[(f32, f32, f32)],
+    ) -> Result<()> {
+        self.write_vertices(device, time_step, vertices)?;
+        self.recommit(device)
+    }
+
+    /// Like [`TriangleMeshGeometry::set_vertices`], without recommitting.
+    fn write_vertices(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+        vertices: &[(f32, f32, f32)],
+    ) -> Result<()> {
+        if time_step >= self.time_step_count {
+            bail!(
+                "Time step {} is out of range for {} time steps",
+                time_step,
+                self.time_step_count
+            );
+        }
+        if vertices.len() != self.vertex_count {
+            bail!(
+                "Expected {} vertices, got {}",
+                self.vertex_count,
+                vertices.len()
+            );
+        }
+
+        let vertex_buf = self.new_vertex_buffer(device, time_step)?;
+        for (i, v) in vertices.iter().enumerate() {
+            vertex_buf[3 * i] = v.0;
+            vertex_buf[3 * i + 1] = v.1;
+            vertex_buf[3 * i + 2] = v.2;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the vertex positions of a time step for in-place modification.
+    ///
+    /// Changes take effect after [`TriangleMeshGeometry::update`], e.g. to animate a deforming
+    /// mesh every frame without recreating the geometry. Vertices read from a strided shared
+    /// buffer, see [`TriangleMeshGeometry::try_new_shared_strided`], cannot be accessed as a
+    /// slice and must be modified through the shared memory instead.
+    ///
+    /// # Safety
+    /// The slice points into memory Embree reads while tracing. No scene the geometry is
+    /// attached to may be queried, e.g. through a [`crate::CommittedScene`] shared with other
+    /// threads, until the slice is dropped and the scene has been committed again after
+    /// [`TriangleMeshGeometry::update`].
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `time_step` - The time step to get the vertices of, see
+    ///   [`TriangleMeshGeometry::set_time_step_count`].
+    ///
+    /// # Returns
+    /// A `Result` containing the vertex positions, or an error if the time step is out of range,
+    /// the vertices are strided or the buffer could not be accessed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    ///
+    /// for frame in 0..60 {
+    ///     // No query of `scene` runs while the vertices are modified
+    ///     for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+    ///         vertex[2] = (frame as f32 * 0.1).sin();
+    ///     }
+    ///     geometry.update(&device).unwrap();
+    ///     scene.commit().unwrap();
+    /// }
+    /// ```
+    pub unsafe fn vertices_mut(
+        &mut self,
+        device: &Device,
+        time_step: u32,
+    ) -> Result<&mut [[f32; 3]]> {
+        if time_step >= self.time_step_count {
+            bail!(
+                "Time step {} is out of range for {} time steps",
+                time_step,
+                self.time_step_count
+            );
+        }
+        if time_step == 0 && !self.vertex_layout.is_packed(3 * size_of::<f32>()) {
+            bail!(
+                "Vertices with stride {} are not tightly packed",
+                self.vertex_layout.stride
+            );
+        }
+        let ptr = self.buffer_data(device, embree4_sys::RTCBufferType::VERTEX, time_step)?;
+        Ok(slice::from_raw_parts_mut(
+            ptr as *mut [f32; 3],
+            self.vertex_count,
+        ))
+    }
+
+    /// Returns the triangle indices for in-place modification.
+    ///
+    /// Changes take effect after [`TriangleMeshGeometry::update`]. Indices read from a strided
+    /// shared buffer cannot be accessed as a slice.
+    ///
+    /// # Safety
+    /// Every index written must be below the vertex count. Embree does not check indices, and
+    /// reads vertices out of bounds for out-of-range indices when a scene using the geometry is
+    /// committed. Use [`validate_mesh`] on untrusted index data. As for
+    /// [`TriangleMeshGeometry::vertices_mut`], no scene the geometry is attached to may be
+    /// queried until the slice is dropped and the scene has been committed again.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    ///
+    /// # Returns
+    /// A `Result` containing the triangle indices, or an error if the indices are strided or the
+    /// buffer could not be accessed.
+    pub unsafe fn indices_mut(&mut self, device: &Device) -> Result<&mut [[u32; 3]]> {
+        if !self.index_layout.is_packed(3 * size_of::<u32>()) {
+            bail!(
+                "Indices with stride {} are not tightly packed",
+                self.index_layout.stride
+            );
+        }
+        let ptr = self.buffer_data(device, embree4_sys::RTCBufferType::INDEX, 0)?;
+        Ok(slice::from_raw_parts_mut(
+            ptr as *mut [u32; 3],
+            self.triangle_count,
+        ))
+    }
+
+    /// Marks the vertex and index buffers as modified, and recommits the geometry.
+    ///
+    /// Call this after changing data through [`TriangleMeshGeometry::vertices_mut`] or
+    /// [`TriangleMeshGeometry::indices_mut`]. Scenes the geometry is attached to must be
+    /// committed again for the change to take effect.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the geometry was recommitted, or an error if an error
+    /// occurred.
+    pub fn update(&mut self, device: &Device) -> Result<()> {
+        unsafe {
+            for time_step in 0..self.time_step_count {
+                embree4_sys::rtcUpdateGeometryBuffer(
+                    self.handle,
+                    embree4_sys::RTCBufferType::VERTEX,
+                    time_step,
+                );
+            }
+            embree4_sys::rtcUpdateGeometryBuffer(self.handle, embree4_sys::RTCBufferType::INDEX, 0);
+        }
+        device_error_or(device, (), "Failed to update triangle mesh buffers")?;
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Failed to commit triangle mesh geometry")
+    }
+
+    /// Returns a pointer to the data of a geometry buffer.
+    fn buffer_data(
+        &self,
+        device: &Device,
+        buffer_type: embree4_sys::RTCBufferType,
+        slot: u32,
+    ) -> Result<*mut std::ffi::c_void> {
+        let ptr = unsafe { embree4_sys::rtcGetGeometryBufferData(self.handle, buffer_type, slot) };
+        if ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to get triangle mesh buffer data",
+            ));
+        }
+        Ok(ptr)
+    }
+
+    /// Allocates a new vertex buffer for a time step, and returns it to fill in.
+    fn new_vertex_buffer(&mut self, device: &Device, time_step: u32) -> Result<&mut [f32]> {
+        let buf_ptr = unsafe {
+            embree4_sys::rtcSetNewGeometryBuffer(
+                self.handle,
+                embree4_sys::RTCBufferType::VERTEX,
+                time_step,
+                embree4_sys::RTCFormat::FLOAT3,
+                3 * size_of::<f32>(),
+                self.vertex_count,
+            )
+        };
+        if buf_ptr.is_null() {
+            return Err(device_error(
+                device,
+                "Failed to create triangle mesh vertex buffer",
+            ));
+        }
+        if time_step == 0 && !self.vertex_layout.is_packed(3 * size_of::<f32>()) {
+            self.vertex_layout = BufferLayout::packed(3 * size_of::<f32>());
+            self.update_callback_data();
+        }
+        Ok(unsafe { slice::from_raw_parts_mut(buf_ptr as *mut f32, 3 * self.vertex_count) })
+    }
 
-        // copy indices into buffer
-        for (i, idx) in indices.iter().enumerate() {
-            index_buf[3 * i] = idx.0;
-            index_buf[3 * i + 1] = idx.1;
-            index_buf[3 * i + 2] = idx.2;
+    /// Sets an opacity callback, which decides for every candidate hit of an occlusion query
+    /// whether the hit point is opaque, and recommits the geometry.
+    ///
+    /// Use this for alpha-tested geometry like foliage, so shadow rays pass through
+    /// transparent texels. Candidate hits for which `opacity` returns `false` do not occlude the
+    /// ray. Intersection queries are not affected. Scenes the geometry is attached to must be
+    /// committed again for the change to take effect.
+    ///
+    /// Scenes the geometry is attached to keep `opacity` alive, as they call it during traversal.
+    /// If `opacity` panics, the candidate hit is treated as transparent. The panic is reported
+    /// by the panic hook, but not propagated, as it is raised within Embree's traversal.
+    ///
+    /// # Arguments
+    /// * `device` - A reference to the `Device` instance.
+    /// * `opacity` - Called with each candidate hit. Returns `true` if the hit point is opaque.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the callback was set, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let mut leaf = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///
+    /// // Cut a hole into the leaf
+    /// leaf.set_opacity_fn(&device, |hit| {
+    ///     let (u, v) = hit.uv();
+    ///     u + v > 0.25
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn set_opacity_fn<F>(&mut self, device: &Device, opacity: F) -> Result<()>
+    where
+        F: Fn(&Hit) -> bool + Send + Sync + 'static,
+    {
+        self.opacity = Some(Arc::new(opacity));
+        self.update_callback_data();
+        unsafe {
+            embree4_sys::rtcSetGeometryOccludedFilterFunction(self.handle, Some(opacity_filter_fn));
         }
+        device_error_or(device, (), "Failed to set triangle mesh opacity function")?;
 
         unsafe {
-            embree4_sys::rtcCommitGeometry(geometry);
+            embree4_sys::rtcCommitGeometry(self.handle);
         }
-        device_error_or(device, (), "Failed to commit triangle mesh geometry")?;
+        device_error_or(device, (), "Failed to commit triangle mesh geometry")
+    }
+}
+
+/// The occlusion filter function of triangle meshes with an opacity callback, which rejects
+/// candidate hits on transparent points.
+unsafe extern "C" fn opacity_filter_fn(args: *const embree4_sys::RTCFilterFunctionNArguments) {
+    let args = &*args;
+    let Some(opacity) = &(*(args.geometryUserPtr as *const CallbackData)).opacity else {
+        return;
+    };
 
-        Ok(Self { handle: geometry })
+    let valid = slice::from_raw_parts_mut(args.valid, args.N as usize);
+    for (i, valid) in valid.iter_mut().enumerate() {
+        if *valid == 0 {
+            continue;
+        }
+
+        let candidate = filter_hit(args, i);
+        // Unwinding into Embree's C++ frames is undefined, so panics reject the hit instead
+        if !catch_unwind(AssertUnwindSafe(|| opacity(&candidate))).unwrap_or(false) {
+            *valid = 0;
+        }
     }
 }
 
+/// The point query function of triangle meshes, which computes the closest point on the
+/// queried triangle and shrinks the query radius to its distance.
+unsafe extern "C" fn point_query_fn(
+    args: *mut embree4_sys::RTCPointQueryFunctionArguments,
+) -> bool {
+    crate::point_query::update_closest_point(args, |geometry, prim_id, p| {
+        let data = embree4_sys::rtcGetGeometryUserData(geometry) as *const CallbackData;
+        let (vertex_stride, index_stride) = match data.as_ref() {
+            Some(data) => (data.vertex_stride, data.index_stride),
+            None => (3 * size_of::<f32>(), 3 * size_of::<u32>()),
+        };
+        // The buffer data starts at the first element, past the offset of the layout
+        let vertices =
+            embree4_sys::rtcGetGeometryBufferData(geometry, embree4_sys::RTCBufferType::VERTEX, 0)
+                as *const u8;
+        let indices =
+            embree4_sys::rtcGetGeometryBufferData(geometry, embree4_sys::RTCBufferType::INDEX, 0)
+                as *const u8;
+
+        let vertex = |i: u32| {
+            let [x, y, z] = *(vertices.add(i as usize * vertex_stride) as *const [f32; 3]);
+            (x, y, z)
+        };
+        let [i0, i1, i2] = *(indices.add(prim_id as usize * index_stride) as *const [u32; 3]);
+        Some(closest_point_on_triangle(
+            p,
+            vertex(i0),
+            vertex(i1),
+            vertex(i2),
+        ))
+    })
+}
+
+/// A builder for a [`TriangleMeshGeometry`], see [`TriangleMeshGeometry::builder`].
+///
+/// Errors of the individual setters are reported by [`TriangleMeshBuilder::commit`].
+pub struct TriangleMeshBuilder<'a> {
+    device: &'a Device,
+    /// The uncommitted geometry, or the first error that occurred while configuring it.
+    geometry: Result<TriangleMeshGeometry>,
+}
+
+impl<'a> TriangleMeshBuilder<'a> {
+    /// Sets the geometry mask, see [`Geometry::set_mask`].
+    pub fn mask(self, mask: u32) -> Self {
+        self.apply(|geometry, device| {
+            unsafe {
+                embree4_sys::rtcSetGeometryMask(geometry.handle, mask);
+            }
+            device_error_or(device, (), "Failed to set triangle mesh mask")
+        })
+    }
+
+    /// Sets the quality of the BVH built for the geometry.
+    pub fn build_quality(self, quality: embree4_sys::RTCBuildQuality) -> Self {
+        self.apply(|geometry, device| {
+            unsafe {
+                embree4_sys::rtcSetGeometryBuildQuality(geometry.handle, quality);
+            }
+            device_error_or(device, (), "Failed to set triangle mesh build quality")
+        })
+    }
+
+    /// Sets the number of time steps, see [`TriangleMeshGeometry::set_time_step_count`].
+    pub fn time_step_count(self, count: u32) -> Self {
+        self.apply(|geometry, device| geometry.write_time_step_count(device, count))
+    }
+
+    /// Sets the vertex positions of a time step, see [`TriangleMeshGeometry::set_vertices`].
+    pub fn vertices(self, time_step: u32, vertices: &[(f32, f32, f32)]) -> Self {
+        self.apply(|geometry, device| geometry.write_vertices(device, time_step, vertices))
+    }
+
+    /// Sets the time range of the time steps, see [`Geometry::set_time_range`].
+    pub fn time_range(self, start: f32, end: f32) -> Self {
+        self.apply(|geometry, device| {
+            unsafe {
+                embree4_sys::rtcSetGeometryTimeRange(geometry.handle, start, end);
+            }
+            device_error_or(device, (), "Failed to set triangle mesh time range")
+        })
+    }
+
+    /// Sets a per-vertex attribute, see [`TriangleMeshGeometry::set_vertex_attribute`].
+    pub fn vertex_attribute(self, slot: u32, components: usize, values: &[f32]) -> Self {
+        self.apply(|geometry, device| {
+            geometry.write_vertex_attribute(device, slot, components, values)
+        })
+    }
+
+    /// Commits the configured geometry.
+    ///
+    /// # Returns
+    /// A `Result` containing the committed `TriangleMeshGeometry`, or the first error that
+    /// occurred while building it.
+    pub fn commit(self) -> Result<TriangleMeshGeometry> {
+        self.geometry?.committed(self.device)
+    }
+
+    /// Applies a change to the geometry, unless an earlier change failed.
+    fn apply(
+        mut self,
+        change: impl FnOnce(&mut TriangleMeshGeometry, &Device) -> Result<()>,
+    ) -> Self {
+        if let Ok(geometry) = &mut self.geometry {
+            if let Err(e) = change(geometry, self.device) {
+                self.geometry = Err(e);
+            }
+        }
+        self
+    }
+}
+
+// Besides the handle, which may be used from any thread, the geometry only owns its retained
+// data, which is Send + Sync
+unsafe impl Send for TriangleMeshGeometry {}
+unsafe impl Sync for TriangleMeshGeometry {}
+
 impl Drop for TriangleMeshGeometry {
     fn drop(&mut self) {
         unsafe {
@@ -120,4 +1338,388 @@ impl Geometry for TriangleMeshGeometry {
     fn geometry(&self) -> embree4_sys::RTCGeometry {
         self.handle
     }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        Some(self.retained.clone())
+    }
+}
+
+#[test]
+fn opacity_fn_lets_shadow_rays_pass() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry
+        .set_opacity_fn(&device, |hit| hit.hit_point().0 < 0.0)
+        .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = |x| crate::Ray::new((x, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.occluded_1(ray(-0.25)).unwrap());
+    assert!(!scene.occluded_1(ray(0.25)).unwrap());
+    assert!(scene.intersect_1(ray(0.25)).unwrap().is_some());
+}
+
+#[test]
+fn opacity_fn_replaces_previous() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let token = Arc::new(());
+    let captured = token.clone();
+    geometry
+        .set_opacity_fn(&device, move |_| Arc::strong_count(&captured) > 0)
+        .unwrap();
+    assert_eq!(Arc::strong_count(&token), 2);
+
+    geometry.set_opacity_fn(&device, |_| false).unwrap();
+    assert_eq!(Arc::strong_count(&token), 1);
+}
+
+#[test]
+fn opacity_fn_panic_rejects_hit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry
+        .set_opacity_fn(&device, |_| panic!("opacity failed"))
+        .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(!scene.occluded_1(ray).unwrap());
+}
+
+#[test]
+fn try_new_f64_recenters() {
+    let device = Device::try_new(None).unwrap();
+    let origin = (1.0e9, 0.0, 0.0);
+    let vertices = [
+        (1.0e9 - 1.0, -1.0, 1.0),
+        (1.0e9 + 1.0, -1.0, 1.0),
+        (1.0e9, 1.0, 1.0),
+    ];
+    let geometry =
+        TriangleMeshGeometry::try_new_f64(&device, &vertices, &[(0, 1, 2)], origin).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::from_f64((1.0e9 + 0.5, 0.0, 0.0), (0.0, 0.0, 1.0), origin);
+    assert_eq!(ray.origin, (0.5, 0.0, 0.0));
+    assert!(scene.intersect_1(ray).unwrap().is_some());
+
+    let far = [(1.0e60, 0.0, 0.0)];
+    assert!(TriangleMeshGeometry::try_new_f64(&device, &far, &[], (0.0, 0.0, 0.0)).is_err());
+}
+
+#[test]
+fn vertex_motion_blur() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry.set_time_step_count(&device, 2).unwrap();
+    geometry
+        .set_vertices(&device, 1, &vertices.map(|(x, y, z)| (x, y, z + 2.0)))
+        .unwrap();
+    assert!(geometry.set_vertices(&device, 2, &vertices).is_err());
+    assert!(geometry.set_vertices(&device, 1, &vertices[..2]).is_err());
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let t = |time| {
+        let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).with_time(time);
+        scene.intersect_1(ray).unwrap().unwrap().t()
+    };
+    assert!((t(0.0) - 1.0).abs() < 1e-5);
+    assert!((t(0.5) - 2.0).abs() < 1e-5);
+    assert!((t(1.0) - 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn time_range_compresses_motion() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry.set_time_step_count(&device, 2).unwrap();
+    geometry
+        .set_vertices(&device, 1, &vertices.map(|(x, y, z)| (x, y, z + 2.0)))
+        .unwrap();
+    geometry.set_time_range(0.0, 0.5);
+    geometry.commit(&device).unwrap();
+    assert_eq!(device.error(), None);
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).with_time(0.25);
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn from_flat_matches_tuples() {
+    let device = Device::try_new(None).unwrap();
+    let positions = [-1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 1.0, 1.0];
+    let geometry = TriangleMeshGeometry::try_from_flat(&device, &positions, &[0, 1, 2]).unwrap();
+    assert!(TriangleMeshGeometry::try_from_flat(&device, &positions[..8], &[0, 1, 2]).is_err());
+    assert!(TriangleMeshGeometry::try_from_flat(&device, &positions, &[0, 1]).is_err());
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.t(), 1.0);
+}
+
+#[cfg(feature = "glam")]
+#[test]
+fn glam_vertices() {
+    use glam::{UVec3, Vec3, Vec3A};
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        Vec3::new(-1.0, -1.0, 1.0),
+        Vec3::new(1.0, -1.0, 1.0),
+        Vec3::new(0.0, 1.0, 2.0),
+    ];
+    let indices = [UVec3::new(0, 1, 2)];
+    let aligned = vertices.map(Vec3A::from);
+
+    for geometry in [
+        TriangleMeshGeometry::try_new_glam(&device, &vertices, &indices).unwrap(),
+        TriangleMeshGeometry::try_new_glam_a(&device, &aligned, &indices).unwrap(),
+    ] {
+        let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+        scene.attach_geometry(&geometry).unwrap();
+        let scene = scene.commit().unwrap();
+
+        let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+        let hit = scene.intersect_1(ray).unwrap().unwrap();
+        assert!((hit.t() - 1.5).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn from_vecs_keeps_data_alive() {
+    let device = Device::try_new(None).unwrap();
+    let positions = vec![-1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 1.0, 1.0];
+    assert!(TriangleMeshGeometry::try_from_vecs(&device, positions.clone(), vec![0, 1]).is_err());
+    let geometry = TriangleMeshGeometry::try_from_vecs(&device, positions, vec![0, 1, 2]).unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.t(), 1.0);
+}
+
+#[test]
+fn rejects_out_of_range_indices() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    assert!(TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 3)]).is_err());
+    assert!(
+        TriangleMeshGeometry::builder(&device, &vertices, &[(0, 1, 3)])
+            .commit()
+            .is_err()
+    );
+
+    let positions = vec![-1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 1.0, 1.0];
+    assert!(TriangleMeshGeometry::try_from_flat(&device, &positions, &[0, 3, 2]).is_err());
+    assert!(TriangleMeshGeometry::try_from_vecs(&device, positions, vec![3, 1, 2]).is_err());
+
+    let quad_vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (1.0, 1.0, 1.0),
+        (-1.0, 1.0, 1.0),
+    ];
+    assert!(
+        crate::geometry::QuadMeshGeometry::try_new(&device, &quad_vertices, &[(0, 1, 2, 4)])
+            .is_err()
+    );
+}
+
+#[test]
+fn update_moves_vertices() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    assert!(unsafe { geometry.vertices_mut(&device, 1) }.is_err());
+    assert_eq!(
+        unsafe { geometry.indices_mut(&device) }.unwrap(),
+        &[[0, 1, 2]]
+    );
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+
+    for z in [1.0, 3.0] {
+        for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+            vertex[2] = z;
+        }
+        geometry.update(&device).unwrap();
+        let committed = scene.commit().unwrap();
+        assert_eq!(committed.intersect_1(ray).unwrap().unwrap().t(), z);
+    }
+}
+
+#[test]
+fn builder_commits_once_configured() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let moved = vertices.map(|(x, y, z)| (x, y, z + 2.0));
+
+    let invalid = TriangleMeshGeometry::builder(&device, &vertices, &[(0, 1, 2)])
+        .vertices(1, &moved)
+        .mask(0b10)
+        .commit();
+    assert!(invalid.is_err());
+
+    let geometry = TriangleMeshGeometry::builder(&device, &vertices, &[(0, 1, 2)])
+        .mask(0b10)
+        .time_step_count(2)
+        .vertices(1, &moved)
+        .commit()
+        .unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).with_time(0.5);
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!((hit.t() - 2.0).abs() < 1e-5);
+    let ray = embree4_sys::RTCRay {
+        mask: 0b01,
+        ..ray.into()
+    };
+    assert!(scene.intersect_1(ray).unwrap().is_none());
+}
+
+#[test]
+fn shared_strided_interleaved() {
+    let device = Device::try_new(None).unwrap();
+    // Position and a 2-component attribute per vertex, behind a 4 byte header
+    let mut vertices: Vec<f32> = vec![
+        9.0, -1.0, -1.0, 1.0, 0.0, 0.0, 1.0, -1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.5, 1.0,
+    ];
+    let mut indices: Vec<u32> = vec![0, 1, 2];
+    let vertex_buf =
+        unsafe { SharedBuffer::try_new(&device, vertices.as_mut_ptr() as _, 16 * 4).unwrap() };
+    let index_buf =
+        unsafe { SharedBuffer::try_new(&device, indices.as_mut_ptr() as _, 3 * 4).unwrap() };
+    let layout = BufferLayout {
+        offset: 4,
+        stride: 5 * 4,
+    };
+
+    let shared = |vertex_layout, vertex_count| {
+        TriangleMeshGeometry::try_new_shared_strided(
+            &device,
+            &vertex_buf,
+            vertex_layout,
+            vertex_count,
+            &index_buf,
+            BufferLayout::packed(3 * 4),
+            1,
+        )
+    };
+    assert!(shared(layout, 4).is_err());
+    assert!(shared(
+        BufferLayout {
+            offset: 2,
+            ..layout
+        },
+        3
+    )
+    .is_err());
+    assert!(shared(
+        BufferLayout {
+            stride: 8,
+            ..layout
+        },
+        3
+    )
+    .is_err());
+    let mut geometry = shared(layout, 3).unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(scene.intersect_1(ray).unwrap().unwrap().t(), 1.0);
+
+    assert!(unsafe { geometry.vertices_mut(&device, 0) }.is_err());
+    assert!(unsafe { geometry.indices_mut(&device) }.is_ok());
+    geometry.set_time_step_count(&device, 2).unwrap();
+    assert_eq!(
+        unsafe { geometry.vertices_mut(&device, 1) }.unwrap(),
+        [[-1.0, -1.0, 1.0], [1.0, -1.0, 1.0], [0.0, 1.0, 1.0]]
+    );
+}
+
+#[test]
+fn shared_strided_point_query() {
+    let device = Device::try_new(None).unwrap();
+    let positions = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, -0.5),
+        (1.0, -1.0, -0.5),
+        (0.0, 1.0, -0.5),
+    ];
+    // Each position is followed by a 3-component attribute, each triangle by a padding index
+    let mut vertices: Vec<f32> = positions
+        .iter()
+        .flat_map(|&(x, y, z)| [x, y, z, 7.0, 7.0, 7.0])
+        .collect();
+    let mut indices: Vec<u32> = vec![0, 1, 2, 99, 3, 4, 5, 99];
+    let vertex_buf = unsafe {
+        SharedBuffer::try_new(&device, vertices.as_mut_ptr() as _, vertices.len() * 4).unwrap()
+    };
+    let index_buf = unsafe {
+        SharedBuffer::try_new(&device, indices.as_mut_ptr() as _, indices.len() * 4).unwrap()
+    };
+    let geometry = TriangleMeshGeometry::try_new_shared_strided(
+        &device,
+        &vertex_buf,
+        BufferLayout {
+            offset: 0,
+            stride: 6 * 4,
+        },
+        6,
+        &index_buf,
+        BufferLayout {
+            offset: 0,
+            stride: 4 * 4,
+        },
+        2,
+    )
+    .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let closest = scene
+        .point_query((0.0, 0.0, 0.0), f32::INFINITY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(closest.prim_id, 1);
+    assert_eq!(closest.point, (0.0, 0.0, -0.5));
+    assert_eq!(closest.distance, 0.5);
 }
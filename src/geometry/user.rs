@@ -1,8 +1,8 @@
-use std::{marker::PhantomData, ptr};
+use std::{any::Any, cell::Cell, sync::Arc};
 
-use crate::{device_error_or, Device};
+use crate::{device_error_or, CommittedScene, Device};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use embree4_sys::{RTCRayHit, RTC_INVALID_GEOMETRY_ID};
 
 use super::Geometry;
@@ -15,6 +15,40 @@ pub trait UserGeometryImpl {
     /// Returns the bounds of the geometry
     fn bounds(&self) -> embree4_sys::RTCBounds;
 
+    /// Returns the bounds of the geometry at a time step, for motion blur.
+    ///
+    /// Time steps are evenly distributed over the time range of the geometry, see
+    /// [`UserGeometry::set_time_step_count`]. Embree interpolates the bounds linearly between
+    /// time steps, so they must enclose the geometry at all times in between.
+    ///
+    /// Defaults to calling [`UserGeometryImpl::bounds`].
+    fn bounds_at(&self, time_step: u32) -> embree4_sys::RTCBounds {
+        let _ = time_step;
+        self.bounds()
+    }
+
+    /// Returns the number of primitives of the geometry.
+    ///
+    /// Embree builds its acceleration structure over the bounds of the individual primitives,
+    /// and calls [`UserGeometryImpl::intersect`] with the ID of each primitive a ray may hit.
+    /// Splitting many objects, e.g. analytic spheres, into primitives of one geometry is much
+    /// faster than a single primitive enclosing them all.
+    ///
+    /// Defaults to `1`.
+    fn primitive_count(&self) -> u32 {
+        1
+    }
+
+    /// Returns the bounds of a primitive at a time step, see
+    /// [`UserGeometryImpl::primitive_count`] and [`UserGeometryImpl::bounds_at`].
+    ///
+    /// Defaults to calling [`UserGeometryImpl::bounds_at`], which suits geometry with a single
+    /// primitive.
+    fn primitive_bounds(&self, prim_id: u32, time_step: u32) -> embree4_sys::RTCBounds {
+        let _ = prim_id;
+        self.bounds_at(time_step)
+    }
+
     /// Computes an intersection between the given ray and the geometry.
     /// If an intersection is found,
     ///
@@ -25,7 +59,9 @@ pub trait UserGeometryImpl {
     ///
     /// must all be updated.
     ///
-    /// Setting `ray_hit.hit.geomID` to the supplied `geom_id` signals an intersection.
+    /// Setting `ray_hit.hit.geomID` to the supplied `geom_id` signals an intersection. The
+    /// intersection is passed to the filter functions of the query, e.g. the scene's hit policy,
+    /// before it is committed.
     fn intersect(
         &self,
         geom_id: u32,
@@ -33,11 +69,172 @@ pub trait UserGeometryImpl {
         ctx: &embree4_sys::RTCRayQueryContext,
         ray_hit: &mut embree4_sys::RTCRayHit,
     );
+
+    /// Like [`UserGeometryImpl::intersect`], but additionally receives a [`ForwardIntersect`]
+    /// handle to continue traversal in another scene, e.g. to implement custom instancing.
+    ///
+    /// Defaults to calling [`UserGeometryImpl::intersect`].
+    fn intersect_with_forward(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        ctx: &embree4_sys::RTCRayQueryContext,
+        ray_hit: &mut embree4_sys::RTCRayHit,
+        forward: &ForwardIntersect,
+    ) {
+        let _ = forward;
+        self.intersect(geom_id, prim_id, ctx, ray_hit);
+    }
+
+    /// Tests whether the given ray is occluded by the geometry, e.g. for shadow rays.
+    ///
+    /// Setting `ray.tfar` to negative infinity signals that the ray is occluded. The occlusion is
+    /// passed to the filter functions of the query as a hit at the ray's original `tfar`, with
+    /// `geom_id` and `prim_id` but without normal or `u`/`v` coordinates.
+    ///
+    /// Defaults to calling [`UserGeometryImpl::intersect`] and reporting the ray as occluded if
+    /// it found an intersection. Override this if occlusion can be tested faster, e.g. without
+    /// computing normals and `u`/`v` coordinates.
+    fn occluded(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        ctx: &embree4_sys::RTCRayQueryContext,
+        ray: &mut embree4_sys::RTCRay,
+    ) {
+        let mut ray_hit = RTCRayHit {
+            ray: *ray,
+            hit: embree4_sys::RTCHit {
+                geomID: RTC_INVALID_GEOMETRY_ID,
+                ..Default::default()
+            },
+        };
+        self.intersect(geom_id, prim_id, ctx, &mut ray_hit);
+        if ray_hit.hit.geomID != RTC_INVALID_GEOMETRY_ID {
+            ray.tfar = f32::NEG_INFINITY;
+        }
+    }
+
+    /// Returns the point on a primitive closest to `point`, for
+    /// [`CommittedScene::point_query`](crate::CommittedScene::point_query).
+    ///
+    /// Defaults to `None`, which excludes the geometry from closest point queries.
+    fn closest_point(&self, prim_id: u32, point: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+        let _ = (prim_id, point);
+        None
+    }
+
+    /// Like [`UserGeometryImpl::occluded`], but additionally receives a [`ForwardOccluded`]
+    /// handle to continue the occlusion test in another scene, e.g. to implement custom
+    /// instancing.
+    ///
+    /// Defaults to calling [`UserGeometryImpl::occluded`].
+    fn occluded_with_forward(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        ctx: &embree4_sys::RTCRayQueryContext,
+        ray: &mut embree4_sys::RTCRay,
+        forward: &ForwardOccluded,
+    ) {
+        let _ = forward;
+        self.occluded(geom_id, prim_id, ctx, ray);
+    }
+}
+
+/// A handle to forward a ray from a user geometry intersect callback into another scene.
+///
+/// See [`UserGeometryImpl::intersect_with_forward`].
+pub struct ForwardIntersect {
+    args: *const embree4_sys::RTCIntersectFunctionNArguments,
+    forwarded: Cell<bool>,
 }
 
+impl ForwardIntersect {
+    /// Continues traversal of the current ray in `scene`, using `ray` as the ray in the space of
+    /// `scene`, e.g. transformed into the object space of an instance.
+    ///
+    /// Hits found in `scene` are reported directly to the traversal that invoked the callback,
+    /// with `inst_id` pushed onto the instance ID stack. After forwarding, changes the callback
+    /// makes to its `ray_hit` are ignored.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to forward the ray into.
+    /// * `ray` - The ray in the space of `scene`. Only the origin, direction and `tnear` are used.
+    /// * `inst_id` - The instance ID to report for hits in `scene`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the ray was forwarded, or an error if the callback was invoked
+    /// for a ray packet, which cannot be forwarded.
+    pub fn forward(
+        &self,
+        scene: &CommittedScene,
+        ray: impl Into<embree4_sys::RTCRay>,
+        inst_id: u32,
+    ) -> Result<()> {
+        if unsafe { (*self.args).N } != 1 {
+            bail!("Could not forward ray: only single ray queries can be forwarded");
+        }
+
+        let mut ray = ray.into();
+        unsafe {
+            embree4_sys::rtcForwardIntersect1(self.args, scene.scene.handle, &mut ray, inst_id);
+        }
+        self.forwarded.set(true);
+        Ok(())
+    }
+}
+
+/// A handle to forward an occlusion ray from a user geometry occluded callback into another
+/// scene.
+///
+/// See [`UserGeometryImpl::occluded_with_forward`].
+pub struct ForwardOccluded {
+    args: *const embree4_sys::RTCOccludedFunctionNArguments,
+    forwarded: Cell<bool>,
+}
+
+impl ForwardOccluded {
+    /// Continues the occlusion test of the current ray in `scene`, using `ray` as the ray in the
+    /// space of `scene`, e.g. transformed into the object space of an instance.
+    ///
+    /// Occlusion found in `scene` is reported directly to the traversal that invoked the
+    /// callback. After forwarding, changes the callback makes to its `ray` are ignored.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to forward the ray into.
+    /// * `ray` - The ray in the space of `scene`. Only the origin, direction and `tnear` are used.
+    /// * `inst_id` - The instance ID to push onto the instance ID stack while traversing `scene`.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the ray was forwarded, or an error if the callback was invoked
+    /// for a ray packet, which cannot be forwarded.
+    pub fn forward(
+        &self,
+        scene: &CommittedScene,
+        ray: impl Into<embree4_sys::RTCRay>,
+        inst_id: u32,
+    ) -> Result<()> {
+        if unsafe { (*self.args).N } != 1 {
+            bail!("Could not forward ray: only single ray queries can be forwarded");
+        }
+
+        let mut ray = ray.into();
+        unsafe {
+            embree4_sys::rtcForwardOccluded1(self.args, scene.scene.handle, &mut ray, inst_id);
+        }
+        self.forwarded.set(true);
+        Ok(())
+    }
+}
+
+/// A geometry whose primitives are intersected by a [`UserGeometryImpl`].
+///
+/// The geometry shares ownership of its data with the scenes it is attached to, so the data
+/// stays alive while Embree may call into it, even after the geometry is dropped.
 pub struct UserGeometry<T: UserGeometryImpl> {
     handle: embree4_sys::RTCGeometry,
-    data: PhantomData<T>,
+    data: Arc<T>,
 }
 
 #[allow(clippy::missing_safety_doc)]
@@ -47,26 +244,39 @@ impl<T: UserGeometryImpl> UserGeometry<T> {
     /// # Arguments
     ///
     /// * `device` - The Embree device.
-    /// * `data` - The user-defined data associated with the geometry.
-    /// * `bounds_fn` - The function pointer to the bounds function.
-    /// * `intersect_fn` - The function pointer to the intersect function.
-    /// * `occluded_fn` - The function pointer to the occluded function.
+    /// * `data` - The user-defined data associated with the geometry, either by value or already
+    ///   shared in an [`Arc`].
     ///
     /// # Returns
     ///
     /// A `Result` containing the `UserGeometry` object if successful, or an `anyhow::Error` if an error occurred.
-    pub fn try_new(device: &Device, data: &T) -> Result<Self> {
+    pub fn try_new(device: &Device, data: impl Into<Arc<T>>) -> Result<Self> {
+        Self::try_new_with_fns(
+            device,
+            data.into(),
+            internal_intersect_fn::<T>,
+            internal_occluded_fn::<T>,
+        )
+    }
+
+    /// Creates a new `UserGeometry` object with the given intersect and occluded functions.
+    pub(super) fn try_new_with_fns(
+        device: &Device,
+        data: Arc<T>,
+        intersect_fn: unsafe extern "C" fn(*const embree4_sys::RTCIntersectFunctionNArguments),
+        occluded_fn: unsafe extern "C" fn(*const embree4_sys::RTCOccludedFunctionNArguments),
+    ) -> Result<Self> {
         let handle = unsafe {
             embree4_sys::rtcNewGeometry(device.handle, embree4_sys::RTCGeometryType::USER)
         };
         device_error_or(device, (), "Could not create user geometry")?;
 
         unsafe {
-            embree4_sys::rtcSetGeometryUserPrimitiveCount(handle, 1);
+            embree4_sys::rtcSetGeometryUserPrimitiveCount(handle, data.primitive_count());
         }
         device_error_or(device, (), "Could not set user geometry primitive count")?;
 
-        let data_ptr = data as *const _ as _;
+        let data_ptr = Arc::as_ptr(&data) as _;
 
         unsafe {
             embree4_sys::rtcSetGeometryUserData(handle, data_ptr);
@@ -83,40 +293,83 @@ impl<T: UserGeometryImpl> UserGeometry<T> {
         device_error_or(device, (), "Could not set user geometry bounds function")?;
 
         unsafe {
-            embree4_sys::rtcSetGeometryIntersectFunction(handle, Some(internal_intersect_fn::<T>));
+            embree4_sys::rtcSetGeometryIntersectFunction(handle, Some(intersect_fn));
         }
         device_error_or(device, (), "Could not set user geometry intersect function")?;
 
-        // unsafe {
-        //     embree4_sys::rtcSetGeometryOccludedFunction(handle, Some(occluded_fn));
-        // }
-        // device_error_or(device, (), "Could not set user geometry occluded function")?;
+        unsafe {
+            embree4_sys::rtcSetGeometryOccludedFunction(handle, Some(occluded_fn));
+        }
+        device_error_or(device, (), "Could not set user geometry occluded function")?;
 
-        // unsafe {
-        //     embree4_sys::rtcSetGeometryPointQueryFunction(
-        //         handle,
-        //         Some(internal_point_query_fn::<T>),
-        //     )
-        // }
+        unsafe {
+            embree4_sys::rtcSetGeometryPointQueryFunction(
+                handle,
+                Some(internal_point_query_fn::<T>),
+            );
+        }
+        device_error_or(
+            device,
+            (),
+            "Could not set user geometry point query function",
+        )?;
 
         unsafe {
             embree4_sys::rtcCommitGeometry(handle);
         }
         device_error_or(device, (), "Could not commit user geometry")?;
 
-        Ok(Self {
-            handle,
-            data: PhantomData,
-        })
+        Ok(Self { handle, data })
     }
 }
 
-impl<T: UserGeometryImpl> Geometry for UserGeometry<T> {
+impl<T: UserGeometryImpl> UserGeometry<T> {
+    /// Returns the user-defined data associated with the geometry.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Sets the number of time steps for motion blur, and recommits the geometry.
+    ///
+    /// Embree queries [`UserGeometryImpl::bounds_at`] for each time step when building the
+    /// scene. Scenes the geometry is attached to must be committed again for the change to take
+    /// effect.
+    ///
+    /// # Arguments
+    /// * `device` - The Embree device.
+    /// * `count` - The number of time steps, from 1 to
+    ///   [`RTC_MAX_TIME_STEP_COUNT`](embree4_sys::RTC_MAX_TIME_STEP_COUNT).
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the count was set, or an error if an error occurred.
+    pub fn set_time_step_count(&mut self, device: &Device, count: u32) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcSetGeometryTimeStepCount(self.handle, count);
+        }
+        device_error_or(device, (), "Could not set user geometry time step count")?;
+
+        unsafe {
+            embree4_sys::rtcCommitGeometry(self.handle);
+        }
+        device_error_or(device, (), "Could not commit user geometry")
+    }
+}
+
+impl<T: UserGeometryImpl + Send + Sync + 'static> Geometry for UserGeometry<T> {
     fn geometry(&self) -> embree4_sys::RTCGeometry {
         self.handle
     }
+
+    fn retained_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        Some(self.data.clone())
+    }
 }
 
+// Embree calls the user data's functions from its worker threads, and the data is shared with
+// the scenes the geometry is attached to, which requires `T: Send + Sync`
+unsafe impl<T: UserGeometryImpl + Send + Sync> Send for UserGeometry<T> {}
+unsafe impl<T: UserGeometryImpl + Send + Sync> Sync for UserGeometry<T> {}
+
 impl<T: UserGeometryImpl> Drop for UserGeometry<T> {
     fn drop(&mut self) {
         unsafe {
@@ -129,18 +382,20 @@ unsafe extern "C" fn internal_bounds_fn<T: UserGeometryImpl>(
     args: *const embree4_sys::RTCBoundsFunctionArguments,
 ) {
     let args = *args;
-    let geom_ptr = args.geometryUserPtr as *const T;
-    let geom = ptr::read(geom_ptr);
+    let geom = &*(args.geometryUserPtr as *const T);
 
-    *args.bounds_o = geom.bounds();
+    *args.bounds_o = geom.primitive_bounds(args.primID, args.timeStep);
 }
 
 unsafe extern "C" fn internal_intersect_fn<T: UserGeometryImpl>(
     args: *const embree4_sys::RTCIntersectFunctionNArguments,
 ) {
+    let forward = ForwardIntersect {
+        args,
+        forwarded: Cell::new(false),
+    };
     let args = &*args;
-    let geom_ptr = args.geometryUserPtr as *const T;
-    let geom = ptr::read(geom_ptr);
+    let geom = &*(args.geometryUserPtr as *const T);
 
     let rayhit_n = args.rayhit as *mut f32;
 
@@ -182,7 +437,8 @@ unsafe extern "C" fn internal_intersect_fn<T: UserGeometryImpl>(
 
         let prim_id = hit_n.add(offset(5, n, i)) as *mut u32;
         let geom_id = hit_n.add(offset(6, n, i)) as *mut u32;
-        let inst_id = hit_n.add(offset(7, n, i)) as *mut u32;
+        // The instance ID stack follows as one field per instance level
+        let inst_id = |level: usize| hit_n.add(offset(7 + level, n, i)) as *mut u32;
 
         let mut ray_hit = RTCRayHit {
             ray: embree4_sys::RTCRay {
@@ -207,13 +463,17 @@ unsafe extern "C" fn internal_intersect_fn<T: UserGeometryImpl>(
                 v: *v,
                 primID: *prim_id,
                 geomID: *geom_id,
-                instID: [*inst_id],
+                instID: std::array::from_fn(|level| *inst_id(level)),
             },
         };
 
-        geom.intersect(args.geomID, args.primID, context, &mut ray_hit);
+        geom.intersect_with_forward(args.geomID, args.primID, context, &mut ray_hit, &forward);
 
-        if ray_hit.hit.geomID != RTC_INVALID_GEOMETRY_ID {
+        // Forwarded hits were already reported by Embree
+        if !forward.forwarded.get()
+            && ray_hit.hit.geomID != RTC_INVALID_GEOMETRY_ID
+            && filter_intersect(forward.args, &mut ray_hit)
+        {
             *tfar = ray_hit.ray.tfar;
 
             *ng_x = ray_hit.hit.Ng_x;
@@ -225,12 +485,351 @@ unsafe extern "C" fn internal_intersect_fn<T: UserGeometryImpl>(
 
             *prim_id = ray_hit.hit.primID;
             *geom_id = ray_hit.hit.geomID;
-            *inst_id = ray_hit.hit.instID[0];
+            for (level, &id) in ray_hit.hit.instID.iter().enumerate() {
+                *inst_id(level) = id;
+            }
         }
     }
 }
 
+unsafe extern "C" fn internal_occluded_fn<T: UserGeometryImpl>(
+    args: *const embree4_sys::RTCOccludedFunctionNArguments,
+) {
+    let forward = ForwardOccluded {
+        args,
+        forwarded: Cell::new(false),
+    };
+    let args = &*args;
+    let geom = &*(args.geometryUserPtr as *const T);
+
+    let ray_n = args.ray as *mut f32;
+
+    let valid_ptr = args.valid as *const u32;
+    let valid = std::slice::from_raw_parts(valid_ptr, args.N as usize);
+
+    let context = &*(args.context as *const embree4_sys::RTCRayQueryContext);
+
+    let n = args.N as usize;
+    for (i, valid) in valid.iter().enumerate() {
+        if *valid == 0 {
+            continue;
+        }
+
+        let tfar = ray_n.add(offset(8, n, i));
+
+        let ray = embree4_sys::RTCRay {
+            org_x: *ray_n.add(offset(0, n, i)),
+            org_y: *ray_n.add(offset(1, n, i)),
+            org_z: *ray_n.add(offset(2, n, i)),
+            tnear: *ray_n.add(offset(3, n, i)),
+            dir_x: *ray_n.add(offset(4, n, i)),
+            dir_y: *ray_n.add(offset(5, n, i)),
+            dir_z: *ray_n.add(offset(6, n, i)),
+            time: *ray_n.add(offset(7, n, i)),
+            tfar: *tfar,
+            mask: *(ray_n.add(offset(9, n, i)) as *const u32),
+            id: *(ray_n.add(offset(10, n, i)) as *const u32),
+            flags: *(ray_n.add(offset(11, n, i)) as *const u32),
+        };
+
+        let mut occluded = ray;
+        geom.occluded_with_forward(args.geomID, args.primID, context, &mut occluded, &forward);
+
+        // Forwarded occlusion was already reported by Embree
+        if !forward.forwarded.get()
+            && occluded.tfar == f32::NEG_INFINITY
+            && filter_occluded(forward.args, ray, args.primID, context)
+        {
+            *tfar = f32::NEG_INFINITY;
+        }
+    }
+}
+
+unsafe extern "C" fn internal_point_query_fn<T: UserGeometryImpl>(
+    args: *mut embree4_sys::RTCPointQueryFunctionArguments,
+) -> bool {
+    crate::point_query::update_closest_point(args, |geometry, prim_id, point| {
+        let geom = &*(embree4_sys::rtcGetGeometryUserData(geometry) as *const T);
+        geom.closest_point(prim_id, point)
+    })
+}
+
+/// Passes a candidate hit to the filter functions of the geometry and the query, which may
+/// modify it.
+///
+/// Returns `true` if the hit was accepted.
+pub(crate) unsafe fn filter_intersect(
+    args: *const embree4_sys::RTCIntersectFunctionNArguments,
+    ray_hit: &mut RTCRayHit,
+) -> bool {
+    let mut valid = -1;
+    let filter_args = embree4_sys::RTCFilterFunctionNArguments {
+        valid: &mut valid,
+        geometryUserPtr: (*args).geometryUserPtr,
+        context: (*args).context,
+        ray: &mut ray_hit.ray as *mut embree4_sys::RTCRay as *mut _,
+        hit: &mut ray_hit.hit as *mut embree4_sys::RTCHit as *mut _,
+        N: 1,
+    };
+    embree4_sys::rtcInvokeIntersectFilterFromGeometry(args, &filter_args);
+    valid != 0
+}
+
+/// Passes the occlusion of `ray` by a primitive to the filter functions of the geometry and the
+/// query, as a hit at the ray's `tfar`.
+///
+/// Returns `true` if the occlusion was accepted.
+pub(crate) unsafe fn filter_occluded(
+    args: *const embree4_sys::RTCOccludedFunctionNArguments,
+    ray: embree4_sys::RTCRay,
+    prim_id: u32,
+    context: &embree4_sys::RTCRayQueryContext,
+) -> bool {
+    let mut ray_hit = RTCRayHit {
+        ray,
+        hit: embree4_sys::RTCHit {
+            primID: prim_id,
+            geomID: (*args).geomID,
+            instID: context.instID,
+            ..Default::default()
+        },
+    };
+    let mut valid = -1;
+    let filter_args = embree4_sys::RTCFilterFunctionNArguments {
+        valid: &mut valid,
+        geometryUserPtr: (*args).geometryUserPtr,
+        context: (*args).context,
+        ray: &mut ray_hit.ray as *mut embree4_sys::RTCRay as *mut _,
+        hit: &mut ray_hit.hit as *mut embree4_sys::RTCHit as *mut _,
+        N: 1,
+    };
+    embree4_sys::rtcInvokeOccludedFilterFromGeometry(args, &filter_args);
+    valid != 0
+}
+
 #[inline(always)]
 fn offset(offset: usize, n: usize, i: usize) -> usize {
     offset * n + i
 }
+
+#[cfg(test)]
+/// A unit square in the xy-plane, moving from z = 1 to z = 3 over the shutter interval.
+struct MovingSquare;
+
+#[cfg(test)]
+impl MovingSquare {
+    fn z(time: f32) -> f32 {
+        1.0 + 2.0 * time
+    }
+}
+
+#[cfg(test)]
+impl UserGeometryImpl for MovingSquare {
+    fn bounds(&self) -> embree4_sys::RTCBounds {
+        self.bounds_at(0)
+    }
+
+    fn closest_point(&self, _prim_id: u32, point: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+        Some((
+            point.0.clamp(-1.0, 1.0),
+            point.1.clamp(-1.0, 1.0),
+            Self::z(0.0),
+        ))
+    }
+
+    fn bounds_at(&self, time_step: u32) -> embree4_sys::RTCBounds {
+        let z = Self::z(time_step as f32);
+        embree4_sys::RTCBounds {
+            lower_x: -1.0,
+            lower_y: -1.0,
+            lower_z: z,
+            align0: 0.0,
+            upper_x: 1.0,
+            upper_y: 1.0,
+            upper_z: z,
+            align1: 0.0,
+        }
+    }
+
+    fn intersect(
+        &self,
+        geom_id: u32,
+        prim_id: u32,
+        _ctx: &embree4_sys::RTCRayQueryContext,
+        ray_hit: &mut RTCRayHit,
+    ) {
+        let ray = &mut ray_hit.ray;
+        let t = (Self::z(ray.time) - ray.org_z) / ray.dir_z;
+        let (x, y) = (ray.org_x + t * ray.dir_x, ray.org_y + t * ray.dir_y);
+        if t < ray.tnear || t > ray.tfar || x.abs() > 1.0 || y.abs() > 1.0 {
+            return;
+        }
+        ray.tfar = t;
+        ray_hit.hit.Ng_z = -1.0;
+        ray_hit.hit.primID = prim_id;
+        ray_hit.hit.geomID = geom_id;
+    }
+}
+
+#[test]
+fn bounds_at_follows_motion() {
+    let device = Device::try_new(None).unwrap();
+    let square = MovingSquare;
+    let mut geometry = UserGeometry::try_new(&device, square).unwrap();
+    geometry.set_time_step_count(&device, 2).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    for time in [0.0, 0.5, 1.0] {
+        let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).with_time(time);
+        let hit = scene.intersect_1(ray).unwrap().unwrap();
+        assert_eq!(hit.t(), MovingSquare::z(time));
+    }
+}
+
+#[test]
+fn scene_retains_user_data() {
+    let device = Device::try_new(None).unwrap();
+    let geometry = UserGeometry::try_new(&device, MovingSquare).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    drop(geometry);
+
+    let scene = scene.commit().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(
+        scene.intersect_1(ray).unwrap().unwrap().t(),
+        MovingSquare::z(0.0)
+    );
+}
+
+#[test]
+fn occluded_falls_back_to_intersect() {
+    let device = Device::try_new(None).unwrap();
+    let square = MovingSquare;
+    let geometry = UserGeometry::try_new(&device, square).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let blocked = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.occluded_1(blocked).unwrap());
+    let short = blocked.with_tfar(0.5);
+    assert!(!scene.occluded_1(short).unwrap());
+    let beside = crate::Ray::new((2.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(!scene.occluded_1(beside).unwrap());
+}
+
+#[test]
+fn multiple_primitives() {
+    /// Unit squares in the xy-plane, one primitive per z coordinate.
+    struct Squares(Vec<f32>);
+
+    impl UserGeometryImpl for Squares {
+        fn bounds(&self) -> embree4_sys::RTCBounds {
+            unreachable!("bounds are queried per primitive")
+        }
+
+        fn primitive_count(&self) -> u32 {
+            self.0.len() as u32
+        }
+
+        fn primitive_bounds(&self, prim_id: u32, _time_step: u32) -> embree4_sys::RTCBounds {
+            let z = self.0[prim_id as usize];
+            embree4_sys::RTCBounds {
+                lower_x: -1.0,
+                lower_y: -1.0,
+                lower_z: z,
+                align0: 0.0,
+                upper_x: 1.0,
+                upper_y: 1.0,
+                upper_z: z,
+                align1: 0.0,
+            }
+        }
+
+        fn intersect(
+            &self,
+            geom_id: u32,
+            prim_id: u32,
+            _ctx: &embree4_sys::RTCRayQueryContext,
+            ray_hit: &mut RTCRayHit,
+        ) {
+            let ray = &mut ray_hit.ray;
+            let t = (self.0[prim_id as usize] - ray.org_z) / ray.dir_z;
+            if t < ray.tnear || t > ray.tfar {
+                return;
+            }
+            ray.tfar = t;
+            ray_hit.hit.Ng_z = -1.0;
+            ray_hit.hit.primID = prim_id;
+            ray_hit.hit.geomID = geom_id;
+        }
+    }
+
+    let device = Device::try_new(None).unwrap();
+    let squares = Squares(vec![5.0, 2.0, 8.0]);
+    let geometry = UserGeometry::try_new(&device, squares).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!((hit.t(), hit.prim_id()), (2.0, 1));
+    let hit = scene.intersect_1(ray.with_tnear(3.0)).unwrap().unwrap();
+    assert_eq!((hit.t(), hit.prim_id()), (5.0, 0));
+}
+
+#[test]
+fn closest_point_on_user_geometry() {
+    let device = Device::try_new(None).unwrap();
+    let square = MovingSquare;
+    let geometry = UserGeometry::try_new(&device, square).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let geom_id = scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let closest = scene
+        .point_query((3.0, 0.5, 0.0), f32::INFINITY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(closest.point, (1.0, 0.5, 1.0));
+    assert_eq!((closest.geom_id, closest.prim_id), (geom_id, 0));
+    assert!(scene.point_query((3.0, 0.5, 0.0), 2.0).unwrap().is_none());
+}
+
+#[test]
+fn hit_policy_rejects_user_geometry() {
+    use crate::{FilterDecision, Hit};
+
+    let device = Device::try_new(None).unwrap();
+    let square = MovingSquare;
+    let geometry = UserGeometry::try_new(&device, square).unwrap();
+    let triangle = crate::geometry::TriangleMeshGeometry::try_new(
+        &device,
+        &[(-1.0, -1.0, 4.0), (1.0, -1.0, 4.0), (0.0, 1.0, 4.0)],
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+    let mut scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let square_id = scene.attach_geometry(&geometry).unwrap();
+    let triangle_id = scene.attach_geometry(&triangle).unwrap();
+    scene
+        .set_hit_policy(move |hit: &Hit| {
+            if hit.geom_id() == square_id {
+                FilterDecision::Reject
+            } else {
+                FilterDecision::Accept
+            }
+        })
+        .unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!((hit.geom_id(), hit.t()), (triangle_id, 4.0));
+    assert!(scene.occluded_1(ray).unwrap());
+    assert!(!scene.occluded_1(ray.with_tfar(2.0)).unwrap());
+}
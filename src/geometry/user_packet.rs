@@ -0,0 +1,391 @@
+use std::slice;
+
+use anyhow::Result;
+
+use crate::Device;
+
+use super::{
+    user::{filter_intersect, filter_occluded},
+    UserGeometry, UserGeometryImpl,
+};
+
+/// A user geometry implementation which intersects all active rays of a ray packet at once,
+/// e.g. with SIMD instructions.
+///
+/// Create the geometry with [`UserGeometry::try_new_packet`]. The scalar
+/// [`UserGeometryImpl::intersect`] is then only used by the default
+/// [`UserGeometryPacketImpl::occluded_packet`].
+pub trait UserGeometryPacketImpl: UserGeometryImpl {
+    /// Computes the intersections between the active rays of `packet` and a primitive.
+    ///
+    /// Report intersections with [`RayHitPacket::report_hit`].
+    fn intersect_packet(&self, geom_id: u32, prim_id: u32, packet: &mut RayHitPacket);
+
+    /// Tests whether the active rays of `packet` are occluded by a primitive.
+    ///
+    /// Report occluded rays with [`RayPacket::set_occluded`].
+    ///
+    /// Defaults to calling [`UserGeometryImpl::occluded`] for each active ray.
+    fn occluded_packet(&self, geom_id: u32, prim_id: u32, packet: &mut RayPacket) {
+        for i in 0..packet.len() {
+            if !packet.is_active(i) {
+                continue;
+            }
+            let mut ray = packet.get(i);
+            self.occluded(geom_id, prim_id, packet.context(), &mut ray);
+            if ray.tfar == f32::NEG_INFINITY {
+                packet.set_occluded(i);
+            }
+        }
+    }
+}
+
+/// The rays of a packet passed to user geometry, in structure-of-arrays layout.
+///
+/// Every accessor returns one value per ray, including inactive ones, see
+/// [`RayPacket::is_active`].
+pub struct RayPacket<'a> {
+    valid: &'a [i32],
+    ray: *mut f32,
+    context: &'a embree4_sys::RTCRayQueryContext,
+    /// The arguments of the occlusion callback, or null for intersection packets.
+    occluded: *const embree4_sys::RTCOccludedFunctionNArguments,
+}
+
+impl<'a> RayPacket<'a> {
+    /// Returns the number of rays in the packet, active or not.
+    pub fn len(&self) -> usize {
+        self.valid.len()
+    }
+
+    /// Returns `true` if the packet has no rays.
+    pub fn is_empty(&self) -> bool {
+        self.valid.is_empty()
+    }
+
+    /// Returns the validity mask of the rays, with `-1` for active and `0` for inactive rays.
+    pub fn valid(&self) -> &[i32] {
+        self.valid
+    }
+
+    /// Returns `true` if ray `i` is active.
+    pub fn is_active(&self, i: usize) -> bool {
+        self.valid[i] != 0
+    }
+
+    /// Returns the ray query context.
+    pub fn context(&self) -> &embree4_sys::RTCRayQueryContext {
+        self.context
+    }
+
+    fn field(&self, field: usize) -> &[f32] {
+        unsafe { slice::from_raw_parts(self.ray.add(field * self.len()), self.len()) }
+    }
+
+    fn field_u32(&self, field: usize) -> &[u32] {
+        unsafe { slice::from_raw_parts(self.ray.add(field * self.len()) as *const u32, self.len()) }
+    }
+
+    /// Returns the x coordinates of the ray origins.
+    pub fn org_x(&self) -> &[f32] {
+        self.field(0)
+    }
+
+    /// Returns the y coordinates of the ray origins.
+    pub fn org_y(&self) -> &[f32] {
+        self.field(1)
+    }
+
+    /// Returns the z coordinates of the ray origins.
+    pub fn org_z(&self) -> &[f32] {
+        self.field(2)
+    }
+
+    /// Returns the start of the ray segments.
+    pub fn tnear(&self) -> &[f32] {
+        self.field(3)
+    }
+
+    /// Returns the x coordinates of the ray directions.
+    pub fn dir_x(&self) -> &[f32] {
+        self.field(4)
+    }
+
+    /// Returns the y coordinates of the ray directions.
+    pub fn dir_y(&self) -> &[f32] {
+        self.field(5)
+    }
+
+    /// Returns the z coordinates of the ray directions.
+    pub fn dir_z(&self) -> &[f32] {
+        self.field(6)
+    }
+
+    /// Returns the ray times.
+    pub fn time(&self) -> &[f32] {
+        self.field(7)
+    }
+
+    /// Returns the end of the ray segments, i.e. the distance of the closest hit so far.
+    pub fn tfar(&self) -> &[f32] {
+        self.field(8)
+    }
+
+    /// Returns the ray masks.
+    pub fn mask(&self) -> &[u32] {
+        self.field_u32(9)
+    }
+
+    /// Returns the ray IDs.
+    pub fn id(&self) -> &[u32] {
+        self.field_u32(10)
+    }
+
+    /// Returns ray `i` as a single ray.
+    pub fn get(&self, i: usize) -> embree4_sys::RTCRay {
+        embree4_sys::RTCRay {
+            org_x: self.org_x()[i],
+            org_y: self.org_y()[i],
+            org_z: self.org_z()[i],
+            tnear: self.tnear()[i],
+            dir_x: self.dir_x()[i],
+            dir_y: self.dir_y()[i],
+            dir_z: self.dir_z()[i],
+            time: self.time()[i],
+            tfar: self.tfar()[i],
+            mask: self.mask()[i],
+            id: self.id()[i],
+            flags: self.field_u32(11)[i],
+        }
+    }
+
+    /// Marks ray `i` as occluded. Only meaningful for occlusion queries.
+    ///
+    /// The occlusion is passed to the filter functions of the query first, see
+    /// [`UserGeometryImpl::occluded`].
+    ///
+    /// # Returns
+    /// `true` if the ray was marked occluded.
+    pub fn set_occluded(&mut self, i: usize) -> bool {
+        assert!(i < self.len(), "ray index {} out of range", i);
+        if !self.occluded.is_null() {
+            let prim_id = unsafe { (*self.occluded).primID };
+            if !unsafe { filter_occluded(self.occluded, self.get(i), prim_id, self.context) } {
+                return false;
+            }
+        }
+        unsafe {
+            *self.ray.add(8 * self.len() + i) = f32::NEG_INFINITY;
+        }
+        true
+    }
+}
+
+/// The rays and hits of a packet passed to user geometry, in structure-of-arrays layout.
+pub struct RayHitPacket<'a> {
+    rays: RayPacket<'a>,
+    hit: *mut f32,
+    args: *const embree4_sys::RTCIntersectFunctionNArguments,
+}
+
+impl<'a> RayHitPacket<'a> {
+    /// Returns the rays of the packet.
+    pub fn rays(&self) -> &RayPacket<'a> {
+        &self.rays
+    }
+
+    /// Reports a hit of ray `i`, if it is closer than the closest hit so far.
+    ///
+    /// The instance ID stack of the hit is taken from the ray query context. The hit is passed to
+    /// the filter functions of the query before it is recorded, see
+    /// [`UserGeometryImpl::intersect`].
+    ///
+    /// # Arguments
+    /// * `i` - The index of the ray in the packet.
+    /// * `t` - The hit distance. Hits outside of `[tnear, tfar]` are ignored.
+    /// * `normal` - The unnormalized geometric normal.
+    /// * `uv` - The `u` and `v` coordinates of the hit on the primitive.
+    /// * `geom_id` - The geometry ID passed to the callback.
+    /// * `prim_id` - The primitive ID passed to the callback.
+    ///
+    /// # Returns
+    /// `true` if the hit was recorded.
+    pub fn report_hit(
+        &mut self,
+        i: usize,
+        t: f32,
+        normal: (f32, f32, f32),
+        uv: (f32, f32),
+        geom_id: u32,
+        prim_id: u32,
+    ) -> bool {
+        let rays = &self.rays;
+        if !rays.is_active(i) || t < rays.tnear()[i] || t > rays.tfar()[i] {
+            return false;
+        }
+
+        let mut ray_hit = embree4_sys::RTCRayHit {
+            ray: embree4_sys::RTCRay {
+                tfar: t,
+                ..rays.get(i)
+            },
+            hit: embree4_sys::RTCHit {
+                Ng_x: normal.0,
+                Ng_y: normal.1,
+                Ng_z: normal.2,
+                u: uv.0,
+                v: uv.1,
+                primID: prim_id,
+                geomID: geom_id,
+                instID: rays.context.instID,
+            },
+        };
+        if !unsafe { filter_intersect(self.args, &mut ray_hit) } {
+            return false;
+        }
+
+        let n = rays.len();
+        unsafe {
+            *rays.ray.add(8 * n + i) = ray_hit.ray.tfar;
+
+            let hit = |field: usize| self.hit.add(field * n + i);
+            *hit(0) = ray_hit.hit.Ng_x;
+            *hit(1) = ray_hit.hit.Ng_y;
+            *hit(2) = ray_hit.hit.Ng_z;
+            *hit(3) = ray_hit.hit.u;
+            *hit(4) = ray_hit.hit.v;
+            *(hit(5) as *mut u32) = ray_hit.hit.primID;
+            *(hit(6) as *mut u32) = ray_hit.hit.geomID;
+            for (level, &inst_id) in ray_hit.hit.instID.iter().enumerate() {
+                *(hit(7 + level) as *mut u32) = inst_id;
+            }
+        }
+        true
+    }
+}
+
+impl<T: UserGeometryPacketImpl> UserGeometry<T> {
+    /// Creates a new `UserGeometry` object, which intersects ray packets through
+    /// [`UserGeometryPacketImpl`] instead of ray by ray.
+    ///
+    /// # Arguments
+    /// * `device` - The Embree device.
+    /// * `data` - The user-defined data associated with the geometry, either by value or already
+    ///   shared in an [`Arc`](std::sync::Arc).
+    ///
+    /// # Returns
+    /// A `Result` containing the `UserGeometry` object if successful, or an error if an error
+    /// occurred.
+    pub fn try_new_packet(device: &Device, data: impl Into<std::sync::Arc<T>>) -> Result<Self> {
+        Self::try_new_with_fns(
+            device,
+            data.into(),
+            intersect_packet_fn::<T>,
+            occluded_packet_fn::<T>,
+        )
+    }
+}
+
+unsafe extern "C" fn intersect_packet_fn<T: UserGeometryPacketImpl>(
+    args: *const embree4_sys::RTCIntersectFunctionNArguments,
+) {
+    let args = &*args;
+    let geom = &*(args.geometryUserPtr as *const T);
+    let n = args.N as usize;
+
+    let ray = args.rayhit as *mut f32;
+    let mut packet = RayHitPacket {
+        rays: RayPacket {
+            valid: slice::from_raw_parts(args.valid, n),
+            ray,
+            context: &*args.context,
+            occluded: std::ptr::null(),
+        },
+        // RTCRayHitN stores the 12 ray fields of all lanes before the hit fields
+        hit: ray.add(12 * n),
+        args,
+    };
+    geom.intersect_packet(args.geomID, args.primID, &mut packet);
+}
+
+unsafe extern "C" fn occluded_packet_fn<T: UserGeometryPacketImpl>(
+    args: *const embree4_sys::RTCOccludedFunctionNArguments,
+) {
+    let args = &*args;
+    let geom = &*(args.geometryUserPtr as *const T);
+    let n = args.N as usize;
+
+    let mut packet = RayPacket {
+        valid: slice::from_raw_parts(args.valid, n),
+        ray: args.ray as *mut f32,
+        context: &*args.context,
+        occluded: args,
+    };
+    geom.occluded_packet(args.geomID, args.primID, &mut packet);
+}
+
+#[test]
+fn packet_user_geometry() {
+    use embree4_sys::RTCRayHit;
+
+    /// The plane z = 1, bounded to a unit square.
+    struct Plane;
+
+    impl UserGeometryImpl for Plane {
+        fn bounds(&self) -> embree4_sys::RTCBounds {
+            embree4_sys::RTCBounds {
+                lower_x: -1.0,
+                lower_y: -1.0,
+                lower_z: 1.0,
+                align0: 0.0,
+                upper_x: 1.0,
+                upper_y: 1.0,
+                upper_z: 1.0,
+                align1: 0.0,
+            }
+        }
+
+        fn intersect(
+            &self,
+            geom_id: u32,
+            prim_id: u32,
+            _ctx: &embree4_sys::RTCRayQueryContext,
+            ray_hit: &mut RTCRayHit,
+        ) {
+            let t = (1.0 - ray_hit.ray.org_z) / ray_hit.ray.dir_z;
+            if t >= ray_hit.ray.tnear && t <= ray_hit.ray.tfar {
+                ray_hit.ray.tfar = t;
+                ray_hit.hit.primID = prim_id;
+                ray_hit.hit.geomID = geom_id;
+            }
+        }
+    }
+
+    impl UserGeometryPacketImpl for Plane {
+        fn intersect_packet(&self, geom_id: u32, prim_id: u32, packet: &mut RayHitPacket) {
+            let rays = packet.rays();
+            let t: Vec<f32> = rays
+                .org_z()
+                .iter()
+                .zip(rays.dir_z())
+                .map(|(org_z, dir_z)| (1.0 - org_z) / dir_z)
+                .collect();
+            for (i, t) in t.into_iter().enumerate() {
+                packet.report_hit(i, t, (0.0, 0.0, -1.0), (0.0, 0.0), geom_id, prim_id);
+            }
+        }
+    }
+
+    let device = Device::try_new(None).unwrap();
+    let plane = Plane;
+    let geometry = UserGeometry::try_new_packet(&device, plane).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!((hit.t(), hit.normal()), (1.0, (0.0, 0.0, -1.0)));
+    assert!(scene.occluded_1(ray).unwrap());
+    assert!(!scene.occluded_1(ray.with_tfar(0.5)).unwrap());
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::math::{cross, dot, sub};
+
+/// A problem with mesh data that would make Embree produce wrong results or crash, see
+/// [`validate_mesh`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshError {
+    /// A triangle references a vertex that does not exist.
+    IndexOutOfRange {
+        /// The index of the triangle.
+        triangle: usize,
+        /// The out-of-range vertex index.
+        index: u32,
+        /// The number of vertices of the mesh.
+        vertex_count: usize,
+    },
+    /// A vertex has a NaN or infinite coordinate.
+    NonFiniteVertex {
+        /// The index of the vertex.
+        vertex: usize,
+        /// The vertex position.
+        position: (f32, f32, f32),
+    },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::IndexOutOfRange {
+                triangle,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "Triangle {} references vertex {}, but the mesh has {} vertices",
+                triangle, index, vertex_count
+            ),
+            MeshError::NonFiniteVertex { vertex, position } => {
+                write!(f, "Vertex {} is not finite: {:?}", vertex, position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// The result of a successful [`validate_mesh`] pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshValidation {
+    /// The indices of triangles with zero area, e.g. with repeated or collinear vertices.
+    ///
+    /// Embree never reports hits on degenerate triangles, so they are harmless, but often hint
+    /// at problems in the source data.
+    pub degenerate_triangles: Vec<usize>,
+}
+
+/// Checks triangle mesh data before it is uploaded to Embree.
+///
+/// Embree does not validate meshes: out-of-range indices read out of bounds, and non-finite
+/// vertices silently corrupt the acceleration structure. Use this on untrusted data, e.g.
+/// meshes loaded from files, or construct the mesh with
+/// [`TriangleMeshGeometry::try_new_validated`](super::TriangleMeshGeometry::try_new_validated).
+///
+/// # Arguments
+/// * `vertices` - The vertex positions.
+/// * `indices` - The triangle indices.
+///
+/// # Returns
+/// The degenerate triangles of the mesh, or the first error found.
+///
+/// # Example
+/// ```
+/// use embree4_rs::geometry::*;
+///
+/// let vertices = [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)];
+/// let validation = validate_mesh(&vertices, &[(0, 1, 2), (0, 0, 1)]).unwrap();
+/// assert_eq!(validation.degenerate_triangles, [1]);
+///
+/// let error = validate_mesh(&vertices, &[(0, 1, 3)]).unwrap_err();
+/// assert!(matches!(error, MeshError::IndexOutOfRange { index: 3, .. }));
+/// ```
+pub fn validate_mesh(
+    vertices: &[(f32, f32, f32)],
+    indices: &[(u32, u32, u32)],
+) -> Result<MeshValidation, MeshError> {
+    for (vertex, &position) in vertices.iter().enumerate() {
+        if !(position.0.is_finite() && position.1.is_finite() && position.2.is_finite()) {
+            return Err(MeshError::NonFiniteVertex { vertex, position });
+        }
+    }
+
+    let mut validation = MeshValidation::default();
+    for (triangle, &(a, b, c)) in indices.iter().enumerate() {
+        for index in [a, b, c] {
+            if index as usize >= vertices.len() {
+                return Err(MeshError::IndexOutOfRange {
+                    triangle,
+                    index,
+                    vertex_count: vertices.len(),
+                });
+            }
+        }
+
+        let (pa, pb, pc) = (
+            vertices[a as usize],
+            vertices[b as usize],
+            vertices[c as usize],
+        );
+        let normal = cross(sub(pb, pa), sub(pc, pa));
+        if dot(normal, normal) == 0.0 {
+            validation.degenerate_triangles.push(triangle);
+        }
+    }
+    Ok(validation)
+}
+
+#[test]
+fn validate_mesh_errors() {
+    let vertices = [
+        (0.0, 0.0, 0.0),
+        (1.0, 0.0, 0.0),
+        (2.0, 0.0, 0.0),
+        (0.0, 1.0, 0.0),
+    ];
+    let validation = validate_mesh(&vertices, &[(0, 1, 3), (0, 1, 2), (3, 3, 1)]).unwrap();
+    assert_eq!(validation.degenerate_triangles, [1, 2]);
+
+    assert_eq!(
+        validate_mesh(&vertices, &[(0, 1, 3), (0, 4, 1)]),
+        Err(MeshError::IndexOutOfRange {
+            triangle: 1,
+            index: 4,
+            vertex_count: 4
+        })
+    );
+
+    let mut vertices = vertices;
+    vertices[2].1 = f32::NAN;
+    assert!(matches!(
+        validate_mesh(&vertices, &[(0, 1, 3)]),
+        Err(MeshError::NonFiniteVertex { vertex: 2, .. })
+    ));
+}
@@ -0,0 +1,271 @@
+use anyhow::{bail, Result};
+
+use crate::{
+    device_error_or,
+    math::{add, cross, dot, normalize, scale},
+    CommittedScene,
+};
+
+/// The world-space position and normal of a hit, see [`Hit::to_world`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldHit {
+    /// The world-space hit point.
+    pub position: (f32, f32, f32),
+    /// The normalized world-space geometric normal.
+    pub normal: (f32, f32, f32),
+}
+
+/// The maximum instance nesting depth, i.e. the length of the instance ID stack of a hit.
+///
+/// This is fixed when Embree is compiled, through its `EMBREE_MAX_INSTANCE_LEVEL_COUNT` option,
+/// and cannot be configured per device. Embree's default build supports a single level.
+pub const MAX_INSTANCE_LEVEL_COUNT: usize = embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize;
+
+/// A ray hit returned by the query API.
+///
+/// Wraps the raw [`RTCRayHit`](embree4_sys::RTCRayHit) reported by Embree, which is still
+/// accessible through [`Hit::raw`].
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub(crate) ray_hit: embree4_sys::RTCRayHit,
+}
+
+impl Hit {
+    /// Wraps a ray hit, returning `None` if the ray did not hit any geometry.
+    pub(crate) fn from_ray_hit(ray_hit: embree4_sys::RTCRayHit) -> Option<Self> {
+        if ray_hit.hit.geomID != embree4_sys::RTC_INVALID_GEOMETRY_ID {
+            Some(Self { ray_hit })
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if both hits are on the same primitive of the same geometry instance.
+    pub(crate) fn same_primitive(&self, other: &Hit) -> bool {
+        self.ray_hit.hit.geomID == other.ray_hit.hit.geomID
+            && self.ray_hit.hit.primID == other.ray_hit.hit.primID
+            && self.ray_hit.hit.instID == other.ray_hit.hit.instID
+    }
+
+    /// Returns the raw Embree ray hit.
+    pub fn raw(&self) -> &embree4_sys::RTCRayHit {
+        &self.ray_hit
+    }
+
+    /// Returns the hit distance along the ray, in multiples of the ray direction's length.
+    pub fn t(&self) -> f32 {
+        self.ray_hit.ray.tfar
+    }
+
+    /// Returns the hit point, computed as `origin + t * direction`.
+    pub fn hit_point(&self) -> (f32, f32, f32) {
+        let ray = &self.ray_hit.ray;
+        add(
+            (ray.org_x, ray.org_y, ray.org_z),
+            scale((ray.dir_x, ray.dir_y, ray.dir_z), self.t()),
+        )
+    }
+
+    /// Returns the normalized geometric normal of the hit primitive.
+    ///
+    /// For instanced geometry, the normal is in the object space of the instance.
+    pub fn normal(&self) -> (f32, f32, f32) {
+        let hit = &self.ray_hit.hit;
+        normalize((hit.Ng_x, hit.Ng_y, hit.Ng_z))
+    }
+
+    /// Returns the barycentric `(u, v)` coordinates of the hit on the primitive.
+    pub fn uv(&self) -> (f32, f32) {
+        (self.ray_hit.hit.u, self.ray_hit.hit.v)
+    }
+
+    /// Returns the ID of the hit geometry.
+    pub fn geom_id(&self) -> u32 {
+        self.ray_hit.hit.geomID
+    }
+
+    /// Returns the ID of the hit primitive within its geometry.
+    pub fn prim_id(&self) -> u32 {
+        self.ray_hit.hit.primID
+    }
+
+    /// Returns the user-defined ID of the ray that produced the hit, see
+    /// [`Ray::id`](crate::Ray::id).
+    pub fn ray_id(&self) -> u32 {
+        self.ray_hit.ray.id
+    }
+
+    /// Returns the world-space position and normal of the hit.
+    ///
+    /// Embree reports normals of instanced geometry in the object space of the instance. This
+    /// looks up the instance transform at the ray's time in `scene` and transforms the normal
+    /// into world space. For non-instanced hits, the normal is returned as is.
+    ///
+    /// Only a single instance level is supported, as the transforms of nested instances are
+    /// stored in the instanced scenes, which cannot be looked up from `scene`.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene the hit was found in.
+    ///
+    /// # Returns
+    /// A `Result` containing the world-space hit data, or an error if the hit is in a nested
+    /// instance or an error occurred.
+    pub fn to_world(&self, scene: &CommittedScene) -> Result<WorldHit> {
+        let position = self.hit_point();
+        let inst_id = match *self.instance_stack() {
+            [] => {
+                return Ok(WorldHit {
+                    position,
+                    normal: self.normal(),
+                })
+            }
+            [inst_id] => inst_id,
+            ref stack => bail!(
+                "Could not transform hit to world space: nested instances {:?} are not supported",
+                stack
+            ),
+        };
+
+        let mut xfm = [0.0f32; 12];
+        unsafe {
+            embree4_sys::rtcGetGeometryTransformFromScene(
+                scene.scene.handle,
+                inst_id,
+                self.ray_hit.ray.time,
+                embree4_sys::RTCFormat::FLOAT3X4_COLUMN_MAJOR,
+                xfm.as_mut_ptr() as *mut _,
+            );
+        }
+        device_error_or(scene.scene.device, (), "Could not get instance transform")?;
+
+        // Normals transform with the inverse transpose of the linear part, whose columns are
+        // the cross products of the matrix columns divided by the determinant.
+        let c0 = (xfm[0], xfm[1], xfm[2]);
+        let c1 = (xfm[3], xfm[4], xfm[5]);
+        let c2 = (xfm[6], xfm[7], xfm[8]);
+        let det = dot(c0, cross(c1, c2));
+        let hit = &self.ray_hit.hit;
+        let normal = add(
+            add(
+                scale(cross(c1, c2), hit.Ng_x),
+                scale(cross(c2, c0), hit.Ng_y),
+            ),
+            scale(cross(c0, c1), hit.Ng_z),
+        );
+
+        Ok(WorldHit {
+            position,
+            normal: normalize(scale(normal, det.signum())),
+        })
+    }
+
+    /// Returns the ID of the outermost instance the hit geometry belongs to, if it was
+    /// instanced.
+    pub fn inst_id(&self) -> Option<u32> {
+        let inst_id = self.ray_hit.hit.instID[0];
+        (inst_id != embree4_sys::RTC_INVALID_GEOMETRY_ID).then_some(inst_id)
+    }
+
+    /// Returns the IDs of the nested instances the hit geometry belongs to, from the outermost to
+    /// the innermost instance.
+    ///
+    /// The stack is empty if the hit geometry was not instanced. Each ID is the geometry ID of
+    /// the instance within the scene containing it.
+    pub fn instance_stack(&self) -> &[u32] {
+        let inst_id = &self.ray_hit.hit.instID;
+        let depth = inst_id
+            .iter()
+            .position(|&id| id == embree4_sys::RTC_INVALID_GEOMETRY_ID)
+            .unwrap_or(inst_id.len());
+        &inst_id[..depth]
+    }
+}
+
+/// An empty placeholder hit with invalid geometry ID, e.g. to initialize buffers for
+/// [`CommittedScene::intersect_multi`].
+impl Default for Hit {
+    fn default() -> Self {
+        Self {
+            ray_hit: embree4_sys::RTCRayHit {
+                ray: Default::default(),
+                hit: Default::default(),
+            },
+        }
+    }
+}
+
+#[test]
+fn hit_accessors() {
+    let ray_hit = embree4_sys::RTCRayHit {
+        ray: embree4_sys::RTCRay {
+            org_x: 1.0,
+            dir_z: 2.0,
+            tfar: 1.5,
+            id: 5,
+            ..Default::default()
+        },
+        hit: embree4_sys::RTCHit {
+            Ng_y: -4.0,
+            u: 0.25,
+            v: 0.5,
+            primID: 3,
+            geomID: 7,
+            ..Default::default()
+        },
+    };
+    assert!(Hit::from_ray_hit(embree4_sys::RTCRayHit {
+        hit: Default::default(),
+        ..ray_hit
+    })
+    .is_none());
+
+    let hit = Hit::from_ray_hit(ray_hit).unwrap();
+    assert_eq!(hit.t(), 1.5);
+    assert_eq!(hit.hit_point(), (1.0, 0.0, 3.0));
+    assert_eq!(hit.normal(), (0.0, -1.0, 0.0));
+    assert_eq!(hit.uv(), (0.25, 0.5));
+    assert_eq!(hit.geom_id(), 7);
+    assert_eq!(hit.prim_id(), 3);
+    assert_eq!(hit.ray_id(), 5);
+    assert_eq!(hit.inst_id(), None);
+    assert!(hit.instance_stack().is_empty());
+}
+
+#[test]
+fn to_world_without_instance() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 2.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    let world = hit.to_world(&scene).unwrap();
+    assert_eq!(world.position, (0.0, 0.0, 1.0));
+    assert_eq!(world.normal, hit.normal());
+}
+
+#[test]
+fn to_world_rejects_nested_instances() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut inst_id = [embree4_sys::RTC_INVALID_GEOMETRY_ID; MAX_INSTANCE_LEVEL_COUNT];
+    if let Some(levels) = inst_id.get_mut(..2) {
+        levels.copy_from_slice(&[0, 0]);
+        let hit = Hit::from_ray_hit(embree4_sys::RTCRayHit {
+            ray: Default::default(),
+            hit: embree4_sys::RTCHit {
+                geomID: 0,
+                instID: inst_id,
+                ..Default::default()
+            },
+        })
+        .unwrap();
+        assert!(hit.to_world(&scene).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{bail, Result};
+
+use crate::{device_error_or, CommittedScene, Hit};
+
+/// An interpolated value and its first derivatives with respect to the barycentric `u` and `v`
+/// coordinates of the primitive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Derivatives<const N: usize> {
+    pub value: [f32; N],
+    pub dpdu: [f32; N],
+    pub dpdv: [f32; N],
+}
+
+impl<const N: usize> Default for Derivatives<N> {
+    fn default() -> Self {
+        Self {
+            value: [0.0; N],
+            dpdu: [0.0; N],
+            dpdv: [0.0; N],
+        }
+    }
+}
+
+/// The second derivatives of an interpolated value, see
+/// [`CommittedScene::interpolate_second_derivatives`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecondDerivatives<const N: usize> {
+    pub ddpdudu: [f32; N],
+    pub ddpdvdv: [f32; N],
+    pub ddpdudv: [f32; N],
+}
+
+impl<const N: usize> Default for SecondDerivatives<N> {
+    fn default() -> Self {
+        Self {
+            ddpdudu: [0.0; N],
+            ddpdvdv: [0.0; N],
+            ddpdudv: [0.0; N],
+        }
+    }
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Interpolates a vertex attribute at a hit.
+    ///
+    /// Uses the hit's primitive and barycentric coordinates to interpolate the attribute set with
+    /// e.g. [`TriangleMeshGeometry::set_vertex_attribute`](crate::geometry::TriangleMeshGeometry::set_vertex_attribute),
+    /// such as smooth shading normals or texture coordinates. Hits on instanced geometry are not
+    /// supported, as their geometry ID refers to the instanced scene.
+    ///
+    /// # Safety
+    /// Embree does not check the interpolation arguments, and reads out of bounds if any of these
+    /// requirements is violated:
+    /// * `hit` must have been returned by a query of this scene, and the hit geometry must not
+    ///   have been modified since, so its primitive ID is in range.
+    /// * `slot` must hold a vertex attribute buffer of the hit geometry with at least `N`
+    ///   components per vertex.
+    ///
+    /// # Arguments
+    /// * `hit` - The hit to interpolate the attribute at.
+    /// * `slot` - The vertex attribute slot.
+    ///
+    /// # Returns
+    /// A `Result` containing the `N` interpolated attribute components, or an error if an error
+    /// occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, -1.0), (0.0, 0.0, 1.0));
+    /// if let Some(hit) = scene.intersect_1(ray).unwrap() {
+    ///     // The scene only holds geometries with 2 components in attribute slot 0
+    ///     let [u, v] = unsafe { scene.interpolate::<2>(&hit, 0) }.unwrap();
+    /// }
+    /// ```
+    pub unsafe fn interpolate<const N: usize>(&self, hit: &Hit, slot: u32) -> Result<[f32; N]> {
+        let mut value = [0.0; N];
+        self.interpolate_raw(
+            hit,
+            embree4_sys::RTCBufferType::VERTEX_ATTRIBUTE,
+            slot,
+            [&mut value],
+        )?;
+        Ok(value)
+    }
+
+    /// Interpolates a vertex attribute and its first derivatives at a hit.
+    ///
+    /// See [`CommittedScene::interpolate`].
+    ///
+    /// # Safety
+    /// See [`CommittedScene::interpolate`].
+    ///
+    /// # Arguments
+    /// * `hit` - The hit to interpolate the attribute at.
+    /// * `slot` - The vertex attribute slot.
+    ///
+    /// # Returns
+    /// A `Result` containing the interpolated attribute and its derivatives, or an error if an
+    /// error occurred.
+    pub unsafe fn interpolate_derivatives<const N: usize>(
+        &self,
+        hit: &Hit,
+        slot: u32,
+    ) -> Result<Derivatives<N>> {
+        let mut d = Derivatives::default();
+        self.interpolate_raw(
+            hit,
+            embree4_sys::RTCBufferType::VERTEX_ATTRIBUTE,
+            slot,
+            [&mut d.value, &mut d.dpdu, &mut d.dpdv],
+        )?;
+        Ok(d)
+    }
+
+    /// Interpolates a vertex attribute and its first and second derivatives at a hit.
+    ///
+    /// See [`CommittedScene::interpolate`].
+    ///
+    /// # Safety
+    /// See [`CommittedScene::interpolate`].
+    ///
+    /// # Arguments
+    /// * `hit` - The hit to interpolate the attribute at.
+    /// * `slot` - The vertex attribute slot.
+    ///
+    /// # Returns
+    /// A `Result` containing the interpolated attribute and its derivatives, or an error if an
+    /// error occurred.
+    pub unsafe fn interpolate_second_derivatives<const N: usize>(
+        &self,
+        hit: &Hit,
+        slot: u32,
+    ) -> Result<(Derivatives<N>, SecondDerivatives<N>)> {
+        let mut d = Derivatives::default();
+        let mut dd = SecondDerivatives::default();
+        self.interpolate_raw(
+            hit,
+            embree4_sys::RTCBufferType::VERTEX_ATTRIBUTE,
+            slot,
+            [
+                &mut d.value,
+                &mut d.dpdu,
+                &mut d.dpdv,
+                &mut dd.ddpdudu,
+                &mut dd.ddpdvdv,
+                &mut dd.ddpdudv,
+            ],
+        )?;
+        Ok((d, dd))
+    }
+
+    /// Computes the surface position and its derivatives `dP/du` and `dP/dv` at a hit.
+    ///
+    /// The derivatives span the tangent plane of the surface in the parameterization of the
+    /// primitive, as required e.g. for bump mapping and texture filtering. See
+    /// [`CommittedScene::interpolate`].
+    ///
+    /// # Safety
+    /// `hit` must have been returned by a query of this scene, and the hit geometry must not have
+    /// been modified since. The hit geometry must have a vertex buffer, i.e. must not be a user
+    /// geometry.
+    ///
+    /// # Arguments
+    /// * `hit` - The hit to compute the derivatives at.
+    ///
+    /// # Returns
+    /// A `Result` containing the position and its derivatives, or an error if an error occurred.
+    pub unsafe fn surface_derivatives(&self, hit: &Hit) -> Result<Derivatives<3>> {
+        let mut d = Derivatives::default();
+        self.interpolate_raw(
+            hit,
+            embree4_sys::RTCBufferType::VERTEX,
+            0,
+            [&mut d.value, &mut d.dpdu, &mut d.dpdv],
+        )?;
+        Ok(d)
+    }
+
+    /// Interpolates a buffer at a hit into `outputs`, which hold the value followed by the
+    /// optional first and second derivatives in the order of `RTCInterpolateArguments`.
+    ///
+    /// The buffer must hold at least `N` components per vertex, and the primitive ID of `hit` must
+    /// be in range for its geometry.
+    unsafe fn interpolate_raw<const N: usize, const K: usize>(
+        &self,
+        hit: &Hit,
+        buffer_type: embree4_sys::RTCBufferType,
+        slot: u32,
+        mut outputs: [&mut [f32; N]; K],
+    ) -> Result<()> {
+        if hit.inst_id().is_some() {
+            bail!("Could not interpolate vertex attribute: hits on instances are not supported");
+        }
+        if !self
+            .scene
+            .attached
+            .lock()
+            .unwrap()
+            .contains_key(&hit.geom_id())
+        {
+            bail!(
+                "Could not interpolate vertex attribute: geometry {} not found",
+                hit.geom_id()
+            );
+        }
+
+        let geometry =
+            unsafe { embree4_sys::rtcGetGeometryThreadSafe(self.scene.handle, hit.geom_id()) };
+        if geometry.is_null() {
+            bail!(
+                "Could not interpolate vertex attribute: geometry {} not found",
+                hit.geom_id()
+            );
+        }
+
+        let mut ptrs = [std::ptr::null_mut(); 6];
+        for (ptr, output) in ptrs.iter_mut().zip(outputs.iter_mut()) {
+            *ptr = output.as_mut_ptr();
+        }
+
+        let (u, v) = hit.uv();
+        let args = embree4_sys::RTCInterpolateArguments {
+            geometry,
+            primID: hit.prim_id(),
+            u,
+            v,
+            bufferType: buffer_type,
+            bufferSlot: slot,
+            P: ptrs[0],
+            dPdu: ptrs[1],
+            dPdv: ptrs[2],
+            ddPdudu: ptrs[3],
+            ddPdvdv: ptrs[4],
+            ddPdudv: ptrs[5],
+            valueCount: N as u32,
+        };
+
+        embree4_sys::rtcInterpolate(&args);
+        device_error_or(
+            self.scene.device,
+            (),
+            "Could not interpolate vertex attribute",
+        )
+    }
+}
+
+#[test]
+fn interpolate_vertex_attribute() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(0.0, 0.0, 1.0), (1.0, 0.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry
+        .set_vertex_attribute(&device, 1, 2, &[0.0, 0.0, 1.0, 0.0, 0.0, 1.0])
+        .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.25, 0.5, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    let [s, t] = unsafe { scene.interpolate::<2>(&hit, 1) }.unwrap();
+    assert!((s - 0.25).abs() < 1e-5);
+    assert!((t - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn surface_derivatives_triangle() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(0.0, 0.0, 1.0), (2.0, 0.0, 1.0), (0.0, 3.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.5, 0.5, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    let d = unsafe { scene.surface_derivatives(&hit) }.unwrap();
+    assert_eq!(d.dpdu, [2.0, 0.0, 0.0]);
+    assert_eq!(d.dpdv, [0.0, 3.0, 0.0]);
+}
+
+#[test]
+fn interpolate_rejects_unknown_geometry() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let hit = Hit::from_ray_hit(embree4_sys::RTCRayHit {
+        ray: Default::default(),
+        hit: embree4_sys::RTCHit {
+            geomID: 3,
+            ..Default::default()
+        },
+    })
+    .unwrap();
+    assert!(unsafe { scene.surface_derivatives(&hit) }.is_err());
+}
@@ -16,14 +16,49 @@
 //! See the [examples/](https://github.com/psytrx/embree4-rs/tree/main/examples) for a quick start
 //! on how to use this crate.
 
+mod bake;
+mod batch;
+mod buffer;
+mod collide;
+mod context;
 mod device;
+mod filter;
 pub mod geometry;
+mod hit;
+mod interpolate;
+mod math;
+mod multi_hit;
+mod owned;
+mod packet;
+#[cfg(feature = "rayon")]
+mod par;
+mod point_query;
+mod ray;
+mod replica;
 mod scene;
+mod shared;
+mod visibility;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, Result};
 
+pub use bake::*;
+pub use batch::*;
+pub use buffer::*;
+pub use collide::*;
+pub use context::*;
 pub use device::*;
+pub use filter::*;
+pub use hit::*;
+pub use interpolate::*;
+pub use multi_hit::*;
+pub use owned::*;
+pub use packet::*;
+pub use point_query::*;
+pub use ray::*;
+pub use replica::*;
 pub use scene::*;
+pub use shared::*;
+pub use visibility::*;
 
 fn device_error_raw(device: embree4_sys::RTCDevice) -> Option<embree4_sys::RTCError> {
     let err = unsafe { embree4_sys::rtcGetDeviceError(device) };
@@ -35,7 +70,29 @@ fn device_error_raw(device: embree4_sys::RTCDevice) -> Option<embree4_sys::RTCEr
 }
 
 fn device_error_or<T>(device: &Device, ok_value: T, message: &str) -> Result<T> {
-    device_error_raw(device.handle)
-        .map(|error| bail!("{}: {:?}", message, error))
-        .unwrap_or(Ok(ok_value))
+    match device_error_raw(device.handle) {
+        Some(error) => Err(describe_device_error(device, error, message)),
+        None => Ok(ok_value),
+    }
+}
+
+/// Builds an error for a failed call which returned a null handle, from the pending device
+/// error.
+fn device_error(device: &Device, message: &str) -> anyhow::Error {
+    match device_error_raw(device.handle) {
+        Some(error) => describe_device_error(device, error, message),
+        None => anyhow!("{}: unknown error", message),
+    }
+}
+
+/// Formats a device error, including the message Embree reported for it, if any.
+fn describe_device_error(
+    device: &Device,
+    error: embree4_sys::RTCError,
+    message: &str,
+) -> anyhow::Error {
+    match device.take_error_message() {
+        Some(details) => anyhow!("{}: {:?}: {}", message, error, details),
+        None => anyhow!("{}: {:?}", message, error),
+    }
 }
@@ -0,0 +1,154 @@
+//! Minimal vector math on `(f32, f32, f32)` tuples, the crate's point and direction type.
+
+pub(crate) type Vec3 = (f32, f32, f32);
+
+/// The relative offset applied along the normal to ray origins on surfaces to avoid
+/// self-intersection.
+pub(crate) const SURFACE_EPSILON: f32 = 1e-4;
+
+#[inline]
+pub(crate) fn add(a: Vec3, b: Vec3) -> Vec3 {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+#[inline]
+pub(crate) fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+#[inline]
+pub(crate) fn scale(a: Vec3, s: f32) -> Vec3 {
+    (a.0 * s, a.1 * s, a.2 * s)
+}
+
+#[inline]
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+#[inline]
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+#[inline]
+pub(crate) fn length(a: Vec3) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Returns the normalized vector, or the zero vector if `a` has zero length.
+#[inline]
+pub(crate) fn normalize(a: Vec3) -> Vec3 {
+    let len = length(a);
+    if len > 0.0 {
+        scale(a, 1.0 / len)
+    } else {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+/// Translates a double-precision point into a local frame around `origin`, narrowing the
+/// result to `f32` only after the subtraction.
+#[inline]
+pub(crate) fn to_local(point: (f64, f64, f64), origin: (f64, f64, f64)) -> Vec3 {
+    (
+        (point.0 - origin.0) as f32,
+        (point.1 - origin.1) as f32,
+        (point.2 - origin.2) as f32,
+    )
+}
+
+/// Returns the largest absolute component of `a`, but at least `1`.
+///
+/// Used to scale epsilons with the floating point precision at a point.
+#[inline]
+pub(crate) fn magnitude(a: Vec3) -> f32 {
+    a.0.abs().max(a.1.abs()).max(a.2.abs()).max(1.0)
+}
+
+/// Offsets a surface point along its unit normal, scaled by the point's magnitude.
+pub(crate) fn offset_origin(point: Vec3, normal: Vec3) -> Vec3 {
+    add(point, scale(normal, SURFACE_EPSILON * magnitude(point)))
+}
+
+/// Builds an orthonormal basis `(tangent, bitangent)` around the unit vector `n`.
+///
+/// See Duff et al., "Building an Orthonormal Basis, Revisited".
+pub(crate) fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let sign = 1.0_f32.copysign(n.2);
+    let a = -1.0 / (sign + n.2);
+    let b = n.0 * n.1 * a;
+    (
+        (1.0 + sign * n.0 * n.0 * a, sign * b, -sign * n.0),
+        (b, sign + n.1 * n.1 * a, -n.1),
+    )
+}
+
+/// Returns the `i`-th of `n` points of the Hammersley sequence in `[0, 1)^2`.
+pub(crate) fn hammersley(i: u32, n: u32) -> (f32, f32) {
+    let u = (i as f32 + 0.5) / n as f32;
+    let v = i.reverse_bits() as f32 * (1.0 / 4_294_967_296.0);
+    (u, v)
+}
+
+/// Maps a point in `[0, 1)^2` to a cosine-weighted direction on the hemisphere around `n`.
+pub(crate) fn cosine_hemisphere(n: Vec3, sample: (f32, f32)) -> Vec3 {
+    let r = sample.0.sqrt();
+    let phi = 2.0 * std::f32::consts::PI * sample.1;
+    let (t, b) = orthonormal_basis(n);
+    let z = (1.0 - sample.0).max(0.0).sqrt();
+    add(
+        add(scale(t, r * phi.cos()), scale(b, r * phi.sin())),
+        scale(n, z),
+    )
+}
+
+/// Returns the point on the triangle `(a, b, c)` closest to `p`.
+///
+/// See Ericson, "Real-Time Collision Detection", section 5.1.5.
+pub(crate) fn closest_point_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let ab = sub(b, a);
+    let ac = sub(c, a);
+    let ap = sub(p, a);
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = sub(p, b);
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return add(a, scale(ab, d1 / (d1 - d3)));
+    }
+
+    let cp = sub(p, c);
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return add(a, scale(ac, d2 / (d2 - d6)));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return add(b, scale(sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6))));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    add(a, add(scale(ab, vb * denom), scale(ac, vc * denom)))
+}
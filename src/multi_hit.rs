@@ -0,0 +1,353 @@
+use anyhow::Result;
+
+use crate::{CommittedScene, FilterDecision, Hit};
+
+/// An iterator over the hits along a ray, in front-to-back order.
+///
+/// Created by [`CommittedScene::hits_along`]. Each step re-casts the ray starting just past the
+/// previous hit, so iteration can be stopped early at no extra cost.
+pub struct HitIter<'s> {
+    scene: &'s CommittedScene<'s>,
+    ray: embree4_sys::RTCRay,
+    done: bool,
+}
+
+impl<'s> Iterator for HitIter<'s> {
+    type Item = Result<Hit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.scene.intersect_1(self.ray) {
+            Ok(Some(hit)) => {
+                // Advance by one ulp, so the previous hit's primitive is not reported again. Hit
+                // distances are positive, so the next larger float has the next bit pattern.
+                self.ray.tnear = f32::from_bits(hit.t().to_bits() + 1);
+                self.done = self.ray.tnear > self.ray.tfar;
+                Some(Ok(hit))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Returns an iterator over the hits along a ray, in front-to-back order.
+    ///
+    /// Unlike [`CommittedScene::intersect_all`], this does not require any scene flags, and
+    /// traversal stops as soon as the iterator is dropped. Hits with exactly the same distance
+    /// as a previous hit, e.g. on coplanar surfaces, are skipped.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to trace. Only hits between `tnear` and `tfar` are reported.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::RTCRay;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// for hit in scene.hits_along(ray) {
+    ///     let hit = hit.unwrap();
+    ///     if hit.geom_id() == 0 {
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    pub fn hits_along(&self, ray: impl Into<embree4_sys::RTCRay>) -> HitIter<'_> {
+        HitIter {
+            scene: self,
+            ray: ray.into(),
+            done: false,
+        }
+    }
+
+    /// Finds all hits along a ray, sorted by distance.
+    ///
+    /// Traversal continues past every hit, so the cost grows with the depth complexity of the
+    /// scene. The scene must have been created with
+    /// [`SceneFlags::filter_function_in_arguments`](crate::SceneFlags::filter_function_in_arguments).
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to trace. Only hits between `tnear` and `tfar` are reported.
+    /// * `max_hits` - The maximum number of hits to return. The nearest hits are kept.
+    ///
+    /// # Returns
+    /// A `Result` containing the hits in front-to-back order, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::RTCRay;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let options = SceneOptions {
+    ///     flags: SceneFlags {
+    ///         filter_function_in_arguments: true,
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    /// let scene = Scene::try_new(&device, options).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// for hit in scene.intersect_all(ray, 16).unwrap() {
+    ///     println!("hit geometry {} at t = {}", hit.geom_id(), hit.t());
+    /// }
+    /// ```
+    pub fn intersect_all(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        max_hits: usize,
+    ) -> Result<Vec<Hit>> {
+        let mut hits = Vec::new();
+        self.intersect_1_with_filter(ray.into(), |hit| {
+            hits.push(*hit);
+            FilterDecision::Reject
+        })?;
+
+        // Embree reports hits in traversal order, and may report a primitive more than once
+        hits.sort_by(|a, b| a.t().total_cmp(&b.t()));
+        hits.dedup_by(|a, b| a.same_primitive(b));
+        hits.truncate(max_hits);
+        Ok(hits)
+    }
+
+    /// Finds the `k` nearest hits along a ray, sorted by distance.
+    ///
+    /// Unlike [`CommittedScene::intersect_all`], candidate hits are kept in a buffer of `k`
+    /// entries during traversal, so memory use is bounded regardless of the depth complexity of
+    /// the scene. The scene must have been created with
+    /// [`SceneFlags::filter_function_in_arguments`](crate::SceneFlags::filter_function_in_arguments).
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to trace. Only hits between `tnear` and `tfar` are reported.
+    /// * `k` - The maximum number of hits to return.
+    ///
+    /// # Returns
+    /// A `Result` containing up to `k` hits in front-to-back order, or an error if an error
+    /// occurred.
+    pub fn intersect_n_nearest(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        k: usize,
+    ) -> Result<Vec<Hit>> {
+        let mut hits = vec![Hit::default(); k];
+        let count = self.intersect_multi(ray, &mut hits)?;
+        hits.truncate(count);
+        Ok(hits)
+    }
+
+    /// Fills a caller-provided buffer with the nearest hits along a ray, sorted by distance.
+    ///
+    /// Like [`CommittedScene::intersect_n_nearest`], but without any heap allocation, for
+    /// real-time use. The scene must have been created with
+    /// [`SceneFlags::filter_function_in_arguments`](crate::SceneFlags::filter_function_in_arguments).
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to trace. Only hits between `tnear` and `tfar` are reported.
+    /// * `buffer` - The output buffer, receiving up to `buffer.len()` hits in front-to-back
+    ///   order. Entries past the returned count are unspecified.
+    ///
+    /// # Returns
+    /// A `Result` containing the number of hits written to `buffer`, or an error if an error
+    /// occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let options = SceneOptions {
+    ///     flags: SceneFlags {
+    ///         filter_function_in_arguments: true,
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    /// let scene = Scene::try_new(&device, options).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let mut hits = [Hit::default(); 8];
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// let count = scene.intersect_multi(ray, &mut hits).unwrap();
+    /// for hit in &hits[..count] {
+    ///     println!("hit geometry {} at t = {}", hit.geom_id(), hit.t());
+    /// }
+    /// ```
+    pub fn intersect_multi(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        buffer: &mut [Hit],
+    ) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        self.intersect_1_with_filter(ray.into(), |hit| {
+            insert_nearest(buffer, &mut count, *hit);
+            FilterDecision::Reject
+        })?;
+        Ok(count)
+    }
+}
+
+/// Inserts `hit` into the sorted prefix `buffer[..*count]`, dropping the farthest hit if the
+/// buffer is full. Primitives that were already recorded are ignored, as Embree may report a
+/// primitive more than once.
+fn insert_nearest(buffer: &mut [Hit], count: &mut usize, hit: Hit) {
+    let recorded = &buffer[..*count];
+    if recorded.iter().any(|other| other.same_primitive(&hit)) {
+        return;
+    }
+
+    let pos = recorded.partition_point(|other| other.t() <= hit.t());
+    if pos == buffer.len() {
+        return;
+    }
+
+    *count = (*count + 1).min(buffer.len());
+    buffer[pos..*count].rotate_right(1);
+    buffer[pos] = hit;
+}
+
+#[cfg(test)]
+fn layered_scene(
+    device: &crate::Device,
+) -> (crate::Scene<'_>, crate::geometry::TriangleMeshGeometry) {
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 2.0),
+        (1.0, -1.0, 2.0),
+        (0.0, 1.0, 2.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(device, &vertices, &[(3, 4, 5), (0, 1, 2)])
+            .unwrap();
+    let options = crate::SceneOptions {
+        flags: crate::SceneFlags {
+            filter_function_in_arguments: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let scene = crate::Scene::try_new(device, options).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    (scene, geometry)
+}
+
+#[test]
+fn intersect_all_sorted() {
+    let device = crate::Device::try_new(None).unwrap();
+    let (scene, _geometry) = layered_scene(&device);
+    let scene = scene.commit().unwrap();
+
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let hits = scene.intersect_all(ray, 8).unwrap();
+    let distances: Vec<_> = hits.iter().map(|hit| hit.t()).collect();
+    assert_eq!(distances, [1.0, 2.0]);
+
+    let hits = scene.intersect_all(ray, 1).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].prim_id(), 1);
+}
+
+#[test]
+fn hits_along_front_to_back() {
+    let device = crate::Device::try_new(None).unwrap();
+    let (scene, _geometry) = layered_scene(&device);
+    let scene = scene.commit().unwrap();
+
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let distances: Vec<_> = scene.hits_along(ray).map(|hit| hit.unwrap().t()).collect();
+    assert_eq!(distances, [1.0, 2.0]);
+}
+
+#[test]
+fn intersect_n_nearest_bounded() {
+    let device = crate::Device::try_new(None).unwrap();
+    let (scene, _geometry) = layered_scene(&device);
+    let scene = scene.commit().unwrap();
+
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    assert!(scene.intersect_n_nearest(ray, 0).unwrap().is_empty());
+
+    let hits = scene.intersect_n_nearest(ray, 1).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].t(), 1.0);
+
+    let hits = scene.intersect_n_nearest(ray, 4).unwrap();
+    let distances: Vec<_> = hits.iter().map(|hit| hit.t()).collect();
+    assert_eq!(distances, [1.0, 2.0]);
+}
+
+#[test]
+fn intersect_multi_fills_buffer() {
+    let device = crate::Device::try_new(None).unwrap();
+    let (scene, _geometry) = layered_scene(&device);
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let mut hits = [Hit::default(); 3];
+    assert_eq!(scene.intersect_multi(ray, &mut hits).unwrap(), 2);
+    assert_eq!((hits[0].t(), hits[1].t()), (1.0, 2.0));
+
+    assert_eq!(scene.intersect_multi(ray, &mut hits[..1]).unwrap(), 1);
+    assert_eq!(hits[0].t(), 1.0);
+}
+
+#[test]
+fn multi_hit_collects_user_geometry() {
+    let device = crate::Device::try_new(None).unwrap();
+    let (scene, _geometry) = layered_scene(&device);
+    let plane =
+        crate::geometry::PlaneGeometry::try_new(&device, (0.0, 0.0, 3.0), (0.0, 0.0, -1.0), None)
+            .unwrap();
+    let plane_id = scene.attach_geometry(&plane).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hits = scene.intersect_all(ray, 8).unwrap();
+    let distances: Vec<_> = hits.iter().map(|hit| hit.t()).collect();
+    assert_eq!(distances, [1.0, 2.0, 3.0]);
+    assert_eq!(hits[2].geom_id(), plane_id);
+
+    let hits = scene.intersect_n_nearest(ray, 3).unwrap();
+    assert_eq!(hits.last().unwrap().geom_id(), plane_id);
+
+    let mut hits = [Hit::default(); 4];
+    assert_eq!(scene.intersect_multi(ray, &mut hits).unwrap(), 3);
+    assert_eq!((hits[2].t(), hits[2].geom_id()), (3.0, plane_id));
+}
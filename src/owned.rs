@@ -0,0 +1,203 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::Result;
+
+use crate::{geometry::Geometry, CommittedScene, Device, HitPolicy, Scene, SceneOptions};
+
+/// A [`Scene`] which keeps its device alive through an [`Arc`], instead of borrowing it.
+///
+/// Unlike `Scene<'a>`, an `OwnedScene` has no lifetime parameter, so it can be stored in
+/// long-lived structs, e.g. the scene cache of a render server, and sent to other threads along
+/// with its committed form [`OwnedCommittedScene`]. All methods of [`Scene`] that
+/// take `&self` are available through [`OwnedScene::scene`]. Methods that take `&mut self` are
+/// forwarded explicitly, as handing out `&mut Scene` would allow swapping scenes between devices.
+///
+/// # Example
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use embree4_rs::{*, geometry::*};
+///
+/// struct Renderer {
+///     device: Arc<Device>,
+///     scene: OwnedCommittedScene,
+/// }
+///
+/// let device = Arc::new(Device::try_new(None).unwrap());
+/// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+/// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+///
+/// let mut scene = OwnedScene::try_new(device.clone(), SceneOptions::default()).unwrap();
+/// scene.attach_geometry_owned(geometry).unwrap();
+/// let renderer = Renderer {
+///     device,
+///     scene: scene.commit_owned().unwrap(),
+/// };
+///
+/// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+/// let hit = renderer.scene.committed().intersect_1(ray).unwrap();
+/// ```
+pub struct OwnedScene {
+    // Declared before `device`, so the scene is released before the device
+    scene: Scene<'static>,
+    device: Arc<Device>,
+}
+
+impl OwnedScene {
+    /// Constructs a new `OwnedScene` on a shared device.
+    ///
+    /// # Arguments
+    /// * `device` - The device, kept alive as long as the scene.
+    /// * `options` - The options for creating the scene.
+    ///
+    /// # Returns
+    /// A `Result` containing the `OwnedScene` instance if successful, or an error if an error
+    /// occurred.
+    pub fn try_new(device: Arc<Device>, options: SceneOptions) -> Result<Self> {
+        // The device lives on the heap as long as `self.device`, which outlives `self.scene`
+        let device_ref: &'static Device = unsafe { &*Arc::as_ptr(&device) };
+        let scene = Scene::try_new(device_ref, options)?;
+        Ok(Self { scene, device })
+    }
+
+    /// Returns the device of the scene.
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    /// Returns the scene, borrowing its device from this `OwnedScene`.
+    ///
+    /// Scenes derived from it, e.g. with [`Scene::duplicate`], cannot outlive the `OwnedScene`:
+    ///
+    /// ```compile_fail
+    /// use std::sync::Arc;
+    ///
+    /// use embree4_rs::*;
+    ///
+    /// let device = Arc::new(Device::try_new(None).unwrap());
+    /// let owned = OwnedScene::try_new(device, SceneOptions::default()).unwrap();
+    /// let escaped: Scene<'static> = owned.scene().duplicate().unwrap();
+    /// drop(owned);
+    /// ```
+    pub fn scene(&self) -> &Scene<'_> {
+        &self.scene
+    }
+
+    /// See [`Scene::attach_geometry_with_payload`].
+    pub fn attach_geometry_with_payload<T: Any + Send + Sync>(
+        &mut self,
+        geometry: &impl Geometry,
+        payload: T,
+    ) -> Result<u32> {
+        self.scene.attach_geometry_with_payload(geometry, payload)
+    }
+
+    /// See [`Scene::attach_geometry_owned`].
+    pub fn attach_geometry_owned<G>(&mut self, geometry: G) -> Result<u32>
+    where
+        G: Geometry + Send + Sync + 'static,
+    {
+        self.scene.attach_geometry_owned(geometry)
+    }
+
+    /// See [`Scene::owned_geometry_mut`].
+    pub fn owned_geometry_mut<G: Geometry + 'static>(&mut self, geom_id: u32) -> Option<&mut G> {
+        self.scene.owned_geometry_mut(geom_id)
+    }
+
+    /// See [`Scene::detach_geometry`].
+    pub fn detach_geometry(&mut self, geom_id: u32) -> Result<()> {
+        self.scene.detach_geometry(geom_id)
+    }
+
+    /// See [`Scene::set_hit_policy`].
+    pub fn set_hit_policy(&mut self, policy: impl HitPolicy + 'static) -> Result<()> {
+        self.scene.set_hit_policy(policy)
+    }
+
+    /// See [`Scene::set_progress_monitor`].
+    pub fn set_progress_monitor(&mut self, callback: impl Fn(f64) -> bool + Send + Sync + 'static) {
+        self.scene.set_progress_monitor(callback)
+    }
+
+    /// Commits the scene, and keeps it committed without borrowing it.
+    ///
+    /// Use [`OwnedCommittedScene::into_scene`] to modify the scene again.
+    ///
+    /// # Returns
+    /// A `Result` containing the committed scene if successful, or an error if an error
+    /// occurred.
+    pub fn commit_owned(self) -> Result<OwnedCommittedScene> {
+        self.scene.commit()?;
+        Ok(OwnedCommittedScene { scene: self })
+    }
+}
+
+/// A committed [`OwnedScene`], see [`OwnedScene::commit_owned`].
+pub struct OwnedCommittedScene {
+    scene: OwnedScene,
+}
+
+impl OwnedCommittedScene {
+    /// Returns the committed scene for queries.
+    pub fn committed(&self) -> CommittedScene<'_> {
+        CommittedScene {
+            scene: &self.scene.scene,
+        }
+    }
+
+    /// Returns the scene, e.g. to modify and commit it again.
+    pub fn into_scene(self) -> OwnedScene {
+        self.scene
+    }
+}
+
+#[test]
+fn owned_committed_scene_moves_to_thread() {
+    let device = Arc::new(Device::try_new(None).unwrap());
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let mut scene = OwnedScene::try_new(device, Default::default()).unwrap();
+    scene.attach_geometry_owned(geometry).unwrap();
+    let committed = Arc::new(scene.commit_owned().unwrap());
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let shared = committed.clone();
+    let t = std::thread::spawn(move || shared.committed().intersect_1(ray).unwrap().unwrap().t())
+        .join()
+        .unwrap();
+    assert_eq!(t, 1.0);
+
+    let scene = std::thread::spawn(move || Arc::into_inner(committed).unwrap().into_scene())
+        .join()
+        .unwrap();
+    assert!(scene.commit_owned().is_ok());
+}
+
+#[test]
+fn owned_scene_without_borrow() {
+    fn build(device: Arc<Device>) -> OwnedCommittedScene {
+        let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+        let geometry =
+            crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)])
+                .unwrap();
+        let mut scene = OwnedScene::try_new(device, Default::default()).unwrap();
+        scene.attach_geometry_owned(geometry).unwrap();
+        scene.commit_owned().unwrap()
+    }
+
+    let device = Arc::new(Device::try_new(None).unwrap());
+    let committed = build(device.clone());
+    drop(device);
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = committed.committed().intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.t(), 1.0);
+
+    let mut scene = committed.into_scene();
+    assert!(scene.scene().geometry(hit.geom_id()).is_ok());
+    scene.detach_geometry(hit.geom_id()).unwrap();
+    let committed = scene.commit_owned().unwrap();
+    assert!(committed.committed().intersect_1(ray).unwrap().is_none());
+}
@@ -0,0 +1,796 @@
+use std::mem::MaybeUninit;
+
+use anyhow::Result;
+
+use crate::{device_error_or, ray::validate_ray, CommittedScene, Hit, QueryOptions};
+
+/// A validity mask for a ray packet of up to 16 lanes, aligned as required by Embree.
+/// Active lanes are `-1`, inactive lanes are `0`.
+#[repr(C, align(64))]
+struct ValidMask<const N: usize>([i32; N]);
+
+impl<const N: usize> From<[bool; N]> for ValidMask<N> {
+    fn from(valid: [bool; N]) -> Self {
+        Self(valid.map(|valid| if valid { -1 } else { 0 }))
+    }
+}
+
+/// The Embree kernels and raw layouts of a ray packet of width `N`.
+trait PacketKernels<const N: usize>: Sized {
+    /// The raw SoA ray/hit packet, e.g. `RTCRayHit4`.
+    type RawHit;
+
+    /// The packet intersection function, e.g. `rtcIntersect4`.
+    const INTERSECT: unsafe extern "C" fn(
+        *const i32,
+        embree4_sys::RTCScene,
+        *mut Self::RawHit,
+        *mut embree4_sys::RTCIntersectArguments,
+    );
+
+    /// Traces the packet for occlusion, e.g. with `rtcOccluded4`.
+    unsafe fn occluded(
+        &mut self,
+        valid: *const i32,
+        scene: embree4_sys::RTCScene,
+        args: *mut embree4_sys::RTCOccludedArguments,
+    );
+
+    /// Returns the `tfar` of a single lane.
+    fn tfar(&self, i: usize) -> f32;
+
+    /// Returns the ray of a single lane.
+    fn ray(&self, i: usize) -> embree4_sys::RTCRay;
+
+    /// Constructs a ray/hit packet from the rays, with all hits marked invalid.
+    fn into_ray_hit(self) -> Self::RawHit;
+
+    /// Extracts the ray and hit of a single lane.
+    fn ray_hit(raw: &Self::RawHit, i: usize) -> embree4_sys::RTCRayHit;
+}
+
+macro_rules! ray_packet {
+    (
+        $(#[$attr:meta])*
+        $name:ident, $raw:ty, $raw_hit:ty, $n:literal, $intersect:path, $occluded:path
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        #[repr(transparent)]
+        pub struct $name {
+            raw: $raw,
+        }
+
+        impl $name {
+            /// Constructs a packet with every lane set to [`RTCRay::default`](embree4_sys::RTCRay).
+            pub fn new() -> Self {
+                Self::from([embree4_sys::RTCRay::default(); $n])
+            }
+
+            /// Returns the number of lanes.
+            pub const fn len(&self) -> usize {
+                $n
+            }
+
+            /// Always returns `false`, packets have a fixed number of lanes.
+            pub const fn is_empty(&self) -> bool {
+                false
+            }
+
+            /// Sets the ray of a single lane.
+            ///
+            /// # Panics
+            /// Panics if `i` is out of bounds.
+            pub fn set(&mut self, i: usize, ray: impl Into<embree4_sys::RTCRay>) {
+                let ray = ray.into();
+                let raw = &mut self.raw;
+                raw.org_x[i] = ray.org_x;
+                raw.org_y[i] = ray.org_y;
+                raw.org_z[i] = ray.org_z;
+                raw.tnear[i] = ray.tnear;
+                raw.dir_x[i] = ray.dir_x;
+                raw.dir_y[i] = ray.dir_y;
+                raw.dir_z[i] = ray.dir_z;
+                raw.time[i] = ray.time;
+                raw.tfar[i] = ray.tfar;
+                raw.mask[i] = ray.mask;
+                raw.id[i] = ray.id;
+                raw.flags[i] = ray.flags;
+            }
+
+            /// Returns the ray of a single lane.
+            ///
+            /// # Panics
+            /// Panics if `i` is out of bounds.
+            pub fn get(&self, i: usize) -> embree4_sys::RTCRay {
+                let raw = &self.raw;
+                embree4_sys::RTCRay {
+                    org_x: raw.org_x[i],
+                    org_y: raw.org_y[i],
+                    org_z: raw.org_z[i],
+                    tnear: raw.tnear[i],
+                    dir_x: raw.dir_x[i],
+                    dir_y: raw.dir_y[i],
+                    dir_z: raw.dir_z[i],
+                    time: raw.time[i],
+                    tfar: raw.tfar[i],
+                    mask: raw.mask[i],
+                    id: raw.id[i],
+                    flags: raw.flags[i],
+                }
+            }
+
+            /// Returns the raw Embree packet.
+            pub fn raw(&self) -> &$raw {
+                &self.raw
+            }
+
+            /// Returns the raw Embree packet mutably, e.g. to fill it directly from SoA data.
+            pub fn raw_mut(&mut self) -> &mut $raw {
+                &mut self.raw
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<R: Into<embree4_sys::RTCRay>> From<[R; $n]> for $name {
+            fn from(rays: [R; $n]) -> Self {
+                // SAFETY: ray packets only contain plain floats and integers.
+                let mut packet = Self {
+                    raw: unsafe { MaybeUninit::zeroed().assume_init() },
+                };
+                for (i, ray) in rays.into_iter().enumerate() {
+                    packet.set(i, ray);
+                }
+                packet
+            }
+        }
+
+        impl PacketKernels<$n> for $name {
+            type RawHit = $raw_hit;
+
+            const INTERSECT: unsafe extern "C" fn(
+                *const i32,
+                embree4_sys::RTCScene,
+                *mut Self::RawHit,
+                *mut embree4_sys::RTCIntersectArguments,
+            ) = $intersect;
+
+            unsafe fn occluded(
+                &mut self,
+                valid: *const i32,
+                scene: embree4_sys::RTCScene,
+                args: *mut embree4_sys::RTCOccludedArguments,
+            ) {
+                $occluded(valid, scene, &mut self.raw, args);
+            }
+
+            fn tfar(&self, i: usize) -> f32 {
+                self.raw.tfar[i]
+            }
+
+            fn ray(&self, i: usize) -> embree4_sys::RTCRay {
+                self.get(i)
+            }
+
+            fn into_ray_hit(self) -> Self::RawHit {
+                // SAFETY: ray hit packets only contain plain floats and integers.
+                let mut packet = Self::RawHit {
+                    ray: self.raw,
+                    hit: unsafe { MaybeUninit::zeroed().assume_init() },
+                };
+                packet.hit.geomID = [embree4_sys::RTC_INVALID_GEOMETRY_ID; $n];
+                packet.hit.instID = [[embree4_sys::RTC_INVALID_GEOMETRY_ID; $n];
+                    embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize];
+                packet
+            }
+
+            fn ray_hit(raw: &Self::RawHit, i: usize) -> embree4_sys::RTCRayHit {
+                embree4_sys::RTCRayHit {
+                    ray: Self { raw: raw.ray }.get(i),
+                    hit: embree4_sys::RTCHit {
+                        Ng_x: raw.hit.Ng_x[i],
+                        Ng_y: raw.hit.Ng_y[i],
+                        Ng_z: raw.hit.Ng_z[i],
+                        u: raw.hit.u[i],
+                        v: raw.hit.v[i],
+                        primID: raw.hit.primID[i],
+                        geomID: raw.hit.geomID[i],
+                        instID: std::array::from_fn(|level| raw.hit.instID[level][i]),
+                    },
+                }
+            }
+        }
+    };
+}
+
+ray_packet!(
+    /// A packet of 4 rays in SoA layout, see [`CommittedScene::intersect_4`].
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{Ray, RayPacket4};
+    ///
+    /// let mut packet = RayPacket4::new();
+    /// for i in 0..packet.len() {
+    ///     packet.set(i, Ray::new((i as f32, 0.0, 0.0), (0.0, 0.0, 1.0)));
+    /// }
+    /// assert_eq!(packet.get(3).org_x, 3.0);
+    /// ```
+    RayPacket4,
+    embree4_sys::RTCRay4,
+    embree4_sys::RTCRayHit4,
+    4,
+    embree4_sys::rtcIntersect4,
+    embree4_sys::rtcOccluded4
+);
+ray_packet!(
+    /// A packet of 8 rays in SoA layout, see [`CommittedScene::intersect_8`].
+    RayPacket8,
+    embree4_sys::RTCRay8,
+    embree4_sys::RTCRayHit8,
+    8,
+    embree4_sys::rtcIntersect8,
+    embree4_sys::rtcOccluded8
+);
+ray_packet!(
+    /// A packet of 16 rays in SoA layout, see [`CommittedScene::intersect_16`].
+    RayPacket16,
+    embree4_sys::RTCRay16,
+    embree4_sys::RTCRayHit16,
+    16,
+    embree4_sys::rtcIntersect16,
+    embree4_sys::rtcOccluded16
+);
+
+impl<'a> CommittedScene<'a> {
+    /// Intersects a packet of 4 rays with the scene.
+    ///
+    /// Tracing rays in packets amortizes the per-call overhead and lets Embree use SIMD
+    /// traversal kernels on SSE-class hardware.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect, either a packet or an array of rays.
+    /// * `valid` - The validity mask. Only rays whose lane is `true` are traced.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each lane, or an error if an error occurred.
+    /// Inactive lanes are always `None`.
+    pub fn intersect_4(
+        &self,
+        rays: impl Into<RayPacket4>,
+        valid: [bool; 4],
+    ) -> Result<[Option<Hit>; 4]> {
+        self.intersect_packet(rays.into(), valid, &Default::default())
+    }
+
+    /// Intersects a packet of 8 rays with the scene.
+    ///
+    /// This is the native packet width on AVX2 hardware. See [`CommittedScene::intersect_4`].
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect, either a packet or an array of rays.
+    /// * `valid` - The validity mask. Only rays whose lane is `true` are traced.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each lane, or an error if an error occurred.
+    /// Inactive lanes are always `None`.
+    pub fn intersect_8(
+        &self,
+        rays: impl Into<RayPacket8>,
+        valid: [bool; 8],
+    ) -> Result<[Option<Hit>; 8]> {
+        self.intersect_packet(rays.into(), valid, &Default::default())
+    }
+
+    /// Intersects a packet of 16 rays with the scene.
+    ///
+    /// This is the native packet width on AVX-512 hardware. See [`CommittedScene::intersect_4`].
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect, either a packet or an array of rays.
+    /// * `valid` - The validity mask. Only rays whose lane is `true` are traced.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each lane, or an error if an error occurred.
+    /// Inactive lanes are always `None`.
+    pub fn intersect_16(
+        &self,
+        rays: impl Into<RayPacket16>,
+        valid: [bool; 16],
+    ) -> Result<[Option<Hit>; 16]> {
+        self.intersect_packet(rays.into(), valid, &Default::default())
+    }
+
+    /// Tests a packet of 4 rays for occlusion.
+    ///
+    /// See [`CommittedScene::occluded_1`].
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to test, either a packet or an array of rays.
+    /// * `valid` - The validity mask. Only rays whose lane is `true` are traced.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` for each occluded lane, or an error if an error occurred.
+    /// Inactive lanes are always `false`.
+    pub fn occluded_4(&self, rays: impl Into<RayPacket4>, valid: [bool; 4]) -> Result<[bool; 4]> {
+        self.occluded_packet(rays.into(), valid)
+    }
+
+    /// Tests a packet of 8 rays for occlusion.
+    ///
+    /// See [`CommittedScene::occluded_4`].
+    pub fn occluded_8(&self, rays: impl Into<RayPacket8>, valid: [bool; 8]) -> Result<[bool; 8]> {
+        self.occluded_packet(rays.into(), valid)
+    }
+
+    /// Tests a packet of 16 rays for occlusion.
+    ///
+    /// See [`CommittedScene::occluded_4`].
+    pub fn occluded_16(
+        &self,
+        rays: impl Into<RayPacket16>,
+        valid: [bool; 16],
+    ) -> Result<[bool; 16]> {
+        self.occluded_packet(rays.into(), valid)
+    }
+
+    /// Intersects any number of rays with the scene, using the widest packet kernel natively
+    /// supported by the device.
+    ///
+    /// Rays are traced in packets of [`Device::native_packet_width`](crate::Device::native_packet_width)
+    /// lanes, with the last packet partially masked. Falls back to [`CommittedScene::intersect_1`]
+    /// if the device does not support packet kernels.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each ray, in order, or an error if an error occurred.
+    pub fn intersect_auto(&self, rays: &[embree4_sys::RTCRay]) -> Result<Vec<Option<Hit>>> {
+        self.intersect_auto_with_options(rays, &Default::default())
+    }
+
+    /// Intersects any number of rays with the scene in packets, using the given query options.
+    ///
+    /// See [`CommittedScene::intersect_auto`].
+    pub fn intersect_auto_with_options(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        options: &QueryOptions,
+    ) -> Result<Vec<Option<Hit>>> {
+        match self.scene.device.native_packet_width() {
+            16 => self.intersect_chunked::<RayPacket16, 16>(rays, options),
+            8 => self.intersect_chunked::<RayPacket8, 8>(rays, options),
+            4 => self.intersect_chunked::<RayPacket4, 4>(rays, options),
+            _ => rays
+                .iter()
+                .map(|ray| self.intersect_1_with_options(*ray, options))
+                .collect(),
+        }
+    }
+
+    /// Intersects primary rays sharing a single origin, e.g. camera rays, with the scene.
+    ///
+    /// The rays are traced as coherent rays (see
+    /// [`RTCRayQueryFlags::COHERENT`](embree4_sys::RTCRayQueryFlags::COHERENT)) in packets of
+    /// the native packet width. Pass the directions in a coherent order, e.g. pixels in tile
+    /// order. Optionally, rays are grouped by the octant of their direction first, so every
+    /// packet traverses the scene in the same order, which helps wide fields of view.
+    ///
+    /// # Arguments
+    /// * `origin` - The shared ray origin.
+    /// * `directions` - The ray directions.
+    /// * `sort_by_octant` - Whether to group rays by direction octant before packing them.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each direction, in the order of `directions`, or an
+    /// error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let (width, height) = (640, 480);
+    /// let directions: Vec<_> = (0..width * height)
+    ///     .map(|i| {
+    ///         let x = (i % width) as f32 / width as f32 - 0.5;
+    ///         let y = (i / width) as f32 / height as f32 - 0.5;
+    ///         (x, y, 1.0)
+    ///     })
+    ///     .collect();
+    /// let hits = scene
+    ///     .intersect_primary((0.0, 0.0, -5.0), &directions, false)
+    ///     .unwrap();
+    /// ```
+    pub fn intersect_primary(
+        &self,
+        origin: (f32, f32, f32),
+        directions: &[(f32, f32, f32)],
+        sort_by_octant: bool,
+    ) -> Result<Vec<Option<Hit>>> {
+        let options = QueryOptions {
+            flags: embree4_sys::RTCRayQueryFlags::COHERENT,
+            ..Default::default()
+        };
+        let ray = |dir: (f32, f32, f32)| embree4_sys::RTCRay {
+            org_x: origin.0,
+            org_y: origin.1,
+            org_z: origin.2,
+            dir_x: dir.0,
+            dir_y: dir.1,
+            dir_z: dir.2,
+            ..Default::default()
+        };
+
+        if !sort_by_octant {
+            let rays: Vec<_> = directions.iter().map(|&dir| ray(dir)).collect();
+            return self.intersect_auto_with_options(&rays, &options);
+        }
+
+        let mut order: Vec<usize> = (0..directions.len()).collect();
+        order.sort_by_key(|&i| octant(directions[i]));
+        let rays: Vec<_> = order.iter().map(|&i| ray(directions[i])).collect();
+        let sorted_hits = self.intersect_auto_with_options(&rays, &options)?;
+
+        let mut hits = vec![None; directions.len()];
+        for (i, hit) in order.into_iter().zip(sorted_hits) {
+            hits[i] = hit;
+        }
+        Ok(hits)
+    }
+
+    /// Tests any number of rays for occlusion, using the widest packet kernel natively
+    /// supported by the device.
+    ///
+    /// See [`CommittedScene::intersect_auto`].
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to test.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` for each occluded ray, in order, or an error if an error
+    /// occurred.
+    pub fn occluded_auto(&self, rays: &[embree4_sys::RTCRay]) -> Result<Vec<bool>> {
+        match self.scene.device.native_packet_width() {
+            16 => self.occluded_chunked::<RayPacket16, 16>(rays),
+            8 => self.occluded_chunked::<RayPacket8, 8>(rays),
+            4 => self.occluded_chunked::<RayPacket4, 4>(rays),
+            _ => rays.iter().map(|ray| self.occluded_1(*ray)).collect(),
+        }
+    }
+
+    fn occluded_chunked<P, const N: usize>(&self, rays: &[embree4_sys::RTCRay]) -> Result<Vec<bool>>
+    where
+        P: PacketKernels<N> + From<[embree4_sys::RTCRay; N]>,
+    {
+        let mut occluded = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(N) {
+            let rays: [embree4_sys::RTCRay; N] =
+                std::array::from_fn(|i| chunk.get(i).copied().unwrap_or_default());
+            let valid = std::array::from_fn(|i| i < chunk.len());
+            let packet_occluded = self.occluded_packet(P::from(rays), valid)?;
+            occluded.extend_from_slice(&packet_occluded[..chunk.len()]);
+        }
+        Ok(occluded)
+    }
+
+    fn intersect_chunked<P, const N: usize>(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        options: &QueryOptions,
+    ) -> Result<Vec<Option<Hit>>>
+    where
+        P: PacketKernels<N> + From<[embree4_sys::RTCRay; N]>,
+    {
+        let mut hits = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(N) {
+            let rays: [embree4_sys::RTCRay; N] =
+                std::array::from_fn(|i| chunk.get(i).copied().unwrap_or_default());
+            let valid = std::array::from_fn(|i| i < chunk.len());
+            let packet_hits = self.intersect_packet(P::from(rays), valid, options)?;
+            hits.extend_from_slice(&packet_hits[..chunk.len()]);
+        }
+        Ok(hits)
+    }
+
+    fn intersect_packet<P: PacketKernels<N>, const N: usize>(
+        &self,
+        packet: P,
+        valid: [bool; N],
+        options: &QueryOptions,
+    ) -> Result<[Option<Hit>; N]> {
+        validate_packet(&packet, valid)?;
+        let mask = ValidMask::from(valid);
+        let mut packet = packet.into_ray_hit();
+        let mut args = options.intersect_arguments();
+        let mut policy = self.policy_context();
+        if let Some(policy) = &mut policy {
+            policy.apply_intersect(&mut args);
+        }
+
+        unsafe {
+            P::INTERSECT(mask.0.as_ptr(), self.scene.handle, &mut packet, &mut args);
+        }
+        if let Some(policy) = policy {
+            policy.resume_panic();
+        }
+        device_error_or(self.scene.device, (), "Could not intersect ray packet")?;
+
+        Ok(std::array::from_fn(|i| {
+            if valid[i] {
+                Hit::from_ray_hit(P::ray_hit(&packet, i))
+            } else {
+                None
+            }
+        }))
+    }
+
+    fn occluded_packet<P: PacketKernels<N>, const N: usize>(
+        &self,
+        packet: P,
+        valid: [bool; N],
+    ) -> Result<[bool; N]> {
+        validate_packet(&packet, valid)?;
+        let mask = ValidMask::from(valid);
+        let mut packet = packet;
+        let mut args = QueryOptions::default().occluded_arguments();
+        let mut policy = self.policy_context();
+        if let Some(policy) = &mut policy {
+            policy.apply_occluded(&mut args);
+        }
+
+        unsafe {
+            packet.occluded(mask.0.as_ptr(), self.scene.handle, &mut args);
+        }
+        if let Some(policy) = policy {
+            policy.resume_panic();
+        }
+        device_error_or(self.scene.device, (), "Could not test ray packet occlusion")?;
+
+        // Embree sets tfar to -inf for occluded rays
+        Ok(std::array::from_fn(|i| {
+            valid[i] && packet.tfar(i) == f32::NEG_INFINITY
+        }))
+    }
+}
+
+/// Validates the rays of the active lanes of a packet, see [`validate_ray`].
+fn validate_packet<P: PacketKernels<N>, const N: usize>(
+    packet: &P,
+    valid: [bool; N],
+) -> Result<()> {
+    (0..N)
+        .filter(|&i| valid[i])
+        .try_for_each(|i| validate_ray(&packet.ray(i)))
+}
+
+/// Returns the octant of a direction, encoded in the sign bits of its components.
+fn octant(dir: (f32, f32, f32)) -> u8 {
+    (dir.0.is_sign_negative() as u8)
+        | (dir.1.is_sign_negative() as u8) << 1
+        | (dir.2.is_sign_negative() as u8) << 2
+}
+
+#[test]
+fn intersect_4_lanes() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let hit_ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let miss_ray = embree4_sys::RTCRay {
+        dir_z: -1.0,
+        ..Default::default()
+    };
+    let hits = scene
+        .intersect_4(
+            [hit_ray, hit_ray, miss_ray, hit_ray],
+            [true, false, true, true],
+        )
+        .unwrap();
+    assert!(hits[0].is_some());
+    assert!(hits[1].is_none());
+    assert!(hits[2].is_none());
+    assert_eq!(hits[3].unwrap().t(), 1.0);
+}
+
+#[test]
+fn intersect_auto_matches_scalar() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let rays: Vec<_> = (0..37)
+        .map(|i| embree4_sys::RTCRay {
+            dir_z: if i % 3 == 0 { -1.0 } else { 1.0 },
+            ..Default::default()
+        })
+        .collect();
+    let hits = scene.intersect_auto(&rays).unwrap();
+    assert_eq!(hits.len(), rays.len());
+    for (ray, hit) in rays.iter().zip(hits) {
+        assert_eq!(hit.is_some(), scene.intersect_1(*ray).unwrap().is_some());
+    }
+}
+
+#[test]
+fn occluded_4_lanes() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let blocked_ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let free_ray = embree4_sys::RTCRay {
+        dir_z: -1.0,
+        ..Default::default()
+    };
+    let occluded = scene
+        .occluded_4(
+            [blocked_ray, blocked_ray, free_ray, blocked_ray],
+            [true, false, true, true],
+        )
+        .unwrap();
+    assert_eq!(occluded, [true, false, false, true]);
+}
+
+#[test]
+fn ray_packet_lanes() {
+    let rays: [crate::Ray; 8] =
+        std::array::from_fn(|i| crate::Ray::new((i as f32, 0.0, 0.0), (0.0, 1.0, 0.0)));
+    let mut packet = RayPacket8::from(rays);
+    assert_eq!(packet.get(5).org_x, 5.0);
+    assert_eq!(packet.get(5).tfar, f32::INFINITY);
+
+    packet.set(
+        5,
+        crate::Ray::new((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)).with_tfar(2.0),
+    );
+    assert_eq!(packet.raw().dir_x[5], 1.0);
+    assert_eq!(packet.get(5).tfar, 2.0);
+    assert_eq!(std::mem::align_of::<RayPacket16>(), 64);
+}
+
+#[test]
+fn occluded_auto_matches_scalar() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let rays: Vec<embree4_sys::RTCRay> = (0..21)
+        .map(|i| crate::Ray::new((i as f32 * 0.1, 0.0, 0.0), (0.0, 0.0, 1.0)).into())
+        .collect();
+    let occluded = scene.occluded_auto(&rays).unwrap();
+    assert_eq!(occluded.len(), rays.len());
+    for (ray, occluded) in rays.iter().zip(occluded) {
+        assert_eq!(occluded, scene.occluded_1(*ray).unwrap());
+    }
+}
+
+#[test]
+fn intersect_primary_preserves_order() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let directions: Vec<_> = (0..37)
+        .map(|i| {
+            let x = (i % 7) as f32 * 0.5 - 1.5;
+            let z = if i % 3 == 0 { -1.0 } else { 1.0 };
+            (x, 0.0, z)
+        })
+        .collect();
+    let unsorted = scene
+        .intersect_primary((0.0, 0.0, 0.0), &directions, false)
+        .unwrap();
+    let sorted = scene
+        .intersect_primary((0.0, 0.0, 0.0), &directions, true)
+        .unwrap();
+    for ((dir, a), b) in directions.iter().zip(&unsorted).zip(&sorted) {
+        let expected = scene
+            .intersect_1(crate::Ray::new((0.0, 0.0, 0.0), *dir))
+            .unwrap();
+        assert_eq!(a.map(|hit| hit.t()), expected.map(|hit| hit.t()));
+        assert_eq!(b.map(|hit| hit.t()), expected.map(|hit| hit.t()));
+    }
+}
+
+#[test]
+fn packets_validate_active_lanes() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    let invalid = embree4_sys::RTCRay {
+        dir_z: f32::NAN,
+        ..Default::default()
+    };
+    let rays = [ray, invalid, ray, ray];
+    assert!(scene.intersect_4(rays, [true, false, true, true]).is_ok());
+    assert!(scene.occluded_4(rays, [true, false, true, true]).is_ok());
+    if cfg!(debug_assertions) {
+        assert!(scene.intersect_4(rays, [true; 4]).is_err());
+        assert!(scene.occluded_4(rays, [true; 4]).is_err());
+        assert!(scene.intersect_auto(&rays).is_err());
+    }
+}
+
+#[test]
+fn packet_queries_apply_hit_policy() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 2.0),
+        (1.0, -1.0, 2.0),
+        (0.0, 1.0, 2.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let mut scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    scene
+        .set_hit_policy(|hit: &Hit| {
+            if hit.prim_id() == 0 {
+                crate::FilterDecision::Reject
+            } else {
+                crate::FilterDecision::Accept
+            }
+        })
+        .unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hits = scene.intersect_4([ray; 4], [true; 4]).unwrap();
+    assert!(hits.iter().all(|hit| hit.unwrap().prim_id() == 1));
+    let occluded = scene
+        .occluded_4([ray.with_tfar(1.5); 4], [true; 4])
+        .unwrap();
+    assert_eq!(occluded, [false; 4]);
+
+    let rays: Vec<embree4_sys::RTCRay> = vec![ray.into(); 21];
+    let hits = scene.intersect_auto(&rays).unwrap();
+    assert!(hits.iter().all(|hit| hit.unwrap().t() == 2.0));
+    let rays: Vec<embree4_sys::RTCRay> = vec![ray.with_tfar(1.5).into(); 21];
+    assert!(scene.occluded_auto(&rays).unwrap().iter().all(|&o| !o));
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::AtomicBool;
+
+use anyhow::Result;
+use rayon::prelude::*;
+
+use crate::{
+    batch::{is_cancelled, CANCEL_CHUNK_SIZE},
+    Batch, BatchStatus, CommittedScene, Hit,
+};
+
+impl<'a> CommittedScene<'a> {
+    /// Intersects many rays with the scene in parallel on the current rayon thread pool.
+    ///
+    /// Rays are split into chunks of [`CANCEL_CHUNK_SIZE`] rays, which are traced on the
+    /// pool's worker threads like [`CommittedScene::intersect_1`]: each ray is validated and
+    /// filtered by the hit policy, and the device error state is checked on the worker thread
+    /// which traced it, as Embree reports errors per thread. Requires the `rayon` feature.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect.
+    /// * `cancel` - An optional cancellation token, checked before each chunk.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each ray, in the order of `rays`, or an error if an
+    /// error occurred. If the batch was cancelled, the results cover the longest prefix of
+    /// `rays` that was fully traced.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let rays: Vec<_> = (0..1_000_000)
+    ///     .map(|i| Ray::new((i as f32, 0.0, 0.0), (0.0, 0.0, 1.0)).into())
+    ///     .collect();
+    /// let hits = scene.par_intersect(&rays, None).unwrap();
+    /// assert_eq!(hits.results.len(), rays.len());
+    /// ```
+    pub fn par_intersect(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Batch<Option<Hit>>> {
+        let chunks: Vec<Option<Vec<Option<Hit>>>> = rays
+            .par_chunks(CANCEL_CHUNK_SIZE)
+            .map(|chunk| {
+                if is_cancelled(cancel) {
+                    return Ok(None);
+                }
+                chunk
+                    .iter()
+                    .map(|ray| self.intersect_1(*ray))
+                    .collect::<Result<_>>()
+                    .map(Some)
+            })
+            .collect::<Result<_>>()?;
+
+        let mut results = Vec::with_capacity(rays.len());
+        let mut status = BatchStatus::Completed;
+        for chunk in chunks {
+            match chunk {
+                Some(hits) => results.extend(hits),
+                None => {
+                    status = BatchStatus::Cancelled;
+                    break;
+                }
+            }
+        }
+        Ok(Batch { results, status })
+    }
+}
+
+#[test]
+fn par_intersect_preserves_order() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let rays: Vec<embree4_sys::RTCRay> = (0..3 * CANCEL_CHUNK_SIZE + 7)
+        .map(|i| {
+            let dir_z = if i % 5 == 0 { -1.0 } else { 1.0 };
+            crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, dir_z)).into()
+        })
+        .collect();
+    let batch = scene.par_intersect(&rays, None).unwrap();
+    assert_eq!(batch.status, BatchStatus::Completed);
+    assert_eq!(batch.results.len(), rays.len());
+    for (i, hit) in batch.results.iter().enumerate() {
+        assert_eq!(hit.is_none(), i % 5 == 0);
+    }
+}
+
+#[test]
+fn par_intersect_rejects_invalid_rays() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut rays: Vec<embree4_sys::RTCRay> =
+        vec![crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).into(); 2 * CANCEL_CHUNK_SIZE];
+    rays[CANCEL_CHUNK_SIZE + 1].dir_x = f32::NAN;
+    assert!(scene.par_intersect(&rays, None).is_err());
+}
@@ -0,0 +1,368 @@
+//! Point queries, which find the geometry closest to a point.
+
+use std::{
+    any::Any,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+};
+
+use anyhow::Result;
+
+use crate::{
+    device_error_or,
+    math::{length, sub, Vec3},
+    CommittedScene,
+};
+
+/// The closest point on the scene's geometry to a query point, see
+/// [`CommittedScene::point_query`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestPoint {
+    /// The closest point on the geometry.
+    pub point: (f32, f32, f32),
+    /// The distance between the query point and [`ClosestPoint::point`].
+    pub distance: f32,
+    /// The ID of the geometry the closest point lies on.
+    pub geom_id: u32,
+    /// The ID of the primitive the closest point lies on.
+    pub prim_id: u32,
+}
+
+/// A primitive visited by a point query, passed to the callback of
+/// [`CommittedScene::point_query_with`].
+pub struct PointQuery<'q> {
+    args: &'q embree4_sys::RTCPointQueryFunctionArguments,
+    shrunk: bool,
+}
+
+impl<'q> PointQuery<'q> {
+    /// Returns the query point.
+    pub fn point(&self) -> (f32, f32, f32) {
+        let query = unsafe { &*self.args.query };
+        (query.x, query.y, query.z)
+    }
+
+    /// Returns the time of the query.
+    pub fn time(&self) -> f32 {
+        unsafe { (*self.args.query).time }
+    }
+
+    /// Returns the current query radius.
+    pub fn radius(&self) -> f32 {
+        unsafe { (*self.args.query).radius }
+    }
+
+    /// Shrinks the query radius, so that primitives farther away than `radius` are culled from
+    /// the rest of the query. Radii larger than the current radius are ignored, as the query
+    /// domain can not grow.
+    pub fn shrink_radius(&mut self, radius: f32) {
+        let query = unsafe { &mut *self.args.query };
+        if radius < query.radius {
+            query.radius = radius;
+            self.shrunk = true;
+        }
+    }
+
+    /// Returns the ID of the visited geometry.
+    pub fn geom_id(&self) -> u32 {
+        self.args.geomID
+    }
+
+    /// Returns the ID of the visited primitive within its geometry.
+    pub fn prim_id(&self) -> u32 {
+        self.args.primID
+    }
+
+    /// Returns the IDs of the instances the visited geometry is nested in, outermost first.
+    pub fn instance_stack(&self) -> &[u32] {
+        let context = unsafe { &*self.args.context };
+        &context.instID[..context.instStackSize as usize]
+    }
+
+    /// Returns the scale of the instance transform if it is a similarity transform, or `0`
+    /// otherwise. Distances in instance space multiplied by this scale are world-space distances.
+    pub fn similarity_scale(&self) -> f32 {
+        self.args.similarityScale
+    }
+}
+
+/// The state of a point query, passed to the point query functions as the user pointer.
+pub(crate) struct PointQueryState<'f> {
+    pub(crate) scene: embree4_sys::RTCScene,
+    pub(crate) closest: Option<ClosestPoint>,
+    /// The callback of [`CommittedScene::point_query_with`], which replaces the built-in
+    /// closest point computation.
+    callback: Option<&'f mut dyn FnMut(&mut PointQuery)>,
+    /// A panic raised by `callback`, resumed once the query returns to Rust.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<'a> CommittedScene<'a> {
+    /// Finds the point on the scene's geometry closest to `point`, within `radius`.
+    ///
+    /// Closest points are computed for triangle meshes, and for user geometry through
+    /// [`UserGeometryImpl::closest_point`](crate::geometry::UserGeometryImpl::closest_point).
+    /// Other geometry is ignored, as is instanced geometry.
+    ///
+    /// # Arguments
+    /// * `point` - The query point.
+    /// * `radius` - The maximum distance of the closest point. Use `f32::INFINITY` for an
+    ///   unbounded search.
+    ///
+    /// # Returns
+    /// A `Result` containing the closest point, if any geometry is within `radius`, or an error
+    /// if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let closest = scene.point_query((0.0, 0.0, 0.0), f32::INFINITY).unwrap().unwrap();
+    /// assert_eq!(closest.point, (0.0, 0.0, 1.0));
+    /// assert_eq!(closest.distance, 1.0);
+    /// ```
+    pub fn point_query(&self, point: (f32, f32, f32), radius: f32) -> Result<Option<ClosestPoint>> {
+        let mut query = embree4_sys::RTCPointQuery {
+            x: point.0,
+            y: point.1,
+            z: point.2,
+            time: 0.0,
+            radius,
+        };
+        let mut context = point_query_context();
+        let mut state = PointQueryState {
+            scene: self.scene.handle,
+            closest: None,
+            callback: None,
+            panic: None,
+        };
+
+        unsafe {
+            embree4_sys::rtcPointQuery(
+                self.scene.handle,
+                &mut query,
+                &mut context,
+                None,
+                &mut state as *mut PointQueryState as *mut _,
+            );
+        }
+        device_error_or(self.scene.device, state.closest, "Could not query point")
+    }
+
+    /// Runs a point query, invoking `callback` for every primitive within the query radius.
+    ///
+    /// The callback replaces the built-in closest point computation of
+    /// [`CommittedScene::point_query`], e.g. to find the k nearest primitives or to compute
+    /// distances to custom primitives. It may shrink the query radius through
+    /// [`PointQuery::shrink_radius`] to cull primitives from the rest of the query.
+    ///
+    /// # Arguments
+    /// * `point` - The query point.
+    /// * `radius` - The initial query radius.
+    /// * `callback` - Called with each primitive whose bounds are within the query radius.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` if the query completed, or an error if an error occurred.
+    ///
+    /// # Panics
+    /// Panics raised by `callback` are propagated once the query has finished.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let mut candidates = Vec::new();
+    /// scene
+    ///     .point_query_with((0.0, 0.0, 0.0), 1.0, |query| {
+    ///         candidates.push((query.geom_id(), query.prim_id()));
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn point_query_with<F>(
+        &self,
+        point: (f32, f32, f32),
+        radius: f32,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&mut PointQuery),
+    {
+        let mut query = embree4_sys::RTCPointQuery {
+            x: point.0,
+            y: point.1,
+            z: point.2,
+            time: 0.0,
+            radius,
+        };
+        let mut context = point_query_context();
+        let mut state = PointQueryState {
+            scene: self.scene.handle,
+            closest: None,
+            callback: Some(&mut callback),
+            panic: None,
+        };
+
+        unsafe {
+            embree4_sys::rtcPointQuery(
+                self.scene.handle,
+                &mut query,
+                &mut context,
+                Some(callback_point_query_fn),
+                &mut state as *mut PointQueryState as *mut _,
+            );
+        }
+        if let Some(panic) = state.panic {
+            resume_unwind(panic);
+        }
+        device_error_or(self.scene.device, (), "Could not query point")
+    }
+}
+
+/// Builds an empty point query context, equivalent to `rtcInitPointQueryContext`.
+pub(crate) fn point_query_context() -> embree4_sys::RTCPointQueryContext {
+    embree4_sys::RTCPointQueryContext {
+        world2inst: [[0.0; 16]; embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize],
+        inst2world: [[0.0; 16]; embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize],
+        instID: [embree4_sys::RTC_INVALID_GEOMETRY_ID;
+            embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize],
+        instStackSize: 0,
+    }
+}
+
+/// Forwards Embree's point query callback to the closure stored in the [`PointQueryState`].
+unsafe extern "C" fn callback_point_query_fn(
+    args: *mut embree4_sys::RTCPointQueryFunctionArguments,
+) -> bool {
+    let args = &*args;
+    let state = &mut *(args.userPtr as *mut PointQueryState);
+    let Some(callback) = state.callback.as_mut() else {
+        return false;
+    };
+    if state.panic.is_some() {
+        return false;
+    }
+
+    let mut query = PointQuery {
+        args,
+        shrunk: false,
+    };
+    // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+    if let Err(panic) = catch_unwind(AssertUnwindSafe(|| callback(&mut query))) {
+        state.panic = Some(panic);
+    }
+    query.shrunk
+}
+
+/// Runs the built-in closest point computation of [`CommittedScene::point_query`] for the
+/// queried primitive, shrinking the query radius to the distance of the closest point.
+///
+/// `closest_point` receives the queried geometry, the primitive ID and the query point, and
+/// returns the closest point on the primitive, if any. Queries of
+/// [`CommittedScene::point_query_with`] and instanced geometry are skipped.
+pub(crate) unsafe fn update_closest_point(
+    args: *mut embree4_sys::RTCPointQueryFunctionArguments,
+    closest_point: impl FnOnce(embree4_sys::RTCGeometry, u32, Vec3) -> Option<Vec3>,
+) -> bool {
+    let args = &*args;
+    if args.userPtr.is_null() || (*args.context).instStackSize > 0 {
+        return false;
+    }
+    let state = &mut *(args.userPtr as *mut PointQueryState);
+    if state.callback.is_some() {
+        return false;
+    }
+    let query = &mut *args.query;
+
+    let geometry = embree4_sys::rtcGetGeometryThreadSafe(state.scene, args.geomID);
+    let p = (query.x, query.y, query.z);
+    let Some(closest) = closest_point(geometry, args.primID, p) else {
+        return false;
+    };
+    let distance = length(sub(closest, p));
+    if distance >= query.radius {
+        return false;
+    }
+
+    query.radius = distance;
+    state.closest = Some(ClosestPoint {
+        point: closest,
+        distance,
+        geom_id: args.geomID,
+        prim_id: args.primID,
+    });
+    true
+}
+
+#[test]
+fn point_query_closest_triangle() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 3.0),
+        (1.0, -1.0, 3.0),
+        (0.0, 1.0, 3.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let closest = scene
+        .point_query((0.0, 0.0, 2.5), f32::INFINITY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(closest.point, (0.0, 0.0, 3.0));
+    assert_eq!(closest.distance, 0.5);
+    assert_eq!(closest.prim_id, 1);
+
+    let closest = scene.point_query((3.0, -1.0, 1.0), 2.5).unwrap().unwrap();
+    assert_eq!(closest.point, (1.0, -1.0, 1.0));
+    assert_eq!(closest.prim_id, 0);
+
+    assert!(scene.point_query((0.0, 0.0, 0.0), 0.5).unwrap().is_none());
+}
+
+#[test]
+fn point_query_with_shrinks_radius() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 3.0),
+        (1.0, -1.0, 3.0),
+        (0.0, 1.0, 3.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut visited = Vec::new();
+    scene
+        .point_query_with((0.0, 0.0, 0.0), 10.0, |query| {
+            visited.push(query.prim_id());
+            assert!(query.instance_stack().is_empty());
+            query.shrink_radius(20.0);
+            assert_eq!(query.radius(), 10.0);
+        })
+        .unwrap();
+    visited.sort();
+    assert_eq!(visited, [0, 1]);
+}
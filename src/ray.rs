@@ -0,0 +1,219 @@
+/// A ray for the high-level query API.
+///
+/// Converts into an [`RTCRay`](embree4_sys::RTCRay), so it can be passed to all scalar queries,
+/// e.g. [`CommittedScene::intersect_1`](crate::CommittedScene::intersect_1).
+///
+/// # Example
+/// ```
+/// use embree4_rs::Ray;
+///
+/// let ray = Ray::new((0.0, 0.0, -1.0), (0.0, 0.0, 1.0))
+///     .with_tfar(10.0)
+///     .with_mask(0b01)
+///     .with_time(0.5)
+///     .with_id(42);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: (f32, f32, f32),
+    /// The ray direction. Does not need to be normalized, distances are measured in multiples
+    /// of its length.
+    pub direction: (f32, f32, f32),
+    /// The start of the ray segment.
+    pub tnear: f32,
+    /// The end of the ray segment.
+    pub tfar: f32,
+    /// The ray mask. A geometry is only hit if the bitwise AND of its mask (see
+    /// [`Geometry::set_mask`](crate::geometry::Geometry::set_mask)) and the ray mask is non-zero.
+    pub mask: u32,
+    /// The time of the ray in `[0, 1]` for sampling motion-blurred geometry.
+    pub time: f32,
+    /// A user-defined ray ID, e.g. the index of the originating pixel or sample. Embree does not
+    /// interpret the ID, it is passed through to the ray's hit, see [`Hit::ray_id`](crate::Hit::ray_id).
+    pub id: u32,
+}
+
+impl Ray {
+    /// Constructs a new `Ray` covering `[0, inf)` at time `0`, hitting all geometry masks.
+    pub fn new(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Self {
+        Self {
+            origin,
+            direction,
+            tnear: 0.0,
+            tfar: f32::INFINITY,
+            mask: u32::MAX,
+            time: 0.0,
+            id: 0,
+        }
+    }
+
+    /// Constructs a new `Ray` from a double-precision origin and direction.
+    ///
+    /// The origin is translated into the local frame around `local_origin` in double precision
+    /// before being narrowed to `f32`, see [`Scene::to_local`](crate::Scene::to_local). The
+    /// direction is narrowed as is, so hit distances are valid in world space as well.
+    ///
+    /// # Arguments
+    /// * `origin` - The world-space ray origin.
+    /// * `direction` - The ray direction.
+    /// * `local_origin` - The origin of the local frame, e.g. the scene's
+    ///   [`world_origin`](crate::Scene::world_origin). Pass `(0.0, 0.0, 0.0)` to keep world
+    ///   coordinates.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::Ray;
+    ///
+    /// let local_origin = (6_378_137.0, 0.0, 0.0);
+    /// let ray = Ray::from_f64((6_378_137.5, 0.0, 0.0), (0.0, 1.0, 0.0), local_origin);
+    /// assert_eq!(ray.origin, (0.5, 0.0, 0.0));
+    /// ```
+    pub fn from_f64(
+        origin: (f64, f64, f64),
+        direction: (f64, f64, f64),
+        local_origin: (f64, f64, f64),
+    ) -> Self {
+        Self::new(
+            crate::math::to_local(origin, local_origin),
+            (direction.0 as f32, direction.1 as f32, direction.2 as f32),
+        )
+    }
+
+    /// Sets the start of the ray segment.
+    pub fn with_tnear(self, tnear: f32) -> Self {
+        Self { tnear, ..self }
+    }
+
+    /// Sets the end of the ray segment.
+    pub fn with_tfar(self, tfar: f32) -> Self {
+        Self { tfar, ..self }
+    }
+
+    /// Sets the ray mask, see [`Ray::mask`].
+    pub fn with_mask(self, mask: u32) -> Self {
+        Self { mask, ..self }
+    }
+
+    /// Sets the time of the ray, see [`Ray::time`].
+    pub fn with_time(self, time: f32) -> Self {
+        Self { time, ..self }
+    }
+
+    /// Sets the ID of the ray, see [`Ray::id`].
+    pub fn with_id(self, id: u32) -> Self {
+        Self { id, ..self }
+    }
+}
+
+impl From<Ray> for embree4_sys::RTCRay {
+    fn from(ray: Ray) -> Self {
+        embree4_sys::RTCRay {
+            org_x: ray.origin.0,
+            org_y: ray.origin.1,
+            org_z: ray.origin.2,
+            tnear: ray.tnear,
+            dir_x: ray.direction.0,
+            dir_y: ray.direction.1,
+            dir_z: ray.direction.2,
+            tfar: ray.tfar,
+            mask: ray.mask,
+            time: ray.time,
+            id: ray.id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Checks a ray for values that make Embree silently report garbage hits: NaN origins or
+/// directions, zero directions and empty or NaN `[tnear, tfar]` intervals.
+///
+/// Only validates in debug builds, release builds pass all rays through unchecked.
+#[cfg_attr(not(debug_assertions), inline(always))]
+pub(crate) fn validate_ray(ray: &embree4_sys::RTCRay) -> anyhow::Result<()> {
+    if cfg!(debug_assertions) {
+        let origin = (ray.org_x, ray.org_y, ray.org_z);
+        let direction = (ray.dir_x, ray.dir_y, ray.dir_z);
+        if origin.0.is_nan() || origin.1.is_nan() || origin.2.is_nan() {
+            anyhow::bail!("Invalid ray: origin {:?} is NaN", origin);
+        }
+        if direction.0.is_nan() || direction.1.is_nan() || direction.2.is_nan() {
+            anyhow::bail!("Invalid ray: direction {:?} is NaN", direction);
+        }
+        if direction == (0.0, 0.0, 0.0) {
+            anyhow::bail!("Invalid ray: direction is zero");
+        }
+        if ray.tnear.is_nan() || ray.tfar.is_nan() {
+            anyhow::bail!(
+                "Invalid ray: tnear {} or tfar {} is NaN",
+                ray.tnear,
+                ray.tfar
+            );
+        }
+        if ray.tnear > ray.tfar {
+            anyhow::bail!(
+                "Invalid ray: tnear {} is greater than tfar {}",
+                ray.tnear,
+                ray.tfar
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn ray_into_rtc_ray() {
+    let ray: embree4_sys::RTCRay = Ray::new((1.0, 2.0, 3.0), (0.0, 1.0, 0.0))
+        .with_tnear(0.5)
+        .with_tfar(4.0)
+        .with_mask(7)
+        .with_time(0.25)
+        .with_id(9)
+        .into();
+    assert_eq!((ray.org_x, ray.org_y, ray.org_z), (1.0, 2.0, 3.0));
+    assert_eq!((ray.dir_x, ray.dir_y, ray.dir_z), (0.0, 1.0, 0.0));
+    assert_eq!((ray.tnear, ray.tfar), (0.5, 4.0));
+    assert_eq!(ray.mask, 7);
+    assert_eq!(ray.time, 0.25);
+    assert_eq!(ray.id, 9);
+}
+
+#[test]
+fn ray_mask_excludes_geometry() {
+    use crate::geometry::Geometry;
+
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry.set_mask(0b10);
+    geometry.commit(&device).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.intersect_1(ray.with_mask(0b10)).unwrap().is_some());
+
+    let mask_supported = unsafe {
+        embree4_sys::rtcGetDeviceProperty(
+            device.handle,
+            embree4_sys::RTCDeviceProperty::RAY_MASK_SUPPORTED,
+        )
+    } != 0;
+    if mask_supported {
+        assert!(scene.intersect_1(ray.with_mask(0b01)).unwrap().is_none());
+        assert!(!scene.occluded_1(ray.with_mask(0b01)).unwrap());
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn validate_ray_rejects_invalid() {
+    let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(validate_ray(&ray.into()).is_ok());
+    assert!(validate_ray(&Ray::new((f32::NAN, 0.0, 0.0), (0.0, 0.0, 1.0)).into()).is_err());
+    assert!(validate_ray(&Ray::new((0.0, 0.0, 0.0), (0.0, f32::NAN, 1.0)).into()).is_err());
+    assert!(validate_ray(&Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 0.0)).into()).is_err());
+    assert!(validate_ray(&ray.with_tnear(2.0).with_tfar(1.0).into()).is_err());
+    assert!(validate_ray(&ray.with_tfar(f32::NAN).into()).is_err());
+}
@@ -0,0 +1,218 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use anyhow::{bail, Result};
+
+use crate::{batch::run_cancellable, Batch, CommittedScene, Device, Hit, Scene, SceneOptions};
+
+/// The same scene, built on multiple devices.
+///
+/// Replicating a scene per device (e.g. one device per NUMA node, pinned via the `threads` and
+/// `set_affinity` device configuration) lets high-throughput services keep every replica's BVH in
+/// node-local memory. Use [`SceneReplicas::commit`] to obtain a [`ReplicaRouter`] that dispatches
+/// ray batches to the replicas.
+pub struct SceneReplicas<'a, G> {
+    scenes: Vec<Scene<'a>>,
+    // The geometries must outlive the scenes they are attached to.
+    _geometries: Vec<G>,
+}
+
+impl<'a, G> SceneReplicas<'a, G> {
+    /// Builds the same scene description on each of the given devices.
+    ///
+    /// # Arguments
+    /// * `devices` - The devices to replicate the scene on. Must not be empty.
+    /// * `options` - The options for creating each scene.
+    /// * `build` - Builds the scene description for one device. It receives the device and the
+    ///   scene to attach geometries to, and returns the geometries, which are kept alive as long
+    ///   as the replicas.
+    ///
+    /// # Returns
+    /// A `Result` containing the `SceneReplicas` if successful, or an error if `devices` is
+    /// empty or building any replica failed.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let devices = [
+    ///     Device::try_new(Some("threads=1")).unwrap(),
+    ///     Device::try_new(Some("threads=1")).unwrap(),
+    /// ];
+    /// let replicas = SceneReplicas::try_new(&devices, Default::default(), |device, scene| {
+    ///     let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    ///     let geometry = TriangleMeshGeometry::try_new(device, &vertices, &[(0, 1, 2)])?;
+    ///     scene.attach_geometry(&geometry)?;
+    ///     Ok(geometry)
+    /// })
+    /// .unwrap();
+    ///
+    /// let router = replicas.commit().unwrap();
+    /// let ray = embree4_sys::RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// let hits = router.intersect(&[ray], None).unwrap();
+    /// assert!(hits.results[0].is_some());
+    /// ```
+    pub fn try_new<F>(devices: &'a [Device], options: SceneOptions, build: F) -> Result<Self>
+    where
+        F: Fn(&'a Device, &Scene<'a>) -> Result<G>,
+    {
+        if devices.is_empty() {
+            bail!("Could not replicate scene: no devices given");
+        }
+
+        let mut scenes = Vec::with_capacity(devices.len());
+        let mut geometries = Vec::with_capacity(devices.len());
+        for device in devices {
+            let scene = Scene::try_new(device, options)?;
+            geometries.push(build(device, &scene)?);
+            scenes.push(scene);
+        }
+
+        Ok(Self {
+            scenes,
+            _geometries: geometries,
+        })
+    }
+
+    /// Returns the number of replicas.
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    /// Returns `true` if there are no replicas. This is never the case for successfully
+    /// constructed replicas.
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// Commits all replicas.
+    ///
+    /// # Returns
+    /// A `Result` containing the `ReplicaRouter` if successful, or an error if committing any
+    /// replica failed.
+    pub fn commit(&self) -> Result<ReplicaRouter<'_>> {
+        let replicas = self
+            .scenes
+            .iter()
+            .map(|scene| {
+                Ok(Replica {
+                    scene: scene.commit()?,
+                    in_flight: AtomicUsize::new(0),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(ReplicaRouter { replicas })
+    }
+}
+
+struct Replica<'a> {
+    scene: CommittedScene<'a>,
+    in_flight: AtomicUsize,
+}
+
+/// Dispatches ray batches to the least-loaded replica of a [`SceneReplicas`].
+///
+/// The load of a replica is the number of rays currently being traced on it. The router is
+/// `Sync`, so batches can be submitted concurrently from many threads.
+pub struct ReplicaRouter<'a> {
+    replicas: Vec<Replica<'a>>,
+}
+
+impl<'a> ReplicaRouter<'a> {
+    /// Intersects a batch of rays with the least-loaded replica.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect.
+    /// * `cancel` - An optional cancellation token. Setting it aborts the batch, returning the
+    ///   hits traced so far.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit for each traced ray, in order, or an error if an error
+    /// occurred.
+    pub fn intersect(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Batch<Option<Hit>>> {
+        let reservation = self.reserve(rays.len());
+        run_cancellable(rays, cancel, |ray| {
+            reservation.replica.scene.intersect_1(*ray)
+        })
+    }
+
+    /// Returns the number of rays currently in flight on each replica.
+    pub fn loads(&self) -> Vec<usize> {
+        self.replicas
+            .iter()
+            .map(|replica| replica.in_flight.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Adds `count` rays to the load of the least-loaded replica, until the returned
+    /// reservation is dropped.
+    fn reserve(&self, count: usize) -> Reservation<'_, 'a> {
+        loop {
+            let (replica, load) = self
+                .replicas
+                .iter()
+                .map(|replica| (replica, replica.in_flight.load(Ordering::Relaxed)))
+                .min_by_key(|&(_, load)| load)
+                .expect("replicas are never empty");
+            // Retry if another thread changed the load since it was read, so concurrent batches
+            // do not all pick the same replica
+            if replica
+                .in_flight
+                .compare_exchange(load, load + count, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Reservation { replica, count };
+            }
+        }
+    }
+}
+
+/// Rays counted towards the load of a replica, released on drop even if tracing panics.
+struct Reservation<'r, 'a> {
+    replica: &'r Replica<'a>,
+    count: usize,
+}
+
+impl Drop for Reservation<'_, '_> {
+    fn drop(&mut self) {
+        self.replica
+            .in_flight
+            .fetch_sub(self.count, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn router_balances_reservations() {
+    let devices = [
+        Device::try_new(Some("threads=1")).unwrap(),
+        Device::try_new(Some("threads=1")).unwrap(),
+    ];
+    let replicas = SceneReplicas::try_new(&devices, Default::default(), |_, _| Ok(())).unwrap();
+    let router = replicas.commit().unwrap();
+
+    let first = router.reserve(10);
+    let second = router.reserve(5);
+    assert!(!std::ptr::eq(first.replica, second.replica));
+    assert_eq!(router.loads(), [10, 5]);
+
+    // The next batch goes to the replica with fewer rays in flight
+    let third = router.reserve(1);
+    assert!(std::ptr::eq(third.replica, second.replica));
+    assert_eq!(router.loads(), [10, 6]);
+
+    drop(first);
+    assert_eq!(router.loads(), [0, 6]);
+    drop((second, third));
+    assert_eq!(router.loads(), [0, 0]);
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).into();
+    let hits = router.intersect(&[ray; 3], None).unwrap();
+    assert_eq!(hits.results.len(), 3);
+    assert_eq!(router.loads(), [0, 0]);
+}
@@ -1,10 +1,50 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
 use anyhow::{bail, Result};
 
-use crate::{device_error_or, device_error_raw, geometry::Geometry, Device};
+use crate::{
+    batch::{is_cancelled, CANCEL_CHUNK_SIZE},
+    device_error, device_error_or,
+    geometry::{Geometry, RetainedData},
+    ray::validate_ray,
+    BatchStatus, Device, FilterDecision, Hit, HitPolicy, RayQueryContext,
+};
 
 pub struct Scene<'a> {
-    device: &'a Device,
-    handle: embree4_sys::RTCScene,
+    pub(crate) device: &'a Device,
+    pub(crate) handle: embree4_sys::RTCScene,
+    world_origin: (f64, f64, f64),
+    pub(crate) hit_policy: Option<Box<dyn HitPolicy + 'a>>,
+    /// The build quality, which Embree cannot report, see [`Scene::duplicate`].
+    build_quality: Mutex<embree4_sys::RTCBuildQuality>,
+    /// The attached geometries, which Embree cannot enumerate.
+    pub(crate) attached: AttachedGeometries,
+    /// User payloads of attached geometries, keyed by geometry ID. Shared with duplicates.
+    payloads: HashMap<u32, Arc<dyn Any + Send + Sync>>,
+    /// Geometries owned by the scene, keyed by geometry ID.
+    owned_geometries: HashMap<u32, Box<dyn Any + Send + Sync>>,
+    /// The progress monitor, boxed so its address stays stable while Embree holds it.
+    progress_monitor: Option<Box<ProgressMonitor<'a>>>,
+}
+
+/// The geometries attached to a scene, keyed by geometry ID, with the data they retain (see
+/// [`Geometry::retained_data`]). Shared with the instances of the scene, which keep the data
+/// alive as long as they reference the scene.
+pub(crate) type AttachedGeometries = Arc<Mutex<BTreeMap<u32, Option<GeometryData>>>>;
+
+/// Data retained by an attached geometry, see [`Geometry::retained_data`].
+type GeometryData = Arc<dyn Any + Send + Sync>;
+
+/// A progress monitor installed with [`Scene::set_progress_monitor`].
+struct ProgressMonitor<'a> {
+    callback: Box<dyn Fn(f64) -> bool + Send + Sync + 'a>,
+    /// The first panic raised by `callback` during a commit, resumed once the commit returns.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
 }
 
 impl<'a> Scene<'a> {
@@ -25,7 +65,12 @@ impl<'a> Scene<'a> {
     /// let device = Device::try_new(None).unwrap();
     /// let options = SceneOptions {
     ///     build_quality: RTCBuildQuality::HIGH,
-    ///     flags: RTCSceneFlags::COMPACT | RTCSceneFlags::ROBUST,
+    ///     flags: SceneFlags {
+    ///         compact: true,
+    ///         robust: true,
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
     /// };
     /// let scene = Scene::try_new(&device, options).unwrap();
     /// ```
@@ -33,11 +78,20 @@ impl<'a> Scene<'a> {
         let handle = unsafe { embree4_sys::rtcNewScene(device.handle) };
 
         if handle.is_null() {
-            let error = device_error_raw(device.handle);
-            bail!("Could not create scene: {:?}", error);
+            return Err(device_error(device, "Could not create scene"));
         }
 
-        let scene = Scene { device, handle };
+        let scene = Scene {
+            device,
+            handle,
+            world_origin: options.world_origin,
+            hit_policy: None,
+            build_quality: Mutex::new(Default::default()),
+            attached: Default::default(),
+            payloads: HashMap::new(),
+            owned_geometries: HashMap::new(),
+            progress_monitor: None,
+        };
 
         if options.build_quality != Default::default() {
             scene.set_build_quality(options.build_quality)?;
@@ -50,6 +104,39 @@ impl<'a> Scene<'a> {
         Ok(scene)
     }
 
+    /// Returns a builder, which creates a scene, attaches geometries and commits the scene in one
+    /// expression.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCBuildQuality;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let floor = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let wall = TriangleMeshGeometry::try_new(&device, &vertices, &[(2, 1, 0)]).unwrap();
+    ///
+    /// let scene = Scene::builder(&device)
+    ///     .build_quality(RTCBuildQuality::HIGH)
+    ///     .flags(SceneFlags {
+    ///         robust: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .attach(&floor)
+    ///     .attach_with_id(7, &wall)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// let hit = scene.committed().intersect_1(ray).unwrap();
+    /// ```
+    pub fn builder(device: &'a Device) -> SceneBuilder<'a> {
+        SceneBuilder {
+            scene: Scene::try_new(device, Default::default()),
+        }
+    }
+
     /// Sets the build quality of the scene.
     ///
     /// # Arguments
@@ -61,25 +148,148 @@ impl<'a> Scene<'a> {
         unsafe {
             embree4_sys::rtcSetSceneBuildQuality(self.handle, quality);
         }
-        device_error_or(self.device, (), "Could not set scene build quality")
+        device_error_or(self.device, (), "Could not set scene build quality")?;
+        *self.build_quality.lock().unwrap() = quality;
+        Ok(())
     }
 
-    /// Sets the flags of the scene.
+    /// Sets the flags of the scene, which take effect on the next commit.
     ///
     /// # Arguments
-    /// * `flags` - The flags to set.
+    /// * `flags` - The flags to set, replacing all previously set flags.
     ///
     /// # Returns
     /// A `Result` indicating success or failure.
-    pub fn set_flags(&self, flags: embree4_sys::RTCSceneFlags) -> Result<()> {
+    pub fn set_flags(&self, flags: SceneFlags) -> Result<()> {
         unsafe {
-            embree4_sys::rtcSetSceneFlags(self.handle, flags);
+            embree4_sys::rtcSetSceneFlags(self.handle, flags.into());
         }
         device_error_or(self.device, (), "Could not set scene flags")
     }
 
+    /// Returns the flags of the scene.
+    pub fn flags(&self) -> SceneFlags {
+        unsafe { embree4_sys::rtcGetSceneFlags(self.handle) }.into()
+    }
+
+    /// Installs a hit policy deciding about every candidate hit of the scene's queries.
+    ///
+    /// The policy is evaluated for the candidate hits of [`CommittedScene::intersect_1`],
+    /// [`CommittedScene::occluded_1`] and their variants, as well as slice, filtered, multi-hit
+    /// and packet queries, e.g. to ignore geometry behind a clip plane. Unchecked queries do not
+    /// evaluate the policy. Packet queries treat [`FilterDecision::Terminate`] like
+    /// [`FilterDecision::Accept`]. Hits of user geometry are evaluated before they are
+    /// committed, and occlusion by user geometry as a hit without normal, see
+    /// [`UserGeometryImpl::occluded`](crate::geometry::UserGeometryImpl::occluded).
+    ///
+    /// Sets the scene flag [`SceneFlags::filter_function_in_arguments`], which takes effect on the
+    /// next commit.
+    ///
+    /// # Arguments
+    /// * `policy` - The hit policy, replacing any previously installed policy.
+    ///
+    /// # Returns
+    /// A `Result` indicating success or failure.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// scene
+    ///     .set_hit_policy(|hit: &Hit| {
+    ///         if hit.hit_point().2 < 0.0 {
+    ///             FilterDecision::Reject
+    ///         } else {
+    ///             FilterDecision::Accept
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// let scene = scene.commit().unwrap();
+    /// ```
+    pub fn set_hit_policy(&mut self, policy: impl HitPolicy + 'a) -> Result<()> {
+        self.set_flags(SceneFlags {
+            filter_function_in_arguments: true,
+            ..self.flags()
+        })?;
+        self.hit_policy = Some(Box::new(policy));
+        Ok(())
+    }
+
+    /// Installs a progress monitor, which is called during commits with the build progress.
+    ///
+    /// The callback receives the progress as a value from 0 to 1, and may be called from
+    /// multiple threads concurrently. Returning `false` cancels the build, and the commit
+    /// returns an error.
+    ///
+    /// # Arguments
+    /// * `callback` - The progress monitor, replacing any previously installed monitor.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    ///
+    /// let percent = AtomicU32::new(0);
+    /// scene.set_progress_monitor(|progress| {
+    ///     let now = (progress * 100.0) as u32;
+    ///     if percent.fetch_max(now, Ordering::Relaxed) < now {
+    ///         println!("building: {}%", now);
+    ///     }
+    ///     true
+    /// });
+    /// let scene = scene.commit().unwrap();
+    /// ```
+    pub fn set_progress_monitor(&mut self, callback: impl Fn(f64) -> bool + Send + Sync + 'a) {
+        let monitor = Box::new(ProgressMonitor {
+            callback: Box::new(callback),
+            panic: Mutex::new(None),
+        });
+        unsafe {
+            embree4_sys::rtcSetSceneProgressMonitorFunction(
+                self.handle,
+                Some(progress_monitor_fn),
+                &*monitor as *const ProgressMonitor as *mut _,
+            );
+        }
+        self.progress_monitor = Some(monitor);
+    }
+
+    /// Returns the world-space origin of the scene's local frame.
+    ///
+    /// See [`SceneOptions::world_origin`].
+    pub fn world_origin(&self) -> (f64, f64, f64) {
+        self.world_origin
+    }
+
+    /// Converts a double-precision world-space point into the scene's local `f32` frame.
+    ///
+    /// Use this to place geometry vertices relative to the scene's world origin, so that
+    /// positions near the origin keep full `f32` precision even in planetary-scale worlds.
+    ///
+    /// # Arguments
+    /// * `point` - The world-space point.
+    ///
+    /// # Returns
+    /// The point relative to the scene's world origin.
+    pub fn to_local(&self, point: (f64, f64, f64)) -> (f32, f32, f32) {
+        crate::math::to_local(point, self.world_origin)
+    }
+
     /// Attaches the given geometry to the scene.
     ///
+    /// The scene retains the Embree geometry and the data of the geometry Embree points to, e.g.
+    /// opacity callbacks, owned vertex buffers or the user data of a
+    /// [`UserGeometry`](crate::geometry::UserGeometry) (see [`Geometry::retained_data`]), so the
+    /// geometry may be dropped while the scene is still in use. Memory the geometry only
+    /// borrows, e.g. of a [`SharedBuffer`](crate::SharedBuffer), must still outlive the scene. Use
+    /// [`Scene::attach_geometry_owned`] to modify the geometry through the scene later.
+    ///
     /// # Arguments
     /// * `geometry` - A reference to the `Geometry` instance to attach.
     ///
@@ -87,11 +297,402 @@ impl<'a> Scene<'a> {
     /// * A `Result` containing the geometry ID if successful, or an error if an error occurred.
     pub fn attach_geometry(&self, geometry: &impl Geometry) -> Result<u32> {
         let geom_id = unsafe { embree4_sys::rtcAttachGeometry(self.handle, geometry.geometry()) };
-        device_error_or(self.device, geom_id, "Could not attach geometry")
+        device_error_or(self.device, (), "Could not attach geometry")?;
+        self.attached
+            .lock()
+            .unwrap()
+            .insert(geom_id, geometry.retained_data());
+        Ok(geom_id)
+    }
+
+    /// Attaches all given geometries to the scene.
+    ///
+    /// Errors are checked once after all geometries have been attached, rather than after every
+    /// geometry. If any geometry could not be attached, the geometries attached by this call are
+    /// detached again, leaving the scene unchanged. Geometries of different types can be attached
+    /// together as `&dyn Geometry`.
+    ///
+    /// # Arguments
+    /// * `geometries` - The geometries to attach.
+    ///
+    /// # Returns
+    /// * A `Result` containing the geometry IDs in the order of `geometries` if successful, or an
+    ///   error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let meshes: Vec<_> = (0..8)
+    ///     .map(|_| TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap())
+    ///     .collect();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let geom_ids = scene.attach_all(&meshes).unwrap();
+    /// assert_eq!(geom_ids.len(), meshes.len());
+    /// let scene = scene.commit().unwrap();
+    /// ```
+    pub fn attach_all<'g, G>(&self, geometries: impl IntoIterator<Item = &'g G>) -> Result<Vec<u32>>
+    where
+        G: Geometry + ?Sized + 'g,
+    {
+        let (geom_ids, retained): (Vec<u32>, Vec<_>) = geometries
+            .into_iter()
+            .map(|geometry| {
+                let geom_id =
+                    unsafe { embree4_sys::rtcAttachGeometry(self.handle, geometry.geometry()) };
+                (geom_id, geometry.retained_data())
+            })
+            .unzip();
+
+        let result = device_error_or(self.device, (), "Could not attach geometries");
+        if result.is_err() {
+            for &geom_id in &geom_ids {
+                if geom_id != embree4_sys::RTC_INVALID_GEOMETRY_ID {
+                    unsafe { embree4_sys::rtcDetachGeometry(self.handle, geom_id) };
+                }
+            }
+        }
+        result?;
+        self.attached
+            .lock()
+            .unwrap()
+            .extend(geom_ids.iter().copied().zip(retained));
+        Ok(geom_ids)
+    }
+
+    /// Attaches the given geometry to the scene, which takes ownership of it.
+    ///
+    /// The geometry lives as long as the scene, or until it is detached with
+    /// [`Scene::detach_geometry`], so it cannot be dropped while the scene still uses it. Use
+    /// [`Scene::owned_geometry_mut`] to modify it later.
+    ///
+    /// # Arguments
+    /// * `geometry` - The geometry to attach.
+    ///
+    /// # Returns
+    /// * A `Result` containing the geometry ID if successful, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// fn build_scene(device: &Device) -> Scene<'_> {
+    ///     let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    ///     let geometry = TriangleMeshGeometry::try_new(device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///     let mut scene = Scene::try_new(device, SceneOptions::default()).unwrap();
+    ///     scene.attach_geometry_owned(geometry).unwrap();
+    ///     scene
+    /// }
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = build_scene(&device);
+    /// let scene = scene.commit().unwrap();
+    /// ```
+    pub fn attach_geometry_owned<G>(&mut self, geometry: G) -> Result<u32>
+    where
+        G: Geometry + Send + Sync + 'static,
+    {
+        let geom_id = self.attach_geometry(&geometry)?;
+        self.owned_geometries.insert(geom_id, Box::new(geometry));
+        Ok(geom_id)
+    }
+
+    /// Returns a geometry attached with [`Scene::attach_geometry_owned`], e.g. to update it.
+    ///
+    /// # Arguments
+    /// * `geom_id` - The geometry ID returned by [`Scene::attach_geometry_owned`].
+    ///
+    /// # Returns
+    /// The geometry, or `None` if the scene does not own a geometry of type `G` with the ID.
+    pub fn owned_geometry_mut<G: Geometry + 'static>(&mut self, geom_id: u32) -> Option<&mut G> {
+        self.owned_geometries.get_mut(&geom_id)?.downcast_mut()
+    }
+
+    /// Attaches the given geometry to the scene with a caller-chosen geometry ID.
+    ///
+    /// Use this to keep geometry IDs stable, e.g. matching entity indices. Embree allocates
+    /// storage up to the largest ID, so IDs should be dense. Do not mix this with
+    /// [`Scene::attach_geometry`] in the same scene, as automatically assigned IDs may collide
+    /// with chosen ones.
+    ///
+    /// # Arguments
+    /// * `geometry` - A reference to the `Geometry` instance to attach.
+    /// * `geom_id` - The geometry ID, which must not be in use in the scene.
+    ///
+    /// # Returns
+    /// A `Result` indicating success or failure, e.g. if the ID is already in use.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    ///
+    /// let entity = 42;
+    /// scene.attach_geometry_by_id(&geometry, entity).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// let hit = scene.intersect_1(ray).unwrap().unwrap();
+    /// assert_eq!(hit.geom_id(), entity);
+    /// ```
+    pub fn attach_geometry_by_id(&self, geometry: &impl Geometry, geom_id: u32) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcAttachGeometryByID(self.handle, geometry.geometry(), geom_id);
+        }
+        device_error_or(self.device, (), "Could not attach geometry")?;
+        self.attached
+            .lock()
+            .unwrap()
+            .insert(geom_id, geometry.retained_data());
+        Ok(())
+    }
+
+    /// Detaches a geometry from the scene, together with its payload.
+    ///
+    /// Geometries attached with [`Scene::attach_geometry_owned`] are dropped.
+    ///
+    /// The change takes effect on the next commit, which only rebuilds the parts of the scene
+    /// that changed. The geometry ID may be reused by geometries attached later.
+    ///
+    /// # Arguments
+    /// * `geom_id` - The geometry ID returned by [`Scene::attach_geometry`].
+    ///
+    /// # Returns
+    /// A `Result` indicating success or failure, e.g. if no geometry with the ID is attached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let mut scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let geom_id = scene.attach_geometry(&geometry).unwrap();
+    ///
+    /// scene.detach_geometry(geom_id).unwrap();
+    /// let scene = scene.commit().unwrap();
+    /// ```
+    pub fn detach_geometry(&mut self, geom_id: u32) -> Result<()> {
+        unsafe {
+            embree4_sys::rtcDetachGeometry(self.handle, geom_id);
+        }
+        device_error_or(self.device, (), "Could not detach geometry")?;
+        self.attached.lock().unwrap().remove(&geom_id);
+        self.payloads.remove(&geom_id);
+        self.owned_geometries.remove(&geom_id);
+        Ok(())
+    }
+
+    /// Creates a new scene with the same geometries attached under the same IDs.
+    ///
+    /// The geometries are not copied, both scenes reference the same Embree geometries, so
+    /// duplicating a scene is cheap. This is useful to build variants of a scene, e.g. with some
+    /// objects detached. The duplicate has the same build quality, flags and world origin, and
+    /// shares the payloads of the geometries. Hit policies and progress monitors are not copied.
+    ///
+    /// The duplicate retains the data of the geometries like this scene, but borrows this
+    /// scene, as it uses the same device.
+    ///
+    /// # Returns
+    /// A `Result` containing the uncommitted duplicate, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let wall = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let wall_id = scene.attach_geometry(&wall).unwrap();
+    ///
+    /// let mut without_wall = scene.duplicate().unwrap();
+    /// without_wall.detach_geometry(wall_id).unwrap();
+    ///
+    /// let with_wall = scene.commit().unwrap();
+    /// let without_wall = without_wall.commit().unwrap();
+    /// ```
+    pub fn duplicate(&self) -> Result<Scene<'_>> {
+        let options = SceneOptions {
+            build_quality: *self.build_quality.lock().unwrap(),
+            flags: self.flags(),
+            world_origin: self.world_origin,
+        };
+        let mut duplicate = Scene::try_new(self.device, options)?;
+
+        let attached = self.attached.lock().unwrap().clone();
+        for &geom_id in attached.keys() {
+            let geometry = self.geometry(geom_id)?;
+            duplicate.attach_geometry_by_id(&geometry, geom_id)?;
+        }
+        *duplicate.attached.lock().unwrap() = attached;
+        duplicate.payloads = self.payloads.clone();
+        Ok(duplicate)
+    }
+
+    /// Looks up an attached geometry by its ID.
+    ///
+    /// # Arguments
+    /// * `geom_id` - The geometry ID, e.g. from [`Hit::geom_id`].
+    ///
+    /// # Returns
+    /// A `Result` containing a reference to the geometry, or an error if no geometry with the ID
+    /// is attached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let mut scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// scene.attach_geometry_with_payload(&geometry, "floor").unwrap();
+    /// let committed = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// let hit = committed.intersect_1(ray).unwrap().unwrap();
+    /// let geometry = scene.geometry(hit.geom_id()).unwrap();
+    /// println!("hit {}", geometry.payload::<&str>().unwrap());
+    /// geometry.disable();
+    /// ```
+    pub fn geometry(&self, geom_id: u32) -> Result<GeometryRef<'_>> {
+        self.attached_geometry(geom_id, "Could not get geometry")
+    }
+
+    /// Looks up an attached geometry by its ID, checking the ID before passing it to Embree.
+    fn attached_geometry(&self, geom_id: u32, message: &str) -> Result<GeometryRef<'_>> {
+        // Embree does not check the ID in release builds, so unknown IDs must not reach it
+        if !self.attached.lock().unwrap().contains_key(&geom_id) {
+            bail!(
+                "{} {}: no geometry with this ID is attached",
+                message,
+                geom_id
+            );
+        }
+        let handle = unsafe { embree4_sys::rtcGetGeometry(self.handle, geom_id) };
+        if handle.is_null() {
+            return Err(device_error(
+                self.device,
+                &format!("{} {}", message, geom_id),
+            ));
+        }
+        Ok(GeometryRef {
+            handle,
+            geom_id,
+            payload: self.payloads.get(&geom_id).map(|payload| &**payload),
+        })
+    }
+
+    /// Attaches the given geometry to the scene, and associates a payload with it.
+    ///
+    /// The payload, e.g. a material ID or entity handle, can be retrieved from hits with
+    /// [`CommittedScene::payload`], instead of maintaining a separate map from geometry IDs.
+    ///
+    /// # Arguments
+    /// * `geometry` - A reference to the `Geometry` instance to attach.
+    /// * `payload` - The value to associate with the geometry.
+    ///
+    /// # Returns
+    /// * A `Result` containing the geometry ID if successful, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// struct Material {
+    ///     albedo: (f32, f32, f32),
+    /// }
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    ///
+    /// let mut scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let red = Material { albedo: (0.8, 0.1, 0.1) };
+    /// scene.attach_geometry_with_payload(&geometry, red).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    /// if let Some(hit) = scene.intersect_1(ray).unwrap() {
+    ///     let material = scene.payload::<Material>(&hit).unwrap();
+    ///     println!("albedo: {:?}", material.albedo);
+    /// }
+    /// ```
+    pub fn attach_geometry_with_payload<T: Any + Send + Sync>(
+        &mut self,
+        geometry: &impl Geometry,
+        payload: T,
+    ) -> Result<u32> {
+        let geom_id = self.attach_geometry(geometry)?;
+        self.payloads.insert(geom_id, Arc::new(payload));
+        Ok(geom_id)
+    }
+
+    /// Returns the payload of an attached geometry, see [`Scene::attach_geometry_with_payload`].
+    ///
+    /// # Arguments
+    /// * `geom_id` - The geometry ID returned by [`Scene::attach_geometry_with_payload`].
+    ///
+    /// # Returns
+    /// The payload, or `None` if the geometry has no payload or its payload is not a `T`.
+    pub fn payload<T: Any>(&self, geom_id: u32) -> Option<&T> {
+        self.payloads.get(&geom_id)?.downcast_ref()
+    }
+
+    /// Enables or disables an attached geometry by its ID, see [`Geometry::enable`] and
+    /// [`Geometry::disable`].
+    ///
+    /// The change takes effect on the next commit.
+    ///
+    /// # Arguments
+    /// * `geom_id` - The geometry ID returned by [`Scene::attach_geometry`].
+    /// * `enabled` - Whether rays should hit the geometry.
+    ///
+    /// # Returns
+    /// A `Result` indicating success or failure, e.g. if no geometry with the ID is attached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::default()).unwrap();
+    /// let geom_id = scene.attach_geometry(&geometry).unwrap();
+    ///
+    /// for frame in 0..60 {
+    ///     scene.set_geometry_enabled(geom_id, frame % 2 == 0).unwrap();
+    ///     let committed = scene.commit().unwrap();
+    /// }
+    /// ```
+    pub fn set_geometry_enabled(&self, geom_id: u32, enabled: bool) -> Result<()> {
+        let geometry = self.attached_geometry(geom_id, "Could not enable or disable geometry")?;
+        if enabled {
+            geometry.enable();
+        } else {
+            geometry.disable();
+        }
+        device_error_or(self.device, (), "Could not enable or disable geometry")
     }
 
     /// Commits the scene.
     ///
+    /// A scene can be committed any number of times. To update a scene, drop the
+    /// `CommittedScene`, modify the scene or its geometries, and commit again. Geometries are
+    /// modified through their own handles, e.g. with
+    /// [`TriangleMeshGeometry::update`](crate::geometry::TriangleMeshGeometry::update), so this
+    /// does not require mutable access to the scene. For scenes updated every frame, create the
+    /// scene with [`SceneOptions::dynamic`].
+    ///
     /// # Returns
     /// A `Result` containing the `CommittedScene` instance if successful, or an error if an error occurred.
     ///
@@ -105,18 +706,153 @@ impl<'a> Scene<'a> {
     /// let scene = Scene::try_new(&device, options).unwrap();
     /// let scene = scene.commit().unwrap();
     /// ```
-    pub fn commit(&self) -> Result<CommittedScene> {
+    pub fn commit(&self) -> Result<CommittedScene<'_>> {
         unsafe {
             embree4_sys::rtcCommitScene(self.handle);
         }
+        if let Some(monitor) = &self.progress_monitor {
+            let panic = monitor
+                .panic
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            if let Some(panic) = panic {
+                resume_unwind(panic);
+            }
+        }
+        let panic = self
+            .attached
+            .lock()
+            .unwrap()
+            .values()
+            .flatten()
+            .filter_map(|data| (**data).downcast_ref::<RetainedData>())
+            .find_map(RetainedData::take_panic);
+        if let Some(panic) = panic {
+            resume_unwind(panic);
+        }
         device_error_or(
             self.device,
             CommittedScene { scene: self },
             "Could not commit scene",
         )
     }
+
+    /// Commits the scene after its geometries were deformed in place.
+    ///
+    /// This is a regular commit, which is cheap if the modified geometries have the build
+    /// quality [`RTCBuildQuality::REFIT`](embree4_sys::RTCBuildQuality::REFIT), see
+    /// [`Geometry::set_build_quality`]: their BVHs keep their structure and only the bounds are
+    /// updated, which typically takes a fraction of a full rebuild. Ray tracing performance
+    /// degrades as the deformation moves away from the initial shape, so rebuild occasionally
+    /// for large motions. See the `refit` example for a timing comparison.
+    ///
+    /// # Returns
+    /// A `Result` containing the `CommittedScene` instance if successful, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    /// use embree4_sys::RTCBuildQuality;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// geometry.set_build_quality(RTCBuildQuality::REFIT);
+    /// geometry.commit(&device).unwrap();
+    ///
+    /// let scene = Scene::try_new(&device, SceneOptions::dynamic()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// scene.commit().unwrap();
+    ///
+    /// for frame in 0..60 {
+    ///     // No query of `scene` runs while the vertices are modified
+    ///     for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+    ///         vertex[2] = 1.0 + (frame as f32 * 0.1).sin();
+    ///     }
+    ///     geometry.update(&device).unwrap();
+    ///     let committed = scene.refit().unwrap();
+    /// }
+    /// ```
+    pub fn refit(&self) -> Result<CommittedScene<'_>> {
+        self.commit()
+    }
+}
+
+/// A builder for a [`Scene`], see [`Scene::builder`].
+///
+/// Errors of the individual steps are reported by [`SceneBuilder::build`].
+pub struct SceneBuilder<'a> {
+    /// The scene, or the first error that occurred while building it.
+    scene: Result<Scene<'a>>,
+}
+
+impl<'a> SceneBuilder<'a> {
+    /// Sets the build quality, see [`Scene::set_build_quality`].
+    pub fn build_quality(self, quality: embree4_sys::RTCBuildQuality) -> Self {
+        self.apply(|scene| scene.set_build_quality(quality))
+    }
+
+    /// Sets the scene flags, see [`Scene::set_flags`].
+    pub fn flags(self, flags: SceneFlags) -> Self {
+        self.apply(|scene| scene.set_flags(flags))
+    }
+
+    /// Attaches a geometry, see [`Scene::attach_geometry`].
+    pub fn attach(self, geometry: &impl Geometry) -> Self {
+        self.apply(|scene| scene.attach_geometry(geometry).map(|_| ()))
+    }
+
+    /// Attaches a geometry with a chosen ID, see [`Scene::attach_geometry_by_id`].
+    pub fn attach_with_id(self, geom_id: u32, geometry: &impl Geometry) -> Self {
+        self.apply(|scene| scene.attach_geometry_by_id(geometry, geom_id))
+    }
+
+    /// Commits the scene with all geometries attached.
+    ///
+    /// # Returns
+    /// A `Result` containing the committed scene, or the first error that occurred while
+    /// building or committing it.
+    pub fn build(self) -> Result<BuiltScene<'a>> {
+        let scene = self.scene?;
+        scene.commit()?;
+        Ok(BuiltScene { scene })
+    }
+
+    /// Applies a step to the scene, unless an earlier step failed.
+    fn apply(mut self, step: impl FnOnce(&Scene<'a>) -> Result<()>) -> Self {
+        if let Ok(scene) = &self.scene {
+            if let Err(e) = step(scene) {
+                self.scene = Err(e);
+            }
+        }
+        self
+    }
+}
+
+/// A scene committed by [`SceneBuilder::build`], which stays committed without being borrowed.
+pub struct BuiltScene<'a> {
+    scene: Scene<'a>,
+}
+
+impl<'a> BuiltScene<'a> {
+    /// Returns the committed scene for queries.
+    pub fn committed(&self) -> CommittedScene<'_> {
+        CommittedScene { scene: &self.scene }
+    }
+
+    /// Returns the scene, e.g. to modify and commit it again.
+    pub fn into_scene(self) -> Scene<'a> {
+        self.scene
+    }
 }
 
+// Besides the Embree scene handle, which may be used from any thread, a scene only holds data
+// which is Send + Sync. Embree supports concurrent queries of a committed scene, while commits
+// must not overlap queries, which `SharedScene` and the borrow of `CommittedScene` ensure.
+unsafe impl<'a> Send for Scene<'a> {}
+unsafe impl<'a> Sync for Scene<'a> {}
+
 impl<'a> Drop for Scene<'a> {
     fn drop(&mut self) {
         unsafe {
@@ -125,36 +861,1453 @@ impl<'a> Drop for Scene<'a> {
     }
 }
 
-#[derive(Default)]
-pub struct SceneOptions {
-    pub build_quality: embree4_sys::RTCBuildQuality,
-    pub flags: embree4_sys::RTCSceneFlags,
+/// Forwards Embree's progress monitor calls to the callback of a [`ProgressMonitor`].
+unsafe extern "C" fn progress_monitor_fn(ptr: *mut std::ffi::c_void, progress: f64) -> bool {
+    let monitor = &*(ptr as *const ProgressMonitor);
+    // Unwinding into Embree's C++ frames is undefined, so panics are caught and resumed later
+    match catch_unwind(AssertUnwindSafe(|| (monitor.callback)(progress))) {
+        Ok(proceed) => proceed,
+        Err(panic) => {
+            let mut slot = monitor.panic.lock().unwrap_or_else(|e| e.into_inner());
+            slot.get_or_insert(panic);
+            false
+        }
+    }
 }
 
-pub struct CommittedScene<'a> {
-    scene: &'a Scene<'a>,
+/// A reference to a geometry attached to a scene, see [`Scene::geometry`].
+#[derive(Clone, Copy)]
+pub struct GeometryRef<'s> {
+    handle: embree4_sys::RTCGeometry,
+    geom_id: u32,
+    payload: Option<&'s (dyn Any + Send + Sync)>,
 }
 
-unsafe impl<'a> Sync for CommittedScene<'a> {}
+impl<'s> GeometryRef<'s> {
+    /// Returns the ID of the geometry within the scene.
+    pub fn geom_id(&self) -> u32 {
+        self.geom_id
+    }
 
-impl<'a> CommittedScene<'a> {
-    pub fn intersect_1(&self, ray: embree4_sys::RTCRay) -> Result<Option<embree4_sys::RTCRayHit>> {
-        let mut ray_hit = embree4_sys::RTCRayHit {
-            ray,
-            hit: Default::default(),
-        };
+    /// Returns the payload of the geometry, see [`Scene::attach_geometry_with_payload`].
+    ///
+    /// # Returns
+    /// The payload, or `None` if the geometry has no payload or its payload is not a `T`.
+    pub fn payload<T: Any>(&self) -> Option<&'s T> {
+        self.payload?.downcast_ref()
+    }
+}
+
+impl<'s> Geometry for GeometryRef<'s> {
+    fn geometry(&self) -> embree4_sys::RTCGeometry {
+        self.handle
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SceneOptions {
+    pub build_quality: embree4_sys::RTCBuildQuality,
+    pub flags: SceneFlags,
+    /// The world-space origin of the scene's local `f32` frame.
+    ///
+    /// Geometry is expected to be specified relative to this point (see [`Scene::to_local`]),
+    /// and double-precision rays passed to [`CommittedScene::intersect_1_f64`] are translated
+    /// into this frame before tracing. Defaults to the world origin.
+    pub world_origin: (f64, f64, f64),
+}
+
+impl SceneOptions {
+    /// Returns options for scenes which are modified and committed again every frame.
+    ///
+    /// Sets [`SceneFlags::dynamic`] and a low build quality, trading some ray tracing performance for faster rebuilds.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let mut geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::dynamic()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    ///
+    /// for frame in 0..60 {
+    ///     // No query of `scene` runs while the vertices are modified
+    ///     for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+    ///         vertex[2] = 1.0 + frame as f32 * 0.1;
+    ///     }
+    ///     geometry.update(&device).unwrap();
+    ///
+    ///     let committed = scene.commit().unwrap();
+    ///     let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    ///     let hit = committed.intersect_1(ray).unwrap();
+    /// }
+    /// ```
+    pub fn dynamic() -> Self {
+        Self {
+            build_quality: embree4_sys::RTCBuildQuality::LOW,
+            flags: SceneFlags {
+                dynamic: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// The flags of a [`Scene`], see [`SceneOptions::flags`] and [`Scene::set_flags`].
+///
+/// All flags are disabled by default, which builds the fastest acceleration structure for scenes
+/// that are committed once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SceneFlags {
+    /// Optimizes the scene for frequent re-commits, e.g. of animated geometry. Commits become
+    /// faster, at the cost of slightly slower ray queries.
+    pub dynamic: bool,
+    /// Builds a more compact acceleration structure, which uses less memory at the cost of
+    /// slower ray queries.
+    pub compact: bool,
+    /// Avoids optimizations which may miss hits, e.g. rays passing through shared edges of
+    /// adjacent triangles, at the cost of slower ray queries.
+    pub robust: bool,
+    /// Lets queries pass filter functions in their arguments, which is required by filtered and
+    /// multi-hit queries as well as hit policies (see [`Scene::set_hit_policy`]). Has a small
+    /// cost for all queries of the scene.
+    pub filter_function_in_arguments: bool,
+}
+
+impl From<SceneFlags> for embree4_sys::RTCSceneFlags {
+    fn from(flags: SceneFlags) -> Self {
+        use embree4_sys::RTCSceneFlags as F;
+
+        let mut raw = F::NONE;
+        for (enabled, flag) in [
+            (flags.dynamic, F::DYNAMIC),
+            (flags.compact, F::COMPACT),
+            (flags.robust, F::ROBUST),
+            (
+                flags.filter_function_in_arguments,
+                F::FILTER_FUNCTION_IN_ARGUMENTS,
+            ),
+        ] {
+            if enabled {
+                raw |= flag;
+            }
+        }
+        raw
+    }
+}
+
+impl From<embree4_sys::RTCSceneFlags> for SceneFlags {
+    fn from(raw: embree4_sys::RTCSceneFlags) -> Self {
+        use embree4_sys::RTCSceneFlags as F;
+
+        let has = |flag: F| raw.0 & flag.0 != 0;
+        Self {
+            dynamic: has(F::DYNAMIC),
+            compact: has(F::COMPACT),
+            robust: has(F::ROBUST),
+            filter_function_in_arguments: has(F::FILTER_FUNCTION_IN_ARGUMENTS),
+        }
+    }
+}
+
+/// Options for individual ray queries, see e.g. [`CommittedScene::intersect_1_with_options`].
+///
+/// Embree 4 has no per-query robust traversal mode: robustness is selected per scene with
+/// [`SceneFlags::robust`], as it changes how the scene's
+/// acceleration structure is built. To trace only some rays robustly, attach the same geometry
+/// to a second scene with the robust flag. Geometry buffers are shared between both scenes,
+/// only the acceleration structure is built twice.
+#[derive(Clone, Copy)]
+pub struct QueryOptions {
+    /// The ray query flags. Use [`RTCRayQueryFlags::COHERENT`](embree4_sys::RTCRayQueryFlags::COHERENT)
+    /// for coherent rays, e.g. primary rays, and the default
+    /// [`RTCRayQueryFlags::INCOHERENT`](embree4_sys::RTCRayQueryFlags::INCOHERENT) otherwise.
+    pub flags: embree4_sys::RTCRayQueryFlags,
+    /// The geometry and traversal features the query may encounter. Restricting the mask, e.g.
+    /// to [`RTCFeatureFlags::RTC_FEATURE_FLAG_TRIANGLE`](embree4_sys::RTCFeatureFlags::RTC_FEATURE_FLAG_TRIANGLE)
+    /// for triangle-only scenes, lets Embree skip code paths for unused features. Geometry whose
+    /// feature is not in the mask may be missed. Defaults to all features.
+    pub feature_mask: embree4_sys::RTCFeatureFlags,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            flags: embree4_sys::RTCRayQueryFlags::INCOHERENT,
+            feature_mask: embree4_sys::RTCFeatureFlags::RTC_FEATURE_FLAG_ALL,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Builds the intersect arguments, equivalent to `rtcInitIntersectArguments` with the
+    /// options applied.
+    pub(crate) fn intersect_arguments(&self) -> embree4_sys::RTCIntersectArguments {
+        embree4_sys::RTCIntersectArguments {
+            flags: self.flags,
+            feature_mask: self.feature_mask,
+            context: std::ptr::null_mut(),
+            filter: None,
+            intersect: None,
+        }
+    }
+
+    /// Builds the occluded arguments, equivalent to `rtcInitOccludedArguments` with the
+    /// options applied.
+    pub(crate) fn occluded_arguments(&self) -> embree4_sys::RTCOccludedArguments {
+        embree4_sys::RTCOccludedArguments {
+            flags: self.flags,
+            feature_mask: self.feature_mask,
+            context: std::ptr::null_mut(),
+            filter: None,
+            occluded: None,
+        }
+    }
+}
+
+pub struct CommittedScene<'a> {
+    pub(crate) scene: &'a Scene<'a>,
+}
+
+/// The number of warm-up rays traced per axis and dimension, see [`CommittedScene::warmup`].
+const WARMUP_RESOLUTION: usize = 32;
+
+impl<'a> CommittedScene<'a> {
+    /// Returns the payload of the geometry a hit belongs to, see
+    /// [`Scene::attach_geometry_with_payload`].
+    ///
+    /// For hits on instanced geometry, the payload of the outermost instance is returned, as
+    /// the IDs of the instanced geometries refer to the instanced scenes.
+    ///
+    /// # Arguments
+    /// * `hit` - A hit found in this scene.
+    ///
+    /// # Returns
+    /// The payload, or `None` if the geometry has no payload or its payload is not a `T`.
+    pub fn payload<T: Any>(&self, hit: &Hit) -> Option<&'a T> {
+        self.scene.payload(hit.inst_id().unwrap_or(hit.geom_id()))
+    }
+
+    /// Looks up an attached geometry by its ID, see [`Scene::geometry`].
+    pub fn geometry(&self, geom_id: u32) -> Result<GeometryRef<'a>> {
+        self.scene.geometry(geom_id)
+    }
+
+    /// Returns the axis-aligned bounding box of the scene.
+    ///
+    /// # Returns
+    /// A `Result` containing the scene bounds if successful, or an error if an error occurred.
+    pub fn bounds(&self) -> Result<embree4_sys::RTCBounds> {
+        let mut bounds = embree4_sys::RTCBounds {
+            lower_x: 0.0,
+            lower_y: 0.0,
+            lower_z: 0.0,
+            align0: 0.0,
+            upper_x: 0.0,
+            upper_y: 0.0,
+            upper_z: 0.0,
+            align1: 0.0,
+        };
+        unsafe {
+            embree4_sys::rtcGetSceneBounds(self.scene.handle, &mut bounds);
+        }
+        device_error_or(self.scene.device, bounds, "Could not get scene bounds")
+    }
+
+    /// Returns the linear bounds of the scene over the ray time range `[0, 1]`.
+    ///
+    /// The scene is contained in the linear interpolation of `bounds0` at time 0 and `bounds1`
+    /// at time 1, e.g. to compute screen-space bounds of motion-blurred geometry at shutter open
+    /// and close.
+    ///
+    /// # Returns
+    /// A `Result` containing the linear scene bounds if successful, or an error if an error
+    /// occurred.
+    pub fn linear_bounds(&self) -> Result<embree4_sys::RTCLinearBounds> {
+        let empty = embree4_sys::RTCBounds {
+            lower_x: 0.0,
+            lower_y: 0.0,
+            lower_z: 0.0,
+            align0: 0.0,
+            upper_x: 0.0,
+            upper_y: 0.0,
+            upper_z: 0.0,
+            align1: 0.0,
+        };
+        let mut bounds = embree4_sys::RTCLinearBounds {
+            bounds0: empty,
+            bounds1: empty,
+        };
+        unsafe {
+            embree4_sys::rtcGetSceneLinearBounds(self.scene.handle, &mut bounds);
+        }
+        device_error_or(
+            self.scene.device,
+            bounds,
+            "Could not get scene linear bounds",
+        )
+    }
+
+    /// Warms up the scene by tracing a sparse grid of rays through its bounds along each axis.
+    ///
+    /// After committing a large scene, the BVH and geometry buffers are not yet paged in, so the
+    /// first queries are dominated by page faults. Calling this once after commit moves that cost
+    /// out of latency-sensitive code paths such as the first frame of an interactive application.
+    ///
+    /// # Returns
+    /// A `Result` indicating success or failure.
+    ///
+    /// # Example
+    /// ```
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    /// let geometry = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// scene.attach_geometry(&geometry).unwrap();
+    /// let scene = scene.commit().unwrap();
+    /// scene.warmup().unwrap();
+    /// ```
+    pub fn warmup(&self) -> Result<()> {
+        let bounds = self.bounds()?;
+        let lower = [bounds.lower_x, bounds.lower_y, bounds.lower_z];
+        let upper = [bounds.upper_x, bounds.upper_y, bounds.upper_z];
+
+        // empty scenes have inverted bounds
+        if (0..3).any(|i| lower[i] > upper[i]) {
+            return Ok(());
+        }
+
+        for axis in 0..3 {
+            let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            for i in 0..WARMUP_RESOLUTION {
+                for j in 0..WARMUP_RESOLUTION {
+                    let s = (i as f32 + 0.5) / WARMUP_RESOLUTION as f32;
+                    let t = (j as f32 + 0.5) / WARMUP_RESOLUTION as f32;
+
+                    let mut org = lower;
+                    org[u_axis] += s * (upper[u_axis] - lower[u_axis]);
+                    org[v_axis] += t * (upper[v_axis] - lower[v_axis]);
+                    let mut dir = [0.0; 3];
+                    dir[axis] = 1.0;
+
+                    let mut ray_hit = embree4_sys::RTCRayHit {
+                        ray: embree4_sys::RTCRay {
+                            org_x: org[0],
+                            org_y: org[1],
+                            org_z: org[2],
+                            dir_x: dir[0],
+                            dir_y: dir[1],
+                            dir_z: dir[2],
+                            ..Default::default()
+                        },
+                        hit: Default::default(),
+                    };
+                    unsafe {
+                        embree4_sys::rtcIntersect1(
+                            self.scene.handle,
+                            &mut ray_hit,
+                            std::ptr::null_mut(),
+                        );
+                    }
+                }
+            }
+        }
+
+        device_error_or(self.scene.device, (), "Could not warm up scene")
+    }
+
+    /// Intersects a double-precision world-space ray with the scene.
+    ///
+    /// The ray origin is translated into the scene's local frame in double precision before
+    /// being narrowed to `f32`, which avoids catastrophic precision loss when both the ray and
+    /// the scene's world origin are far away from the world origin.
+    ///
+    /// Since the translation does not scale the ray, the returned hit distance `tfar` is valid
+    /// in world space as well: the world-space hit point is `origin + tfar * direction`.
+    ///
+    /// # Arguments
+    /// * `origin` - The world-space ray origin.
+    /// * `direction` - The ray direction.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit in the scene's local frame, if any, or an error if an error
+    /// occurred.
+    pub fn intersect_1_f64(
+        &self,
+        origin: (f64, f64, f64),
+        direction: (f64, f64, f64),
+    ) -> Result<Option<Hit>> {
+        let origin = self.scene.to_local(origin);
+        let ray = embree4_sys::RTCRay {
+            org_x: origin.0,
+            org_y: origin.1,
+            org_z: origin.2,
+            dir_x: direction.0 as f32,
+            dir_y: direction.1 as f32,
+            dir_z: direction.2 as f32,
+            ..Default::default()
+        };
+        self.intersect_1(ray)
+    }
+
+    pub fn intersect_1(&self, ray: impl Into<embree4_sys::RTCRay>) -> Result<Option<Hit>> {
+        self.intersect_1_with_options(ray, &Default::default())
+    }
+
+    /// Intersects a single ray with the scene, using the given query options.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    /// * `options` - The query options, e.g. the coherency hint.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit, if any, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::{RTCFeatureFlags, RTCRay, RTCRayQueryFlags};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let options = QueryOptions {
+    ///     flags: RTCRayQueryFlags::COHERENT,
+    ///     feature_mask: RTCFeatureFlags::RTC_FEATURE_FLAG_TRIANGLE,
+    /// };
+    /// let ray = RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// let hit = scene.intersect_1_with_options(ray, &options).unwrap();
+    /// ```
+    pub fn intersect_1_with_options(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        options: &QueryOptions,
+    ) -> Result<Option<Hit>> {
+        self.intersect_1_with_context(ray, &mut RayQueryContext::new(), options)
+    }
+
+    /// Intersects a single ray with the scene, passing `context` through traversal.
+    ///
+    /// User geometry callbacks receive the context, including the instance ID stack maintained
+    /// by Embree. See [`CommittedScene::intersect_1_with_options`].
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    /// * `context` - The ray query context.
+    /// * `options` - The query options.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit, if any, or an error if an error occurred.
+    pub fn intersect_1_with_context(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        context: &mut RayQueryContext,
+        options: &QueryOptions,
+    ) -> Result<Option<Hit>> {
+        let ray = ray.into();
+        validate_ray(&ray)?;
+        if self.scene.hit_policy.is_some() {
+            return self.intersect_1_filtered_with_context(ray, context, options, |_| {
+                FilterDecision::Accept
+            });
+        }
+
+        let mut args = options.intersect_arguments();
+        args.context = context.raw_mut();
+        let mut ray_hit = embree4_sys::RTCRayHit {
+            ray,
+            hit: Default::default(),
+        };
+
+        unsafe {
+            embree4_sys::rtcIntersect1(self.scene.handle, &mut ray_hit, &mut args);
+        }
+        device_error_or(self.scene.device, (), "Could not intersect ray")?;
+
+        Ok(Hit::from_ray_hit(ray_hit))
+    }
+
+    /// Intersects a single ray with the scene without checking the device for errors.
+    ///
+    /// Querying the device error state after every ray is a synchronization point, which is
+    /// measurable in hot loops. Use this variant there, and check [`Device::error`] once
+    /// afterwards. If an error occurs, the returned hit is unspecified. Unlike
+    /// [`CommittedScene::intersect_1`], the hit policy of the scene is not applied.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    ///
+    /// # Returns
+    /// The hit, if any.
+    ///
+    /// # Panics
+    /// In debug builds, panics if the ray is invalid, e.g. has a NaN origin or a zero direction.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let hits = (0..1024)
+    ///     .filter_map(|i| {
+    ///         let ray = Ray::new((i as f32, 0.0, 0.0), (0.0, 0.0, 1.0));
+    ///         scene.intersect_1_unchecked(ray)
+    ///     })
+    ///     .count();
+    /// assert!(device.error().is_none());
+    /// ```
+    pub fn intersect_1_unchecked(&self, ray: impl Into<embree4_sys::RTCRay>) -> Option<Hit> {
+        let ray = ray.into();
+        if let Err(e) = validate_ray(&ray) {
+            panic!("{}", e);
+        }
+        let mut ray_hit = embree4_sys::RTCRayHit {
+            ray,
+            hit: Default::default(),
+        };
 
         unsafe {
             embree4_sys::rtcIntersect1(self.scene.handle, &mut ray_hit, std::ptr::null_mut());
         }
+
+        Hit::from_ray_hit(ray_hit)
+    }
+
+    /// Intersects the ray of a caller-owned ray hit with the scene, writing the result in place.
+    ///
+    /// Reusing a single `RTCRayHit` across queries avoids constructing a new ray hit per ray.
+    /// The hit part is reset before tracing, so only the ray needs to be updated between calls.
+    ///
+    /// # Arguments
+    /// * `ray_hit` - The ray hit. On return, `ray_hit.ray.tfar` holds the hit distance and
+    ///   `ray_hit.hit` the hit data, if the ray hit any geometry.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` if the ray hit any geometry, or an error if an error
+    /// occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::RTCRayHit;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let mut ray_hit = RTCRayHit {
+    ///     ray: Default::default(),
+    ///     hit: Default::default(),
+    /// };
+    /// for i in 0..1024 {
+    ///     ray_hit.ray = Ray::new((i as f32, 0.0, 0.0), (0.0, 0.0, 1.0)).into();
+    ///     if scene.intersect_into(&mut ray_hit).unwrap() {
+    ///         println!("hit primitive {}", ray_hit.hit.primID);
+    ///     }
+    /// }
+    /// ```
+    pub fn intersect_into(&self, ray_hit: &mut embree4_sys::RTCRayHit) -> Result<bool> {
+        validate_ray(&ray_hit.ray)?;
+        ray_hit.hit.geomID = embree4_sys::RTC_INVALID_GEOMETRY_ID;
+        ray_hit.hit.instID = [embree4_sys::RTC_INVALID_GEOMETRY_ID;
+            embree4_sys::RTC_MAX_INSTANCE_LEVEL_COUNT as usize];
+
+        unsafe {
+            embree4_sys::rtcIntersect1(self.scene.handle, ray_hit, std::ptr::null_mut());
+        }
+        device_error_or(self.scene.device, (), "Could not intersect ray")?;
+
+        Ok(ray_hit.hit.geomID != embree4_sys::RTC_INVALID_GEOMETRY_ID)
+    }
+
+    /// Intersects a single ray with the scene, returning only the hit distance.
+    ///
+    /// Skips building a [`Hit`], for depth maps and range sensors that need nothing but the
+    /// distance. See [`CommittedScene::intersect_distance_with_options`] to restrict the
+    /// feature mask to the geometry types in the scene.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    ///
+    /// # Returns
+    /// A `Result` containing the hit distance in multiples of the ray direction's length, if the
+    /// ray hit any geometry, or an error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let depth = scene
+    ///     .intersect_distance(Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+    ///     .unwrap()
+    ///     .unwrap_or(f32::INFINITY);
+    /// ```
+    pub fn intersect_distance(&self, ray: impl Into<embree4_sys::RTCRay>) -> Result<Option<f32>> {
+        self.intersect_distance_with_options(ray, &Default::default())
+    }
+
+    /// Intersects a single ray with the scene, returning only the hit distance, using the given
+    /// query options.
+    ///
+    /// For maximum throughput, restrict [`QueryOptions::feature_mask`] to the features of the
+    /// scene, e.g. triangles only. See [`CommittedScene::intersect_distance`].
+    pub fn intersect_distance_with_options(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        options: &QueryOptions,
+    ) -> Result<Option<f32>> {
+        if self.scene.hit_policy.is_some() {
+            let hit = self.intersect_1_with_options(ray, options)?;
+            return Ok(hit.map(|hit| hit.t()));
+        }
+
+        let ray = ray.into();
+        validate_ray(&ray)?;
+        let mut args = options.intersect_arguments();
+        let mut ray_hit = embree4_sys::RTCRayHit {
+            ray,
+            hit: Default::default(),
+        };
+
+        unsafe {
+            embree4_sys::rtcIntersect1(self.scene.handle, &mut ray_hit, &mut args);
+        }
         device_error_or(self.scene.device, (), "Could not intersect ray")?;
 
         Ok(
-            if ray_hit.hit.geomID != embree4_sys::RTC_INVALID_GEOMETRY_ID {
-                Some(ray_hit)
-            } else {
-                None
-            },
+            (ray_hit.hit.geomID != embree4_sys::RTC_INVALID_GEOMETRY_ID)
+                .then_some(ray_hit.ray.tfar),
         )
     }
+
+    /// Checks whether the given ray is occluded by any geometry in the scene.
+    ///
+    /// Occlusion queries terminate at the first hit found and do not compute any hit data,
+    /// which makes them cheaper than [`CommittedScene::intersect_1`] for shadow rays.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to test. Only hits between `tnear` and `tfar` count as occluding.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` if the ray is occluded, or an error if an error occurred.
+    pub fn occluded_1(&self, ray: impl Into<embree4_sys::RTCRay>) -> Result<bool> {
+        self.occluded_1_with_options(ray, &Default::default())
+    }
+
+    /// Checks whether the given ray is occluded, using the given query options.
+    ///
+    /// See [`CommittedScene::occluded_1`].
+    pub fn occluded_1_with_options(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        options: &QueryOptions,
+    ) -> Result<bool> {
+        self.occluded_1_with_context(ray, &mut RayQueryContext::new(), options)
+    }
+
+    /// Checks whether the given ray is occluded, passing `context` through traversal.
+    ///
+    /// See [`CommittedScene::intersect_1_with_context`].
+    pub fn occluded_1_with_context(
+        &self,
+        ray: impl Into<embree4_sys::RTCRay>,
+        context: &mut RayQueryContext,
+        options: &QueryOptions,
+    ) -> Result<bool> {
+        let mut ray = ray.into();
+        validate_ray(&ray)?;
+        if self.scene.hit_policy.is_some() {
+            return self.occluded_1_filtered_with_context(ray, context, options, |_| {
+                FilterDecision::Accept
+            });
+        }
+
+        let mut args = options.occluded_arguments();
+        args.context = context.raw_mut();
+
+        unsafe {
+            embree4_sys::rtcOccluded1(self.scene.handle, &mut ray, &mut args);
+        }
+        device_error_or(self.scene.device, (), "Could not test ray occlusion")?;
+
+        // Embree sets tfar to -inf for occluded rays
+        Ok(ray.tfar == f32::NEG_INFINITY)
+    }
+
+    /// Intersects many rays with the scene, writing the hits to a caller-owned slice.
+    ///
+    /// Each ray is traced like [`CommittedScene::intersect_1`], i.e. validated and filtered by
+    /// the hit policy. The rays are validated and traced in chunks, checking the device for
+    /// errors once per chunk. Reusing the output slice avoids allocating results for every
+    /// batch.
+    ///
+    /// # Arguments
+    /// * `rays` - The rays to intersect.
+    /// * `hits` - The output slice, receiving the hit for each ray. Must have the same length
+    ///   as `rays`.
+    /// * `cancel` - An optional cancellation token, checked before each chunk.
+    ///
+    /// # Returns
+    /// A `Result` containing whether all rays were traced, or an error if an error occurred. If
+    /// the batch was cancelled, the hits of the rays which were not traced are left unchanged.
+    pub fn intersect_slice(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        hits: &mut [Option<Hit>],
+        cancel: Option<&AtomicBool>,
+    ) -> Result<BatchStatus> {
+        self.intersect_slice_with_options(rays, hits, cancel, &Default::default())
+    }
+
+    /// Intersects many rays with the scene in a single call, using the given query options.
+    ///
+    /// Pass [`RTCRayQueryFlags::COHERENT`](embree4_sys::RTCRayQueryFlags::COHERENT) for batches of
+    /// coherent rays, e.g. camera rays in tile order. See [`CommittedScene::intersect_slice`].
+    pub fn intersect_slice_with_options(
+        &self,
+        rays: &[embree4_sys::RTCRay],
+        hits: &mut [Option<Hit>],
+        cancel: Option<&AtomicBool>,
+        options: &QueryOptions,
+    ) -> Result<BatchStatus> {
+        if rays.len() != hits.len() {
+            bail!(
+                "Could not intersect rays: expected {} hit slots, got {}",
+                rays.len(),
+                hits.len()
+            );
+        }
+
+        let chunks = rays
+            .chunks(CANCEL_CHUNK_SIZE)
+            .zip(hits.chunks_mut(CANCEL_CHUNK_SIZE));
+        for (rays, hits) in chunks {
+            if is_cancelled(cancel) {
+                return Ok(BatchStatus::Cancelled);
+            }
+
+            // Hit policies are evaluated through a filtered query per ray
+            if self.scene.hit_policy.is_some() {
+                for (ray, hit) in rays.iter().zip(hits) {
+                    *hit = self.intersect_1_with_options(*ray, options)?;
+                }
+                continue;
+            }
+
+            for ray in rays {
+                validate_ray(ray)?;
+            }
+            let mut context = RayQueryContext::new();
+            let mut args = options.intersect_arguments();
+            args.context = context.raw_mut();
+            for (ray, hit) in rays.iter().zip(hits) {
+                let mut ray_hit = embree4_sys::RTCRayHit {
+                    ray: *ray,
+                    hit: Default::default(),
+                };
+                unsafe {
+                    embree4_sys::rtcIntersect1(self.scene.handle, &mut ray_hit, &mut args);
+                }
+                *hit = Hit::from_ray_hit(ray_hit);
+            }
+            device_error_or(self.scene.device, (), "Could not intersect rays")?;
+        }
+        Ok(BatchStatus::Completed)
+    }
+}
+
+#[test]
+fn to_local_relative_to_world_origin() {
+    let device = Device::try_new(None).unwrap();
+    let options = SceneOptions {
+        world_origin: (6_378_137.0, 0.0, -1.0e7),
+        ..Default::default()
+    };
+    let scene = Scene::try_new(&device, options).unwrap();
+    assert_eq!(
+        scene.to_local((6_378_137.25, 0.5, -1.0e7 - 0.125)),
+        (0.25, 0.5, -0.125)
+    );
+}
+
+#[test]
+fn intersect_1_f64_keeps_precision_far_from_origin() {
+    let device = Device::try_new(None).unwrap();
+    let options = SceneOptions {
+        world_origin: (6_378_137.0, 0.0, 0.0),
+        ..Default::default()
+    };
+    let scene = Scene::try_new(&device, options).unwrap();
+    // Narrower than the f32 spacing of 0.5 at this distance from the world origin
+    let vertices = [
+        (6_378_137.2, -1.0, 1.0),
+        (6_378_137.4, -1.0, 1.0),
+        (6_378_137.3, 1.0, 1.0),
+    ];
+    assert_eq!(6_378_137.3_f64 as f32, 6_378_137.5);
+    let geometry = crate::geometry::TriangleMeshGeometry::try_new_f64(
+        &device,
+        &vertices,
+        &[(0, 1, 2)],
+        scene.world_origin(),
+    )
+    .unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let hit = scene
+        .intersect_1_f64((6_378_137.3, -0.5, -10.0), (0.0, 0.0, 1.0))
+        .unwrap()
+        .unwrap();
+    assert!((hit.t() - 11.0).abs() < 1e-5);
+    let point = hit.hit_point();
+    assert!((point.0 - 0.3).abs() < 1e-5 && (point.1 + 0.5).abs() < 1e-5);
+
+    let beside = scene
+        .intersect_1_f64((6_378_137.45, -0.5, -10.0), (0.0, 0.0, 1.0))
+        .unwrap();
+    assert!(beside.is_none());
+}
+
+#[test]
+fn occluded_1_empty_scene() {
+    let device = Device::try_new(None).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+    let ray = embree4_sys::RTCRay {
+        dir_z: 1.0,
+        ..Default::default()
+    };
+    assert!(!scene.occluded_1(ray).unwrap());
+}
+
+#[test]
+fn intersect_slice_length_mismatch() {
+    let device = Device::try_new(None).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+    let rays = [embree4_sys::RTCRay::default(); 2];
+    let mut hits = [None; 3];
+    assert!(scene.intersect_slice(&rays, &mut hits, None).is_err());
+}
+
+#[test]
+fn intersect_slice_cancelled() {
+    use crate::batch::CANCEL_CHUNK_SIZE;
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+    // Alternating rays towards and away from the triangle
+    let rays: Vec<embree4_sys::RTCRay> = (0..2 * CANCEL_CHUNK_SIZE)
+        .map(|i| {
+            let dir_z = if i % 2 == 0 { 1.0 } else { -1.0 };
+            crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, dir_z)).into()
+        })
+        .collect();
+
+    let mut hits = vec![None; rays.len()];
+    let status = scene.intersect_slice(&rays, &mut hits, None).unwrap();
+    assert_eq!(status, BatchStatus::Completed);
+    for (i, hit) in hits.iter().enumerate() {
+        assert_eq!(hit.map(|hit| hit.t()), (i % 2 == 0).then_some(1.0));
+    }
+
+    let mut hits = vec![None; rays.len()];
+    let cancel = AtomicBool::new(true);
+    let status = scene
+        .intersect_slice(&rays, &mut hits, Some(&cancel))
+        .unwrap();
+    assert_eq!(status, BatchStatus::Cancelled);
+    assert!(hits.iter().all(Option::is_none));
+
+    // Rays are only validated in debug builds
+    if cfg!(debug_assertions) {
+        let mut rays = rays;
+        rays[CANCEL_CHUNK_SIZE + 1].dir_z = 0.0;
+        let mut hits = vec![None; rays.len()];
+        assert!(scene.intersect_slice(&rays, &mut hits, None).is_err());
+    }
+}
+
+#[test]
+fn intersect_1_unchecked_matches_checked() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    for dir_z in [1.0, -1.0] {
+        let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, dir_z));
+        let checked = scene.intersect_1(ray).unwrap().map(|hit| hit.t());
+        let unchecked = scene.intersect_1_unchecked(ray).map(|hit| hit.t());
+        assert_eq!(checked, unchecked);
+    }
+    assert!(device.error().is_none());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Invalid ray")]
+fn intersect_1_unchecked_validates_ray() {
+    let device = Device::try_new(None).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+    scene.intersect_1_unchecked(crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 0.0)));
+}
+
+#[test]
+fn intersect_into_resets_hit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let mut ray_hit = embree4_sys::RTCRayHit {
+        ray: crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)).into(),
+        hit: Default::default(),
+    };
+    assert!(scene.intersect_into(&mut ray_hit).unwrap());
+    assert_eq!(ray_hit.ray.tfar, 1.0);
+
+    ray_hit.ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)).into();
+    assert!(!scene.intersect_into(&mut ray_hit).unwrap());
+    assert_eq!(ray_hit.hit.geomID, embree4_sys::RTC_INVALID_GEOMETRY_ID);
+}
+
+#[test]
+fn hit_policy_rejects_and_terminates() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 2.0),
+        (1.0, -1.0, 2.0),
+        (0.0, 1.0, 2.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    scene
+        .set_hit_policy(|hit: &Hit| {
+            if hit.t() < 1.5 {
+                FilterDecision::Reject
+            } else {
+                FilterDecision::Accept
+            }
+        })
+        .unwrap();
+    let committed = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = committed.intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.prim_id(), 1);
+    assert!(committed.occluded_1(ray).unwrap());
+    assert!(!committed.occluded_1(ray.with_tfar(1.5)).unwrap());
+}
+
+#[test]
+fn intersect_distance_matches_hit() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 2.0));
+    assert_eq!(scene.intersect_distance(ray).unwrap(), Some(0.5));
+    let options = QueryOptions {
+        feature_mask: embree4_sys::RTCFeatureFlags::RTC_FEATURE_FLAG_TRIANGLE,
+        ..Default::default()
+    };
+    assert_eq!(
+        scene
+            .intersect_distance_with_options(ray.with_tfar(0.25), &options)
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn disabled_geometry_is_ignored() {
+    use crate::geometry::Geometry;
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    let geom_id = scene.attach_geometry(&geometry).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+
+    scene.set_geometry_enabled(geom_id, false).unwrap();
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_none());
+    geometry.enable();
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_some());
+    geometry.disable();
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_none());
+    assert!(scene.set_geometry_enabled(geom_id + 1, true).is_err());
+    scene.detach_geometry(geom_id).unwrap();
+    assert!(scene.set_geometry_enabled(geom_id, true).is_err());
+}
+
+#[test]
+fn payloads_by_geometry() {
+    let device = Device::try_new(None).unwrap();
+    let near = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let far = near.map(|(x, y, z)| (x + 4.0, y, z));
+    let near =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &near, &[(0, 1, 2)]).unwrap();
+    let far = crate::geometry::TriangleMeshGeometry::try_new(&device, &far, &[(0, 1, 2)]).unwrap();
+
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    let near_id = scene.attach_geometry_with_payload(&near, "near").unwrap();
+    scene.attach_geometry_with_payload(&far, 7u32).unwrap();
+    assert_eq!(scene.payload::<&str>(near_id), Some(&"near"));
+    assert_eq!(scene.payload::<u32>(near_id), None);
+    let scene = scene.commit().unwrap();
+
+    let hit = |x| {
+        let ray = crate::Ray::new((x, 0.0, 0.0), (0.0, 0.0, 1.0));
+        scene.intersect_1(ray).unwrap().unwrap()
+    };
+    assert_eq!(scene.payload::<&str>(&hit(0.0)), Some(&"near"));
+    assert_eq!(scene.payload::<u32>(&hit(4.0)), Some(&7));
+}
+
+#[test]
+fn detached_geometry_is_removed() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    let geom_id = scene.attach_geometry_with_payload(&geometry, 1u32).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_some());
+
+    scene.detach_geometry(geom_id).unwrap();
+    assert_eq!(scene.payload::<u32>(geom_id), None);
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_none());
+    assert!(scene.detach_geometry(geom_id).is_err());
+}
+
+#[test]
+fn attach_geometry_by_id_keeps_id() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry_by_id(&geometry, 5).unwrap();
+    assert!(scene.attach_geometry_by_id(&geometry, 5).is_err());
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(scene.intersect_1(ray).unwrap().unwrap().geom_id(), 5);
+}
+
+#[test]
+fn geometry_lookup_by_id() {
+    use crate::geometry::Geometry;
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    let geom_id = scene
+        .attach_geometry_with_payload(&geometry, "mesh")
+        .unwrap();
+    assert!(scene.geometry(geom_id + 1).is_err());
+    assert!(scene.geometry(u32::MAX - 1).is_err());
+
+    let found = scene.geometry(geom_id).unwrap();
+    assert_eq!(found.geometry(), geometry.geometry());
+    assert_eq!(found.geom_id(), geom_id);
+    assert_eq!(found.payload::<&str>(), Some(&"mesh"));
+
+    let committed = scene.commit().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = committed.intersect_1(ray).unwrap().unwrap();
+    let found = committed.geometry(hit.geom_id()).unwrap();
+    assert_eq!(found.geometry(), geometry.geometry());
+}
+
+#[test]
+fn bounds_and_warmup_of_known_mesh() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -2.0, 1.0), (3.0, -2.0, 1.0), (0.0, 4.0, 5.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let bounds = scene.bounds().unwrap();
+    for (value, expected) in [
+        (bounds.lower_x, -1.0),
+        (bounds.lower_y, -2.0),
+        (bounds.lower_z, 1.0),
+        (bounds.upper_x, 3.0),
+        (bounds.upper_y, 4.0),
+        (bounds.upper_z, 5.0),
+    ] {
+        assert!((value - expected).abs() < 1e-4);
+    }
+
+    scene.warmup().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.intersect_1(ray).unwrap().is_some());
+
+    // Empty scenes have inverted bounds, which warmup skips
+    let empty = Scene::try_new(&device, Default::default()).unwrap();
+    let empty = empty.commit().unwrap();
+    let bounds = empty.bounds().unwrap();
+    assert!(bounds.lower_x > bounds.upper_x);
+    empty.warmup().unwrap();
+}
+
+#[test]
+fn linear_bounds_follow_motion() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry.set_time_step_count(&device, 2).unwrap();
+    geometry
+        .set_vertices(&device, 1, &vertices.map(|(x, y, z)| (x, y, z + 2.0)))
+        .unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let bounds = scene.linear_bounds().unwrap();
+    for (z, expected) in [
+        (bounds.bounds0.lower_z, 1.0),
+        (bounds.bounds0.upper_z, 1.0),
+        (bounds.bounds1.lower_z, 3.0),
+        (bounds.bounds1.upper_z, 3.0),
+    ] {
+        assert!((z - expected).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn progress_monitor_reports_and_cancels() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let device = Device::try_new(None).unwrap();
+    // Enough triangles for the builder to report progress
+    let vertices: Vec<_> = (0..4096)
+        .map(|i| {
+            let corner = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)][i % 3];
+            ((i / 3) as f32 + corner.0, corner.1, 1.0)
+        })
+        .collect();
+    let indices: Vec<_> = (0..4096 / 3)
+        .map(|i| (3 * i, 3 * i + 1, 3 * i + 2))
+        .collect();
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &indices).unwrap();
+
+    let called = AtomicBool::new(false);
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    scene.set_progress_monitor(|progress| {
+        assert!((0.0..=1.0).contains(&progress));
+        called.store(true, Ordering::Relaxed);
+        true
+    });
+    scene.commit().unwrap();
+    assert!(called.load(Ordering::Relaxed));
+
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    scene.set_progress_monitor(|_| false);
+    assert!(scene.commit().is_err());
+}
+
+#[test]
+fn dynamic_scene_recommits_per_frame() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let mut scene = Scene::try_new(&device, SceneOptions::dynamic()).unwrap();
+    let geom_id = scene.attach_geometry(&geometry).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+
+    for frame in 1..4 {
+        for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+            vertex[2] = frame as f32;
+        }
+        geometry.update(&device).unwrap();
+        let committed = scene.commit().unwrap();
+        assert_eq!(
+            committed.intersect_1(ray).unwrap().unwrap().t(),
+            frame as f32
+        );
+    }
+
+    scene.detach_geometry(geom_id).unwrap();
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_none());
+}
+
+#[test]
+fn refit_follows_deformation() {
+    use crate::geometry::Geometry;
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let mut geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    geometry.set_build_quality(embree4_sys::RTCBuildQuality::REFIT);
+    geometry.commit(&device).unwrap();
+    assert!(device.error().is_none());
+
+    let scene = Scene::try_new(&device, SceneOptions::dynamic()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(
+        scene
+            .commit()
+            .unwrap()
+            .intersect_1(ray)
+            .unwrap()
+            .unwrap()
+            .t(),
+        1.0
+    );
+
+    for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+        vertex[2] = 5.0;
+    }
+    geometry.update(&device).unwrap();
+    assert_eq!(
+        scene
+            .refit()
+            .unwrap()
+            .intersect_1(ray)
+            .unwrap()
+            .unwrap()
+            .t(),
+        5.0
+    );
+}
+
+#[test]
+fn owned_geometry_outlives_caller() {
+    let device = Device::try_new(None).unwrap();
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    let geom_id = {
+        let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+        let geometry =
+            crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)])
+                .unwrap();
+        scene.attach_geometry_owned(geometry).unwrap()
+    };
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(
+        scene
+            .commit()
+            .unwrap()
+            .intersect_1(ray)
+            .unwrap()
+            .unwrap()
+            .t(),
+        1.0
+    );
+
+    assert!(scene
+        .owned_geometry_mut::<crate::geometry::QuadMeshGeometry>(geom_id)
+        .is_none());
+    let geometry = scene
+        .owned_geometry_mut::<crate::geometry::TriangleMeshGeometry>(geom_id)
+        .unwrap();
+    for vertex in unsafe { geometry.vertices_mut(&device, 0) }.unwrap() {
+        vertex[2] = 2.0;
+    }
+    geometry.update(&device).unwrap();
+    assert_eq!(
+        scene
+            .commit()
+            .unwrap()
+            .intersect_1(ray)
+            .unwrap()
+            .unwrap()
+            .t(),
+        2.0
+    );
+
+    scene.detach_geometry(geom_id).unwrap();
+    assert!(scene
+        .owned_geometry_mut::<crate::geometry::TriangleMeshGeometry>(geom_id)
+        .is_none());
+}
+
+#[test]
+fn scene_builder_attaches_geometries() {
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let near =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let far = crate::geometry::TriangleMeshGeometry::try_new(
+        &device,
+        &vertices.map(|(x, y, z)| (x, y, z + 1.0)),
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+
+    let invalid = Scene::builder(&device)
+        .build_quality(embree4_sys::RTCBuildQuality::REFIT)
+        .attach(&near)
+        .build();
+    assert!(invalid.is_err());
+
+    let scene = Scene::builder(&device)
+        .flags(SceneFlags {
+            robust: true,
+            ..Default::default()
+        })
+        .attach_with_id(3, &far)
+        .attach_with_id(5, &near)
+        .build()
+        .unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.committed().intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.geom_id(), 5);
+
+    let mut scene = scene.into_scene();
+    scene.detach_geometry(5).unwrap();
+    let scene = scene.commit().unwrap();
+    assert_eq!(scene.intersect_1(ray).unwrap().unwrap().geom_id(), 3);
+}
+
+#[test]
+fn scene_flags_round_trip() {
+    let device = Device::try_new(None).unwrap();
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    assert_eq!(scene.flags(), SceneFlags::default());
+
+    let flags = SceneFlags {
+        compact: true,
+        filter_function_in_arguments: true,
+        ..Default::default()
+    };
+    scene.set_flags(flags).unwrap();
+    assert_eq!(scene.flags(), flags);
+    assert_eq!(
+        embree4_sys::RTCSceneFlags::from(flags).0,
+        (embree4_sys::RTCSceneFlags::COMPACT
+            | embree4_sys::RTCSceneFlags::FILTER_FUNCTION_IN_ARGUMENTS)
+            .0
+    );
+}
+
+#[test]
+fn attach_all_returns_ids_in_order() {
+    use crate::geometry::TriangleMeshGeometry;
+
+    let device = Device::try_new(None).unwrap();
+    let near = TriangleMeshGeometry::try_new(
+        &device,
+        &[(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)],
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+    let far = TriangleMeshGeometry::try_new(
+        &device,
+        &[(-1.0, -1.0, 2.0), (1.0, -1.0, 2.0), (0.0, 1.0, 2.0)],
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    let geometries: [&dyn Geometry; 2] = [&far, &near];
+    let geom_ids = scene.attach_all(geometries).unwrap();
+    assert_eq!(geom_ids, [0, 1]);
+
+    let scene = scene.commit().unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert_eq!(scene.intersect_1(ray).unwrap().unwrap().geom_id(), 1);
+}
+
+#[test]
+fn duplicate_shares_geometries() {
+    use crate::geometry::TriangleMeshGeometry;
+
+    let device = Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let near = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let far = TriangleMeshGeometry::try_new(
+        &device,
+        &vertices.map(|(x, y, z)| (x, y, z + 1.0)),
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+
+    let mut scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry_with_payload(&far, "far").unwrap();
+    scene.attach_geometry_by_id(&near, 7).unwrap();
+
+    let mut duplicate = scene.duplicate().unwrap();
+    duplicate.detach_geometry(7).unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let committed = scene.commit().unwrap();
+    assert_eq!(committed.intersect_1(ray).unwrap().unwrap().geom_id(), 7);
+    let committed = duplicate.commit().unwrap();
+    let hit = committed.intersect_1(ray).unwrap().unwrap();
+    assert_eq!(committed.payload::<&str>(&hit), Some(&"far"));
+}
+
+#[test]
+fn attached_geometry_data_outlives_wrapper() {
+    use crate::geometry::{Geometry, TriangleMeshGeometry};
+
+    let device = Device::try_new(None).unwrap();
+    let positions = vec![-1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 1.0, 1.0];
+    let mesh = TriangleMeshGeometry::try_from_vecs(&device, positions, vec![0, 1, 2]).unwrap();
+    let data = Arc::downgrade(&mesh.retained_data().unwrap());
+
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&mesh).unwrap();
+    drop(mesh);
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    assert!(scene.commit().unwrap().intersect_1(ray).unwrap().is_some());
+    drop(scene);
+    assert!(data.upgrade().is_none());
 }
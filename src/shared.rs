@@ -0,0 +1,165 @@
+//! Sharing a scene between threads which query it and a thread which updates it.
+
+use std::{
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use anyhow::Result;
+
+use crate::{CommittedScene, Scene};
+
+/// A scene which is queried by many threads while another thread updates it.
+///
+/// Queries run through read guards returned by [`SharedScene::read`], which may be held by any
+/// number of threads at once. [`SharedScene::update`] waits for all read guards to be dropped,
+/// modifies the scene and commits it before readers are let back in, so queries never observe
+/// an uncommitted scene. Expensive preparation, e.g. creating new geometries, should happen
+/// before calling [`SharedScene::update`], as queries are blocked while it runs.
+pub struct SharedScene<'a> {
+    scene: RwLock<Scene<'a>>,
+}
+
+impl<'a> SharedScene<'a> {
+    /// Commits the given scene and shares it.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to share.
+    ///
+    /// # Returns
+    /// A `Result` containing the `SharedScene` if the scene was committed successfully, or an
+    /// error if an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::{*, geometry::*};
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    /// let mesh = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    /// let scene = Scene::try_new(&device, SceneOptions::dynamic()).unwrap();
+    /// let geom_id = scene.attach_geometry(&mesh).unwrap();
+    /// let shared = SharedScene::try_new(scene).unwrap();
+    ///
+    /// std::thread::scope(|s| {
+    ///     for _ in 0..4 {
+    ///         s.spawn(|| {
+    ///             let scene = shared.read();
+    ///             let ray = Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    ///             let hit = scene.committed().intersect_1(ray).unwrap();
+    ///         });
+    ///     }
+    ///
+    ///     shared
+    ///         .update(|scene| scene.set_geometry_enabled(geom_id, false))
+    ///         .unwrap()
+    ///         .unwrap();
+    /// });
+    /// ```
+    pub fn try_new(scene: Scene<'a>) -> Result<Self> {
+        scene.commit()?;
+        Ok(Self {
+            scene: RwLock::new(scene),
+        })
+    }
+
+    /// Acquires read access to the committed scene, blocking while an update is in progress.
+    ///
+    /// # Returns
+    /// A guard, which releases read access when dropped.
+    pub fn read(&self) -> SceneReadGuard<'_, 'a> {
+        SceneReadGuard {
+            scene: self.scene.read().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    /// Modifies the scene and commits it, while no read guards are held.
+    ///
+    /// Blocks until all read guards have been dropped. The scene is committed after `update`
+    /// returns, even if it panics, in which case the panic is propagated after the commit.
+    ///
+    /// # Arguments
+    /// * `update` - Modifies the scene, e.g. attaches or detaches geometries.
+    ///
+    /// # Returns
+    /// A `Result` containing the return value of `update` if the scene was committed
+    /// successfully, or an error if an error occurred.
+    pub fn update<R>(&self, update: impl FnOnce(&mut Scene<'a>) -> R) -> Result<R> {
+        let mut scene = self.scene.write().unwrap_or_else(|e| e.into_inner());
+        // Commit even if the update panics, so readers never see an uncommitted scene
+        let result = catch_unwind(AssertUnwindSafe(|| update(&mut scene)));
+        let committed = scene.commit().map(|_| ());
+        match result {
+            Ok(value) => committed.map(|()| value),
+            Err(panic) => resume_unwind(panic),
+        }
+    }
+
+    /// Returns the shared scene.
+    pub fn into_inner(self) -> Scene<'a> {
+        self.scene.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Read access to a [`SharedScene`], see [`SharedScene::read`].
+pub struct SceneReadGuard<'s, 'a> {
+    scene: RwLockReadGuard<'s, Scene<'a>>,
+}
+
+impl<'s, 'a> SceneReadGuard<'s, 'a> {
+    /// Returns the committed scene to query.
+    pub fn committed(&self) -> CommittedScene<'_> {
+        CommittedScene { scene: &self.scene }
+    }
+}
+
+#[test]
+fn update_while_reading() {
+    use std::sync::Barrier;
+
+    use crate::geometry::TriangleMeshGeometry;
+
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let near = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let far = TriangleMeshGeometry::try_new(
+        &device,
+        &vertices.map(|(x, y, z)| (x, y, z + 1.0)),
+        &[(0, 1, 2)],
+    )
+    .unwrap();
+
+    let scene = Scene::try_new(&device, Default::default()).unwrap();
+    let far_id = scene.attach_geometry(&far).unwrap();
+    let shared = SharedScene::try_new(scene).unwrap();
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+
+    let barrier = Barrier::new(5);
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                barrier.wait();
+                for _ in 0..100 {
+                    let scene = shared.read();
+                    assert!(scene.committed().intersect_1(ray).unwrap().is_some());
+                }
+            });
+        }
+        barrier.wait();
+        shared
+            .update(|scene| scene.attach_geometry(&near))
+            .unwrap()
+            .unwrap();
+    });
+
+    let scene = shared.read();
+    let hit = scene.committed().intersect_1(ray).unwrap().unwrap();
+    assert_ne!(hit.geom_id(), far_id);
+}
+
+#[test]
+fn shared_scene_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedScene>();
+    assert_send_sync::<CommittedScene>();
+}
@@ -0,0 +1,380 @@
+use std::sync::atomic::AtomicBool;
+
+use anyhow::Result;
+
+use crate::{
+    batch::{is_cancelled, CANCEL_CHUNK_SIZE},
+    math::{dot, length, magnitude, offset_origin, scale, sub, SURFACE_EPSILON},
+    BatchStatus, CommittedScene, Hit, WorldHit,
+};
+
+impl<'a> CommittedScene<'a> {
+    /// Checks whether the segment between two points is occluded.
+    ///
+    /// The segment is shortened by `epsilon` at both ends, so surfaces the points lie on do not
+    /// occlude it. Use this for visibility tests between a shading point and a light sample.
+    ///
+    /// # Arguments
+    /// * `p0` - The start point of the segment.
+    /// * `p1` - The end point of the segment.
+    /// * `epsilon` - The distance excluded at both ends of the segment.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` if any geometry lies between the points, or an error if an
+    /// error occurred. Points closer than `2 * epsilon` are never occluded.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let visible = !scene
+    ///     .occluded_between((0.0, 0.0, 0.0), (0.0, 10.0, 0.0), 1e-4)
+    ///     .unwrap();
+    /// ```
+    pub fn occluded_between(
+        &self,
+        p0: (f32, f32, f32),
+        p1: (f32, f32, f32),
+        epsilon: f32,
+    ) -> Result<bool> {
+        match segment_ray(p0, p1, epsilon) {
+            Some(ray) => self.occluded_1(ray),
+            None => Ok(false),
+        }
+    }
+
+    /// Checks whether a light sample is occluded as seen from a hit.
+    ///
+    /// The shadow ray starts at the hit point, offset along the geometric normal towards the
+    /// light sample, and ends just before the sample. Both offsets scale with the magnitude of
+    /// the points, which avoids self-shadowing acne without missing nearby occluders.
+    ///
+    /// # Arguments
+    /// * `hit` - The hit to cast the shadow ray from, found in this scene.
+    /// * `light_point` - The light sample position.
+    ///
+    /// # Returns
+    /// A `Result` containing `true` if the light sample is occluded, or an error if the hit is
+    /// in a nested instance, see [`Hit::to_world`], or an error occurred.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    /// use embree4_sys::RTCRay;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let ray = RTCRay {
+    ///     dir_z: 1.0,
+    ///     ..Default::default()
+    /// };
+    /// if let Some(hit) = scene.intersect_1(ray).unwrap() {
+    ///     let lit = !scene.occluded_from_hit(&hit, (0.0, 10.0, 0.0)).unwrap();
+    /// }
+    /// ```
+    pub fn occluded_from_hit(&self, hit: &Hit, light_point: (f32, f32, f32)) -> Result<bool> {
+        let WorldHit {
+            position: point,
+            mut normal,
+        } = hit.to_world(self)?;
+        if dot(normal, sub(light_point, point)) < 0.0 {
+            normal = scale(normal, -1.0);
+        }
+
+        let origin = offset_origin(point, normal);
+        let epsilon = SURFACE_EPSILON * magnitude(point).max(magnitude(light_point));
+        self.occluded_between(origin, light_point, epsilon)
+    }
+
+    /// Computes the mutual visibility between every source and every target point.
+    ///
+    /// Traces one occlusion ray per pair, see [`CommittedScene::occluded_between`]. The rays are
+    /// traced in packets, see [`CommittedScene::occluded_auto`].
+    ///
+    /// # Arguments
+    /// * `sources` - The source points, one per matrix row.
+    /// * `targets` - The target points, one per matrix column.
+    /// * `epsilon` - The distance excluded at both ends of each segment.
+    /// * `cancel` - An optional cancellation token, checked before each chunk of rays.
+    ///
+    /// # Returns
+    /// A `Result` containing the visibility matrix and whether all pairs were traced, or an
+    /// error if an error occurred. If the computation was cancelled, pairs whose rays were not
+    /// traced are reported as not visible.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use embree4_rs::*;
+    ///
+    /// let device = Device::try_new(None).unwrap();
+    /// let scene = Scene::try_new(&device, Default::default()).unwrap();
+    /// let scene = scene.commit().unwrap();
+    ///
+    /// let sensors = [(0.0, 0.0, 0.0), (5.0, 0.0, 0.0)];
+    /// let targets = [(0.0, 10.0, 0.0), (5.0, 10.0, 0.0), (10.0, 10.0, 0.0)];
+    /// let (matrix, _) = scene
+    ///     .visibility_matrix(&sensors, &targets, 1e-4, None)
+    ///     .unwrap();
+    /// assert!(matrix.is_visible(1, 2));
+    /// ```
+    pub fn visibility_matrix(
+        &self,
+        sources: &[(f32, f32, f32)],
+        targets: &[(f32, f32, f32)],
+        epsilon: f32,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(VisibilityMatrix, BatchStatus)> {
+        let mut matrix = VisibilityMatrix {
+            rows: sources.len(),
+            cols: targets.len(),
+            bits: vec![0; (sources.len() * targets.len()).div_ceil(64)],
+        };
+
+        let pair_count = sources.len() * targets.len();
+        for start in (0..pair_count).step_by(CANCEL_CHUNK_SIZE) {
+            if is_cancelled(cancel) {
+                return Ok((matrix, BatchStatus::Cancelled));
+            }
+
+            let mut indices = Vec::with_capacity(CANCEL_CHUNK_SIZE);
+            let mut rays = Vec::with_capacity(CANCEL_CHUNK_SIZE);
+            for index in start..(start + CANCEL_CHUNK_SIZE).min(pair_count) {
+                let source = sources[index / targets.len()];
+                let target = targets[index % targets.len()];
+                match segment_ray(source, target, epsilon) {
+                    Some(ray) => {
+                        indices.push(index);
+                        rays.push(ray);
+                    }
+                    None => matrix.set_visible(index),
+                }
+            }
+
+            let occluded = self.occluded_auto(&rays)?;
+            for (index, occluded) in indices.into_iter().zip(occluded) {
+                if !occluded {
+                    matrix.set_visible(index);
+                }
+            }
+        }
+        Ok((matrix, BatchStatus::Completed))
+    }
+}
+
+/// The mutual visibility between sets of source and target points, see
+/// [`CommittedScene::visibility_matrix`].
+///
+/// Stores one bit per pair in row-major order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VisibilityMatrix {
+    rows: usize,
+    cols: usize,
+    bits: Vec<u64>,
+}
+
+impl VisibilityMatrix {
+    /// Returns the number of rows, i.e. source points.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the number of columns, i.e. target points.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns `true` if the target point `target` is visible from the source point `source`.
+    ///
+    /// # Panics
+    /// Panics if `source` or `target` is out of bounds.
+    pub fn is_visible(&self, source: usize, target: usize) -> bool {
+        assert!(
+            source < self.rows && target < self.cols,
+            "visibility matrix index ({}, {}) out of bounds ({}, {})",
+            source,
+            target,
+            self.rows,
+            self.cols
+        );
+        let index = self.index(source, target);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns the packed visibility bits. Bit `k % 64` of word `k / 64` holds the visibility
+    /// of pair `k = source * cols + target`.
+    pub fn as_bits(&self) -> &[u64] {
+        &self.bits
+    }
+
+    fn index(&self, source: usize, target: usize) -> usize {
+        source * self.cols + target
+    }
+
+    fn set_visible(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+}
+
+/// Builds the occlusion ray for the segment between two points, shortened by `epsilon` at
+/// both ends, or `None` if the points are closer than `2 * epsilon`.
+fn segment_ray(
+    p0: (f32, f32, f32),
+    p1: (f32, f32, f32),
+    epsilon: f32,
+) -> Option<embree4_sys::RTCRay> {
+    let delta = sub(p1, p0);
+    let distance = length(delta);
+    let tnear = epsilon;
+    let tfar = distance - epsilon;
+    if tfar <= tnear {
+        return None;
+    }
+
+    let direction = scale(delta, 1.0 / distance);
+    Some(embree4_sys::RTCRay {
+        org_x: p0.0,
+        org_y: p0.1,
+        org_z: p0.2,
+        tnear,
+        dir_x: direction.0,
+        dir_y: direction.1,
+        dir_z: direction.2,
+        tfar,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn occluded_between_segment() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    assert!(scene
+        .occluded_between((0.0, 0.0, 0.0), (0.0, 0.0, 2.0), 1e-4)
+        .unwrap());
+    // The end point lies on the triangle
+    assert!(!scene
+        .occluded_between((0.0, 0.0, 0.0), (0.0, 0.0, 1.0), 1e-4)
+        .unwrap());
+    assert!(!scene
+        .occluded_between((0.0, 0.0, 0.0), (0.0, 0.0, 1e-5), 1e-4)
+        .unwrap());
+}
+
+#[test]
+fn occluded_from_hit_no_self_shadowing() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [
+        (-1.0, -1.0, 1.0),
+        (1.0, -1.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (-1.0, -1.0, 3.0),
+        (1.0, -1.0, 3.0),
+        (0.0, 1.0, 3.0),
+    ];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2), (3, 4, 5)])
+            .unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert!(!scene.occluded_from_hit(&hit, (0.0, 0.0, -5.0)).unwrap());
+    assert!(!scene.occluded_from_hit(&hit, (0.0, 0.0, 2.0)).unwrap());
+    assert!(scene.occluded_from_hit(&hit, (0.0, 0.0, 4.0)).unwrap());
+}
+
+#[test]
+fn occluded_from_hit_rotated_instance() {
+    use crate::geometry::{InstanceGeometry, TriangleMeshGeometry};
+
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (0.0, 1.0, 0.0)];
+    let triangle = TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let object = crate::Scene::try_new(&device, Default::default()).unwrap();
+    object.attach_geometry(&triangle).unwrap();
+    let object = object.commit().unwrap();
+
+    // Rotated about the x axis, so the object-space z normal points along world y
+    let mut front = InstanceGeometry::try_new(&device, &object).unwrap();
+    front
+        .set_transform(
+            &device,
+            0,
+            &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 2.0],
+        )
+        .unwrap();
+    let mut back = InstanceGeometry::try_new(&device, &object).unwrap();
+    back.set_transform(
+        &device,
+        0,
+        &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 2.0, 2.0],
+    )
+    .unwrap();
+
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&front).unwrap();
+    scene.attach_geometry(&back).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let ray = crate::Ray::new((0.0, -5.0, 2.0), (0.0, 1.0, 0.0));
+    let hit = scene.intersect_1(ray).unwrap().unwrap();
+    assert_eq!(hit.t(), 5.0);
+    assert!(!scene.occluded_from_hit(&hit, (0.0, -5.0, 2.0)).unwrap());
+    assert!(scene.occluded_from_hit(&hit, (0.0, 5.0, 2.0)).unwrap());
+}
+
+#[test]
+fn visibility_matrix_pairs() {
+    let device = crate::Device::try_new(None).unwrap();
+    let vertices = [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (0.0, 1.0, 1.0)];
+    let geometry =
+        crate::geometry::TriangleMeshGeometry::try_new(&device, &vertices, &[(0, 1, 2)]).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    scene.attach_geometry(&geometry).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let sources = [(0.0, 0.0, 0.0), (5.0, 0.0, 0.0)];
+    let targets = [(0.0, 0.0, 2.0), (5.0, 0.0, 2.0), (0.0, 0.0, 0.0)];
+    let (matrix, status) = scene
+        .visibility_matrix(&sources, &targets, 1e-4, None)
+        .unwrap();
+    assert_eq!(status, BatchStatus::Completed);
+    assert_eq!((matrix.rows(), matrix.cols()), (2, 3));
+    assert!(!matrix.is_visible(0, 0));
+    assert!(matrix.is_visible(0, 1));
+    assert!(matrix.is_visible(0, 2));
+    assert!(matrix.is_visible(1, 0));
+    assert!(matrix.is_visible(1, 1));
+    assert!(matrix.is_visible(1, 2));
+    assert_eq!(matrix.as_bits(), [0b111110]);
+}
+
+#[test]
+fn visibility_matrix_cancelled() {
+    let device = crate::Device::try_new(None).unwrap();
+    let scene = crate::Scene::try_new(&device, Default::default()).unwrap();
+    let scene = scene.commit().unwrap();
+
+    let sources = [(0.0, 0.0, 0.0)];
+    let targets = [(0.0, 0.0, 2.0)];
+    let cancel = AtomicBool::new(true);
+    let (matrix, status) = scene
+        .visibility_matrix(&sources, &targets, 1e-4, Some(&cancel))
+        .unwrap();
+    assert_eq!(status, BatchStatus::Cancelled);
+    assert!(!matrix.is_visible(0, 0));
+}